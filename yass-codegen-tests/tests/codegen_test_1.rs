@@ -55,6 +55,24 @@ fn test_document_fail_unknown_field() {
     assert_eq!(gen::EmptyStruct::from_yass_document(&data_yass, &pos_map).unwrap_err(), expected_error);
 }
 
+#[test]
+fn test_document_fail_with_source() {
+    let mut source_map = yass::SourceMap::new();
+    let source = source_map.add("test.yass");
+    let field_pos = yass::Pos::with_source(1, 0, source);
+    
+    let data_yass = yass_document!(("test") "field": "test");
+    let mut field_pos_map = yass::PosMap::new();
+    field_pos_map.set_struct_field_span(yass::Span::new(field_pos, yass::Pos::with_source(1, 10, source), 7, 17));
+    let mut pos_map = yass::PosMap::new();
+    pos_map.push_child(field_pos_map);
+    
+    let error = gen::EmptyStruct::from_yass_document(&data_yass, &pos_map).unwrap_err();
+    assert_eq!(error.pos(), Some(field_pos));
+    assert_eq!(error.to_string(), "Unknown field \"field\" in struct \"empty-struct\" at 2:1");
+    assert_eq!(source_map.display(&error).to_string(), "Unknown field \"field\" in struct \"empty-struct\" at test.yass:2:1");
+}
+
 // struct
 #[test]
 fn test_struct_empty() {
@@ -167,7 +167,7 @@ impl<'a> CodeGen<'a> {
         code_writer.with_indent(|code_writer| {
             code_writer.add_line("if let yass::Value::Struct(ref fields) = *value {".to_string());
             code_writer.with_indent(|code_writer| {
-                code_writer.add_line("Self::from_yass_struct(fields.as_slice(), pos_map.get_value_pos(value), pos_map)".to_string());
+                code_writer.add_line("Self::from_yass_struct(fields.as_slice(), pos_map.get_value_pos(), pos_map)".to_string());
             });
             
             code_writer.add_line("} else {".to_string());
@@ -175,7 +175,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.add_line("Err(yass_schema_error::Error::InvalidValueTypeForStruct {".to_string());
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line(format!("struct_name: {:?}.to_string(),", struct_def.yass_name));
-                    code_writer.add_line("value_pos: pos_map.get_value_pos(value),".to_string());
+                    code_writer.add_line("value_pos: pos_map.get_value_pos(),".to_string());
                 });
                 code_writer.add_line("})".to_string());
             });
@@ -198,7 +198,8 @@ impl<'a> CodeGen<'a> {
                 });
                 code_writer.add_line("}".to_string());
                 code_writer.add_empty_line();
-                code_writer.add_line("Self::from_yass_struct(document.root_fields.as_slice(), Some(yass::Pos::new(0, 0)), pos_map)".to_string());
+                code_writer.add_line("let source = pos_map.get_header_span().map_or(yass::SourceId::UNKNOWN, |span| span.start.source);".to_string());
+                code_writer.add_line("Self::from_yass_struct(document.root_fields.as_slice(), Some(yass::Pos::with_source(0, 0, source)), pos_map)".to_string());
             });
             code_writer.add_line("}".to_string());
         }
@@ -221,8 +222,9 @@ impl<'a> CodeGen<'a> {
             }
             code_writer.add_empty_line();
             
-            code_writer.add_line("for field in fields {".to_string());
+            code_writer.add_line("for (i, field) in fields.iter().enumerate() {".to_string());
             code_writer.with_indent(|code_writer| {
+                code_writer.add_line("let field_pos_map = pos_map.child(i);".to_string());
                 code_writer.add_line("match field.key.as_str() {".to_string());
                 code_writer.with_indent(|code_writer| {
                     for field in struct_def.fields {
@@ -237,7 +239,7 @@ impl<'a> CodeGen<'a> {
                                         code_writer.with_indent(|code_writer| {
                                             code_writer.add_line(format!("struct_name: {:?}.to_string(),", struct_def.yass_name));
                                             code_writer.add_line(format!("field_name: {:?}.to_string(),", field.yass_name));
-                                            code_writer.add_line("field_pos: pos_map.get_struct_field_pos(&field.value),".to_string());
+                                            code_writer.add_line("field_pos: field_pos_map.get_struct_field_pos(),".to_string());
                                         });
                                         code_writer.add_line("});".to_string());
                                     });
@@ -245,7 +247,7 @@ impl<'a> CodeGen<'a> {
                                     code_writer.with_indent(|code_writer| {
                                         let prefix = format!("field_{} = Some(", field.code_name);
                                         let suffix = ");";
-                                        self.gen_from_yass_value(&field.type_, prefix.as_str(), suffix, "(*field.value)", code_writer);
+                                        self.gen_from_yass_value(&field.type_, prefix.as_str(), suffix, "(*field.value)", "field_pos_map", code_writer);
                                     });
                                     code_writer.add_line("}".to_string());
                                 }
@@ -253,7 +255,7 @@ impl<'a> CodeGen<'a> {
                                 schema::StructFieldMode::MultipleRequired => {
                                     let prefix = format!("fields_{}.push(", field.code_name);
                                     let suffix = ")";
-                                    self.gen_from_yass_value(&field.type_, prefix.as_str(), suffix, "(*field.value)", code_writer);
+                                    self.gen_from_yass_value(&field.type_, prefix.as_str(), suffix, "(*field.value)", "field_pos_map", code_writer);
                                 }
                             }
                         });
@@ -265,7 +267,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line(format!("struct_name: {:?}.to_string(),", struct_def.yass_name));
                             code_writer.add_line("field_name: field.key.clone(),".to_string());
-                            code_writer.add_line("field_pos: pos_map.get_struct_field_pos(&field.value),".to_string());
+                            code_writer.add_line("field_pos: field_pos_map.get_struct_field_pos(),".to_string());
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -385,7 +387,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line(format!("{:?} => {{", variant.yass_name));
                         code_writer.with_indent(|code_writer| {
                             let prefix = format!("Ok({}::{}(", tagged_union_def.code_name, variant.code_name);
-                            self.gen_from_yass_value(&variant.type_, prefix.as_str(), "))", "(**_vvalue)", code_writer);
+                            self.gen_from_yass_value(&variant.type_, prefix.as_str(), "))", "(**_vvalue)", "pos_map.child(0)", code_writer);
                         });
                         code_writer.add_line("}".to_string());
                     }
@@ -395,7 +397,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line(format!("tagged_union_name: {:?}.to_string(),", tagged_union_def.yass_name));
                             code_writer.add_line("variant_name: variant.clone(),".to_string());
-                            code_writer.add_line("value_pos: pos_map.get_struct_field_pos(value),".to_string());
+                            code_writer.add_line("value_pos: pos_map.get_struct_field_pos(),".to_string());
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -408,7 +410,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.add_line("Err(yass_schema_error::Error::InvalidValueTypeForTaggedUnion {".to_string());
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line(format!("tagged_union_name: {:?}.to_string(),", tagged_union_def.yass_name));
-                    code_writer.add_line("value_pos: pos_map.get_value_pos(value),".to_string());
+                    code_writer.add_line("value_pos: pos_map.get_value_pos(),".to_string());
                 });
                 code_writer.add_line("})".to_string());
             });
@@ -480,7 +482,7 @@ impl<'a> CodeGen<'a> {
                             code_writer.with_indent(|code_writer| {
                                 code_writer.add_line(format!("enum_name: {:?}.to_string(),", enum_def.yass_name));
                                 code_writer.add_line("value_name: atom.clone(),".to_string());
-                                code_writer.add_line("value_pos: pos_map.get_struct_field_pos(value),".to_string());
+                                code_writer.add_line("value_pos: pos_map.get_struct_field_pos(),".to_string());
                             });
                             code_writer.add_line("});".to_string());
                         });
@@ -494,7 +496,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.add_line("Err(yass_schema_error::Error::InvalidValueTypeForEnum {".to_string());
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line(format!("enum_name: {:?}.to_string(),", enum_def.yass_name));
-                    code_writer.add_line("value_pos: pos_map.get_value_pos(value),".to_string());
+                    code_writer.add_line("value_pos: pos_map.get_value_pos(),".to_string());
                 });
                 code_writer.add_line("})".to_string());
             });
@@ -602,7 +604,7 @@ impl<'a> CodeGen<'a> {
         }
     }
     
-    fn gen_from_yass_value(&self, type_: &schema::Type, prefix: &str, suffix: &str, value: &str, code_writer: &mut CodeWriter) {
+    fn gen_from_yass_value(&self, type_: &schema::Type, prefix: &str, suffix: &str, value: &str, pos_map: &str, code_writer: &mut CodeWriter) {
         match *type_ {
             schema::Type::RawAtom => {
                 code_writer.add_line(format!("{}if let yass::Value::Atom(ref atom) = {} {{", prefix, value));
@@ -613,7 +615,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForRawAtom {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -631,7 +633,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidBoolValue {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -641,7 +643,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForBool {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -659,7 +661,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidInt32Value {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -669,7 +671,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForInt32 {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -687,7 +689,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidUInt32Value {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -697,7 +699,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForUInt32 {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -715,7 +717,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidInt64Value {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -725,7 +727,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForInt64 {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -743,7 +745,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidUInt64Value {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -753,7 +755,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForUInt64 {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -771,7 +773,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidFloatValue {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -781,7 +783,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForFloat {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -799,7 +801,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidByteStringValue {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -809,7 +811,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForByteString {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -827,7 +829,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidUtf8StringValue {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -837,7 +839,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForUtf8String {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -855,7 +857,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.add_line("return Err(yass_schema_error::Error::InvalidAsciiStringValue {".to_string());
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("value: atom.clone(),".to_string());
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -865,7 +867,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForAsciiString {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -875,9 +877,10 @@ impl<'a> CodeGen<'a> {
                 code_writer.add_line(format!("{}if let yass::Value::Array(ref items) = {} {{", prefix, value));
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("let mut vec = Vec::with_capacity(items.len());".to_string());
-                    code_writer.add_line("for item in items {".to_string());
+                    code_writer.add_line("for (i, item) in items.iter().enumerate() {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        self.gen_from_yass_value(item_type, "vec.push(", ");", "(**item)", code_writer);
+                        code_writer.add_line(format!("let item_pos_map = {}.child(i);", pos_map));
+                        self.gen_from_yass_value(item_type, "vec.push(", ");", "(**item)", "item_pos_map", code_writer);
                     });
                     code_writer.add_line("}".to_string());
                     code_writer.add_line("vec".to_string());
@@ -886,7 +889,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForArray {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -901,7 +904,7 @@ impl<'a> CodeGen<'a> {
                         code_writer.with_indent(|code_writer| {
                             code_writer.add_line("num_elements: items.len(),".to_string());
                             code_writer.add_line(format!("num_expected: {},", item_types.len()));
-                            code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                            code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                         });
                         code_writer.add_line("});".to_string());
                     });
@@ -912,7 +915,8 @@ impl<'a> CodeGen<'a> {
                     for (i, item_type) in item_types.iter().enumerate() {
                         let prefix = format!("let item_{} = ", i);
                         let item_value = format!("(*items[{}])", i);
-                        self.gen_from_yass_value(item_type, prefix.as_str(), ";", item_value.as_str(), code_writer);
+                        let item_pos_map = format!("{}.child({})", pos_map, i);
+                        self.gen_from_yass_value(item_type, prefix.as_str(), ";", item_value.as_str(), item_pos_map.as_str(), code_writer);
                         
                         if i != 0 {
                             ret_str.push_str(", ");
@@ -927,7 +931,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForTuple {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -937,9 +941,10 @@ impl<'a> CodeGen<'a> {
                 code_writer.add_line(format!("{}if let yass::Value::Struct(ref items) = {} {{", prefix, value));
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("let mut vec = Vec::with_capacity(items.len());".to_string());
-                    code_writer.add_line("for item in items {".to_string());
+                    code_writer.add_line("for (i, item) in items.iter().enumerate() {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        self.gen_from_yass_value(item_type, "vec.push((item.key.clone(), ", "));", "(*item.value)", code_writer);
+                        code_writer.add_line(format!("let item_pos_map = {}.child(i);", pos_map));
+                        self.gen_from_yass_value(item_type, "vec.push((item.key.clone(), ", "));", "(*item.value)", "item_pos_map", code_writer);
                     });
                     code_writer.add_line("}".to_string());
                     code_writer.add_line("vec".to_string());
@@ -948,7 +953,7 @@ impl<'a> CodeGen<'a> {
                 code_writer.with_indent(|code_writer| {
                     code_writer.add_line("return Err(yass_schema_error::Error::InvalidValueTypeForDictionary {".to_string());
                     code_writer.with_indent(|code_writer| {
-                        code_writer.add_line(format!("value_pos: pos_map.get_value_pos(&{}),", value));
+                        code_writer.add_line(format!("value_pos: {}.get_value_pos(),", pos_map));
                     });
                     code_writer.add_line("});".to_string());
                 });
@@ -956,12 +961,12 @@ impl<'a> CodeGen<'a> {
             }
             schema::Type::Defined(ref type_name) => {
                 let code_name = self.type_def_name_map.get(type_name).unwrap();
-                code_writer.add_line(format!("{}{}::from_yass_value(&{}, pos_map)?{}", prefix, code_name, value, suffix));
+                code_writer.add_line(format!("{}{}::from_yass_value(&{}, {})?{}", prefix, code_name, value, pos_map, suffix));
             }
             schema::Type::Boxed(ref item_type) => {
                 let prefix = format!("{}Box::new(", prefix);
                 let suffix = format!("){}", suffix);
-                self.gen_from_yass_value(item_type, prefix.as_str(), suffix.as_str(), value, code_writer);
+                self.gen_from_yass_value(item_type, prefix.as_str(), suffix.as_str(), value, pos_map, code_writer);
             }
         }
     }
@@ -0,0 +1,307 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! `yass!` macro
+//!
+//! `yass!` takes YASS text written directly in Rust code and expands to
+//! a `yass::Document` or a `yass::Value`. The text is parsed at compile
+//! time with `yass_parser`, so syntax errors are compile errors.
+//!
+//! The input is a document if it begins with a header and it is a valid
+//! document. Otherwise, it must be a single value.
+//!
+//! `#{expr}` in place of a value inserts `yass::Value::from(expr)`.
+//! Floats have no `From` impl, since infinite and NaN ones have no atom,
+//! so use `#{yass::Value::float(x)}`, which panics on them, or
+//! `yass::Value::try_float` to handle the `None`.
+//!
+//! Since the input must also be valid Rust tokens, comments cannot be
+//! written and atoms cannot contain characters that are not allowed in
+//! Rust code, such as a single `'`.
+//!
+//! Whether two tokens are written together (as in `-2.5e3`) or separated
+//! by spaces is taken from their line and column, so Rust 1.88 or later
+//! is required. Token spacing cannot be recovered otherwise: `value-2` and
+//! `value - 2` produce the same tokens. For the same reason, when the input
+//! comes from another macro whose tokens do not keep their source layout
+//! (for example, all of them at `Span::call_site()`), tokens are taken as
+//! separated, so `-2.5e3` becomes the atoms `-` and `2.5e3`.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! #[macro_use]
+//! extern crate yass;
+//! #[macro_use]
+//! extern crate yass_macros;
+//!
+//! fn main() {
+//!     let size = 3;
+//!     let document = yass! {
+//!         (example)
+//!         key1 "value1"
+//!         key2 value-2
+//!         items [1 -2.5e3 #{size}]
+//!         point (xy){x 1 y 2}
+//!     };
+//!     assert_eq!(document, yass_document!(
+//!         ("example")
+//!         "key1": r#""value1""#,
+//!         "key2": "value-2",
+//!         "items": ["1", "-2.5e3", "3"],
+//!         "point": (as "xy": {"x": "1", "y": "2"})
+//!     ));
+//!     
+//!     let value = yass!([a (t)b {c #{"d"}}]);
+//!     assert_eq!(value, *yass_value!(["a", (as "t": "b"), {"c": r#""d""#}]));
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate yass;
+extern crate yass_parser;
+
+use proc_macro::{Delimiter, Span, TokenStream, TokenTree};
+
+/// Text given to `yass_parser`, built from the input tokens.
+struct Text {
+    text: String,
+    /// Byte range of each token in `text`.
+    tokens: Vec<(usize, usize, Span)>,
+    /// Offset of each `#{expr}` in `text` and its expression.
+    interpolations: Vec<(usize, proc_macro2::TokenStream)>,
+    /// `(line, column)` where the previous token ended.
+    last_end: Option<(usize, usize)>,
+}
+
+impl Text {
+    fn push_token(&mut self, text: &str, span: Span) {
+        // `Span::start` and `Span::end` are stable since Rust 1.88.
+        let start = span.start();
+        if let Some(last_end) = self.last_end {
+            if last_end != (start.line(), start.column()) {
+                self.text.push(' ');
+            }
+        }
+        let end = span.end();
+        self.last_end = Some((end.line(), end.column()));
+        
+        self.tokens.push((self.text.len(), self.text.len() + text.len(), span));
+        self.text.push_str(text);
+    }
+    
+    fn push_stream(&mut self, stream: TokenStream) {
+        let mut iter = stream.into_iter().peekable();
+        while let Some(token) = iter.next() {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::None => {
+                            self.push_stream(group.stream());
+                            continue;
+                        }
+                    };
+                    self.push_token(open, group.span_open());
+                    self.push_stream(group.stream());
+                    self.push_token(close, group.span_close());
+                }
+                TokenTree::Punct(ref punct) if punct.as_char() == '#' => {
+                    let is_interpolation = match iter.peek() {
+                        Some(TokenTree::Group(group)) => group.delimiter() == Delimiter::Brace,
+                        _ => false,
+                    };
+                    if is_interpolation {
+                        let group = match iter.next() {
+                            Some(TokenTree::Group(group)) => group,
+                            _ => unreachable!(),
+                        };
+                        // Force separation from neighbour tokens.
+                        self.last_end = None;
+                        self.text.push(' ');
+                        self.interpolations.push((self.text.len(), group.stream().into()));
+                        self.push_token("#", punct.span());
+                        self.last_end = None;
+                        self.text.push(' ');
+                    } else {
+                        self.push_token("#", punct.span());
+                    }
+                }
+                TokenTree::Punct(punct) => self.push_token(&punct.as_char().to_string(), punct.span()),
+                TokenTree::Ident(ident) => self.push_token(&ident.to_string(), ident.span()),
+                TokenTree::Literal(literal) => self.push_token(&literal.to_string(), literal.span()),
+            }
+        }
+    }
+    
+    /// Finds the span of the token at `offset`.
+    fn span_at(&self, offset: usize) -> Span {
+        for &(start, end, span) in self.tokens.iter() {
+            if offset < end || (offset == end && start == end) {
+                return span;
+            }
+        }
+        self.tokens.last().map_or(Span::call_site(), |&(_, _, span)| span)
+    }
+    
+    fn interpolation_at(&self, offset: usize) -> Option<&proc_macro2::TokenStream> {
+        self.interpolations.iter().find(|&&(i, _)| i == offset).map(|(_, expr)| expr)
+    }
+}
+
+struct Error {
+    msg: String,
+    offset: usize,
+}
+
+/// Expands the parsed tree. `base` is the offset of the text given by
+/// the user within the parsed text.
+struct Expander<'a> {
+    text: &'a Text,
+    base: usize,
+}
+
+impl<'a> Expander<'a> {
+    fn check_not_interpolated(&self, span: Option<yass::Span>) -> Result<(), Error> {
+        if let Some(span) = span {
+            let offset = span.start_offset - self.base;
+            if self.text.interpolation_at(offset).is_some() {
+                return Err(Error { msg: "Interpolation is only allowed in place of a value".to_string(), offset: offset });
+            }
+        }
+        Ok(())
+    }
+    
+    fn expand_value(&self, value: &yass::Value, pos_map: &yass::PosMap) -> Result<proc_macro2::TokenStream, Error> {
+        match *value {
+            yass::Value::Atom(ref atom) => {
+                let interpolation = pos_map.get_value_span()
+                    .and_then(|span| self.text.interpolation_at(span.start_offset - self.base));
+                match interpolation {
+                    Some(expr) => Ok(quote! { ::yass::Value::from(#expr) }),
+                    None => Ok(quote! { ::yass::Value::Atom(::std::string::String::from(#atom)) }),
+                }
+            }
+            yass::Value::Array(ref items) => {
+                let mut expanded_items = Vec::new();
+                for (i, item) in items.iter().enumerate() {
+                    let expanded = self.expand_value(item, pos_map.child(i))?;
+                    expanded_items.push(quote! { ::std::boxed::Box::new(#expanded) });
+                }
+                Ok(quote! { ::yass::Value::Array(vec![#(#expanded_items),*]) })
+            }
+            yass::Value::Tagged(ref tag, ref sub_value) => {
+                self.check_not_interpolated(pos_map.get_tag_span())?;
+                let expanded = self.expand_value(sub_value, pos_map.child(0))?;
+                Ok(quote! {
+                    ::yass::Value::Tagged(::std::string::String::from(#tag), ::std::boxed::Box::new(#expanded))
+                })
+            }
+            yass::Value::Struct(ref fields) => {
+                let expanded_fields = self.expand_fields(fields, pos_map)?;
+                Ok(quote! { ::yass::Value::Struct(vec![#(#expanded_fields),*]) })
+            }
+        }
+    }
+    
+    fn expand_fields(&self, fields: &[yass::StructField], pos_map: &yass::PosMap)
+        -> Result<Vec<proc_macro2::TokenStream>, Error>
+    {
+        let mut expanded_fields = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            let field_pos_map = pos_map.child(i);
+            self.check_not_interpolated(field_pos_map.get_key_span())?;
+            let key = &field.key;
+            let value = self.expand_value(&field.value, field_pos_map)?;
+            expanded_fields.push(quote! {
+                ::yass::StructField {
+                    key: ::std::string::String::from(#key),
+                    value: ::std::boxed::Box::new(#value),
+                }
+            });
+        }
+        Ok(expanded_fields)
+    }
+    
+    fn expand_document(&self, document: &yass::Document, pos_map: &yass::PosMap) -> Result<proc_macro2::TokenStream, Error> {
+        self.check_not_interpolated(pos_map.get_header_span())?;
+        let header = &document.header;
+        let expanded_fields = self.expand_fields(&document.root_fields, pos_map)?;
+        Ok(quote! {
+            ::yass::Document {
+                header: ::std::string::String::from(#header),
+                root_fields: vec![#(#expanded_fields),*],
+            }
+        })
+    }
+}
+
+fn parser_error(error: &yass_parser::ParserError, base: usize) -> Error {
+    // The position is meaningless in the macro input, the error is
+    // reported at the span of the token instead.
+    // Texts are written in a single line, so columns are offsets.
+    Error { msg: error.message(), offset: (error.pos().column as usize).saturating_sub(base) }
+}
+
+fn expand(text: &Text) -> Result<proc_macro2::TokenStream, Error> {
+    let limits = yass_parser::ParserLimits::unlimited();
+    
+    let document_error = if text.text.starts_with('(') {
+        match yass_parser::parse(limits, text.text.as_bytes()) {
+            Ok((document, pos_map)) => {
+                return Expander { text: text, base: 0 }.expand_document(&document, &pos_map);
+            }
+            Err(error) => Some(parser_error(&error, 0)),
+        }
+    } else {
+        None
+    };
+    
+    // Parse a value as the only field of a document.
+    const PREFIX: &str = "(value) value ";
+    let value_text = format!("{}{}", PREFIX, text.text);
+    match yass_parser::parse(limits, value_text.as_bytes()) {
+        Ok((mut document, pos_map)) => {
+            if document.root_fields.len() != 1 {
+                let offset = pos_map.child(1).get_key_span().map_or(0, |span| span.start_offset - PREFIX.len());
+                return Err(document_error.unwrap_or(Error { msg: "Expected a single value".to_string(), offset: offset }));
+            }
+            let value = document.root_fields.pop().unwrap().value;
+            Expander { text: text, base: PREFIX.len() }.expand_value(&value, pos_map.child(0))
+        }
+        Err(error) => Err(document_error.unwrap_or_else(|| parser_error(&error, PREFIX.len()))),
+    }
+}
+
+/// Expands YASS text to a `yass::Document` or a `yass::Value`.
+///
+/// See the crate documentation.
+#[proc_macro]
+pub fn yass(input: TokenStream) -> TokenStream {
+    let mut text = Text {
+        text: String::new(),
+        tokens: Vec::new(),
+        interpolations: Vec::new(),
+        last_end: None,
+    };
+    text.push_stream(input);
+    
+    match expand(&text) {
+        Ok(expanded) => expanded.into(),
+        Err(error) => {
+            let span = proc_macro2::Span::from(text.span_at(error.offset));
+            let msg = error.msg;
+            quote_spanned!(span=> compile_error!(#msg)).into()
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[macro_use]
+extern crate yass;
+#[macro_use]
+extern crate yass_macros;
+
+#[test]
+fn test_document() {
+    let document = yass! {
+        (example-1-widget)
+        debug true
+        window {
+            title "Sample Konfabulator Widget"
+            width 500
+        }
+        item (image){
+            src "Images/Sun.png"
+            hoffset -250
+        }
+        item (text){
+            on-mouse-up "sun1.opacity = (sun1.opacity / 100) * 90;"
+        }
+    };
+    assert_eq!(document, yass_document!(
+        ("example-1-widget")
+        "debug": "true",
+        "window": {"title": r#""Sample Konfabulator Widget""#, "width": "500"},
+        "item": (as "image": {"src": r#""Images/Sun.png""#, "hoffset": "-250"}),
+        "item": (as "text": {"on-mouse-up": r#""sun1.opacity = (sun1.opacity / 100) * 90;""#})
+    ));
+    
+    assert_eq!(yass!((empty)), yass_document!(("empty")));
+}
+
+#[test]
+fn test_value() {
+    assert_eq!(yass!(atom), *yass_value!("atom"));
+    assert_eq!(yass!("a \"b\"\n"), *yass_value!(r#""a \"b\"\n""#));
+    assert_eq!(yass!(1.5e-3), *yass_value!("1.5e-3"));
+    assert_eq!(yass!(-1), *yass_value!("-1"));
+    assert_eq!(yass!(a.b:c), *yass_value!("a.b:c"));
+    assert_eq!(yass!([]), *yass_value!([]));
+    assert_eq!(yass!({}), *yass_value!({}));
+    assert_eq!(yass!([[1 2] [3]]), *yass_value!([["1", "2"], ["3"]]));
+    assert_eq!(yass!((tag)value), *yass_value!((as "tag": "value")));
+    assert_eq!(yass!((tag) {a 1}), *yass_value!((as "tag": {"a": "1"})));
+    assert_eq!(yass!((a)(b)[c]), *yass_value!((as "a": (as "b": ["c"]))));
+}
+
+#[test]
+fn test_interpolation() {
+    let name = "example";
+    let values = yass::Value::Array(vec![Box::new(yass::Value::from(1)), Box::new(yass::Value::try_float(2.5).unwrap())]);
+    
+    let document = yass! {
+        (test)
+        name #{name}
+        count #{name.len() as u32}
+        values #{values.clone()}
+        nested [#{true}#{false} (t)#{-1i64}]
+    };
+    assert_eq!(document, yass_document!(
+        ("test")
+        "name": r#""example""#,
+        "count": "7",
+        "values": ["1", "2.5"],
+        "nested": ["true", "false", (as "t": "-1")]
+    ));
+    
+    assert_eq!(yass!(#{String::from("x")}), *yass_value!(r#""x""#));
+    assert_eq!(yass!({ key #{values} }), *yass_value!({"key": ["1", "2.5"]}));
+}
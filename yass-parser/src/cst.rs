@@ -0,0 +1,897 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Lossless concrete syntax tree.
+//!
+//! Unlike `yass_parser::parse`, `cst::parse` keeps every byte of the
+//! input: comments, line breaks, indentation and the original spelling
+//! of atoms are stored as trivia attached to the tokens. Writing a tree
+//! back with `Document::to_bytes` gives exactly the parsed text.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! extern crate yass_parser;
+//!
+//! fn main() {
+//!     let text = b"\\ comment\n(example)\nkey1 value1  \\ another comment\n";
+//!
+//!     let parser_limits = yass_parser::ParserLimits::unlimited();
+//!     let cst = yass_parser::cst::parse(parser_limits, text).unwrap();
+//!     assert_eq!(cst.to_bytes(), text.to_vec());
+//!
+//!     let document = cst.to_document();
+//!     assert_eq!(document.root_fields[0].key, "key1");
+//! }
+//! ```
+//!
+//! Editing
+//! -------
+//!
+//! ```
+//! extern crate yass;
+//! extern crate yass_parser;
+//!
+//! fn main() {
+//!     let text = b"(example)\nname \"x\"  \\ the name\nversion 1  \\ bump me\n";
+//!
+//!     let parser_limits = yass_parser::ParserLimits::unlimited();
+//!     let mut cst = yass_parser::cst::parse(parser_limits, text).unwrap();
+//!     let path = yass::Path::parse("version").unwrap();
+//!     cst.set(&path, &yass::Value::Atom("2".to_string())).unwrap();
+//!
+//!     let expected = b"(example)\nname \"x\"  \\ the name\nversion 2  \\ bump me\n";
+//!     assert_eq!(cst.to_bytes(), expected.to_vec());
+//! }
+//! ```
+
+use yass;
+
+use super::{Lexer, ParserError, ParserLimits, TokenKind};
+use reader::{Events, RawEvent, Tokens};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TriviaKind {
+    /// A sequence of spaces and tabulators.
+    Whitespace,
+    /// A single line break (`"\n"`, `"\r\n"` or `"\r"`).
+    LineBreak,
+    /// A comment, from the `\` to the end of the line (line break
+    /// not included).
+    Comment,
+}
+
+/// A piece of text without meaning that appears between tokens.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Vec<u8>,
+}
+
+impl Trivia {
+    #[inline]
+    pub fn whitespace(text: &str) -> Self {
+        Self { kind: TriviaKind::Whitespace, text: text.as_bytes().to_vec() }
+    }
+    
+    #[inline]
+    pub fn line_break(text: &str) -> Self {
+        Self { kind: TriviaKind::LineBreak, text: text.as_bytes().to_vec() }
+    }
+    
+    #[inline]
+    pub fn comment(text: &str) -> Self {
+        Self { kind: TriviaKind::Comment, text: text.as_bytes().to_vec() }
+    }
+}
+
+/// A token with the trivia that precedes it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub leading_trivia: Vec<Trivia>,
+    pub text: String,
+}
+
+impl Token {
+    /// Creates a token without leading trivia.
+    #[inline]
+    pub fn new(text: &str) -> Self {
+        Self { leading_trivia: Vec::new(), text: text.to_string() }
+    }
+    
+    #[inline]
+    pub fn with_trivia(leading_trivia: Vec<Trivia>, text: &str) -> Self {
+        Self { leading_trivia: leading_trivia, text: text.to_string() }
+    }
+    
+    pub fn write(&self, output: &mut Vec<u8>) {
+        write_trivia(&self.leading_trivia, output);
+        output.extend_from_slice(self.text.as_bytes());
+    }
+}
+
+fn write_trivia(trivia: &[Trivia], output: &mut Vec<u8>) {
+    for piece in trivia {
+        output.extend_from_slice(&piece.text);
+    }
+}
+
+/// A value with all its tokens.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Value {
+    Atom(Token),
+    Array(Array),
+    Tagged(Tagged),
+    Struct(Struct),
+}
+
+/// `[` items `]`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Array {
+    pub open: Token,
+    pub items: Vec<Value>,
+    pub close: Token,
+}
+
+/// `(` tag `)` value
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tagged {
+    pub open: Token,
+    pub tag: Token,
+    pub close: Token,
+    pub value: Box<Value>,
+}
+
+/// `{` fields `}`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Struct {
+    pub open: Token,
+    pub fields: Vec<StructField>,
+    pub close: Token,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StructField {
+    pub key: Token,
+    pub value: Value,
+}
+
+/// A full document. `trailing_trivia` holds everything after the last
+/// token.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Document {
+    pub open: Token,
+    pub header: Token,
+    pub close: Token,
+    pub root_fields: Vec<StructField>,
+    pub trailing_trivia: Vec<Trivia>,
+    /// Line break and indentation of new tokens, detected when the
+    /// document is parsed.
+    layout: Layout,
+}
+
+impl Value {
+    /// Converts to a `yass::Value`, dropping all trivia.
+    pub fn to_value(&self) -> Box<yass::Value> {
+        match *self {
+            Value::Atom(ref token) => Box::new(yass::Value::Atom(token.text.clone())),
+            Value::Array(ref array) => {
+                Box::new(yass::Value::Array(array.items.iter().map(|item| item.to_value()).collect()))
+            }
+            Value::Tagged(ref tagged) => {
+                Box::new(yass::Value::Tagged(tagged.tag.text.clone(), tagged.value.to_value()))
+            }
+            Value::Struct(ref struct_) => {
+                Box::new(yass::Value::Struct(struct_.fields.iter().map(|field| field.to_struct_field()).collect()))
+            }
+        }
+    }
+    
+    /// Converts from a `yass::Value`. Arrays and structs are laid out
+    /// one item per line, indented two spaces per level, like the
+    /// spaced style of `yass_serializer`.
+    #[inline]
+    pub fn from_value(value: &yass::Value, depth: usize) -> Self {
+        let layout = Layout::default();
+        Self::render(value, &layout, &layout.indent_unit.repeat(depth))
+    }
+    
+    /// Converts from a `yass::Value`, where `indent` is the indentation
+    /// of the line the value begins in.
+    fn render(value: &yass::Value, layout: &Layout, indent: &str) -> Self {
+        match *value {
+            yass::Value::Atom(ref atom) => Value::Atom(Token::new(atom)),
+            yass::Value::Array(ref array) => {
+                let item_indent = format!("{}{}", indent, layout.indent_unit);
+                let items = array.iter().map(|item| {
+                    let mut item = Self::render(item, layout, &item_indent);
+                    item.first_token_mut().leading_trivia = layout.line_break_and_indent(&item_indent);
+                    item
+                }).collect::<Vec<_>>();
+                let close = if items.is_empty() {
+                    Token::new("]")
+                } else {
+                    Token::with_trivia(layout.line_break_and_indent(indent), "]")
+                };
+                Value::Array(Array { open: Token::new("["), items: items, close: close })
+            }
+            yass::Value::Tagged(ref tag, ref sub_value) => {
+                Value::Tagged(Tagged {
+                    open: Token::new("("),
+                    tag: Token::new(tag),
+                    close: Token::new(")"),
+                    value: Box::new(Self::render(sub_value, layout, indent)),
+                })
+            }
+            yass::Value::Struct(ref fields) => {
+                let field_indent = format!("{}{}", indent, layout.indent_unit);
+                let fields = fields.iter().map(|field| {
+                    StructField::render(field, layout.line_break_and_indent(&field_indent), layout, &field_indent)
+                }).collect::<Vec<_>>();
+                let close = if fields.is_empty() {
+                    Token::new("}")
+                } else {
+                    Token::with_trivia(layout.line_break_and_indent(indent), "}")
+                };
+                Value::Struct(Struct { open: Token::new("{"), fields: fields, close: close })
+            }
+        }
+    }
+    
+    /// Gets the first token of the value, which holds the trivia that
+    /// precedes it.
+    pub fn first_token(&self) -> &Token {
+        match *self {
+            Value::Atom(ref token) => token,
+            Value::Array(ref array) => &array.open,
+            Value::Tagged(ref tagged) => &tagged.open,
+            Value::Struct(ref struct_) => &struct_.open,
+        }
+    }
+    
+    pub fn first_token_mut(&mut self) -> &mut Token {
+        match *self {
+            Value::Atom(ref mut token) => token,
+            Value::Array(ref mut array) => &mut array.open,
+            Value::Tagged(ref mut tagged) => &mut tagged.open,
+            Value::Struct(ref mut struct_) => &mut struct_.open,
+        }
+    }
+    
+    pub fn write(&self, output: &mut Vec<u8>) {
+        match *self {
+            Value::Atom(ref token) => token.write(output),
+            Value::Array(ref array) => {
+                array.open.write(output);
+                for item in array.items.iter() {
+                    item.write(output);
+                }
+                array.close.write(output);
+            }
+            Value::Tagged(ref tagged) => {
+                tagged.open.write(output);
+                tagged.tag.write(output);
+                tagged.close.write(output);
+                tagged.value.write(output);
+            }
+            Value::Struct(ref struct_) => {
+                struct_.open.write(output);
+                for field in struct_.fields.iter() {
+                    field.write(output);
+                }
+                struct_.close.write(output);
+            }
+        }
+    }
+}
+
+impl StructField {
+    #[inline]
+    pub fn to_struct_field(&self) -> yass::StructField {
+        yass::StructField {
+            key: self.key.text.clone(),
+            value: self.value.to_value(),
+        }
+    }
+    
+    /// Converts from a `yass::StructField`. `leading_trivia` is placed
+    /// before the key and `depth` is the indentation level of the field.
+    #[inline]
+    pub fn from_struct_field(field: &yass::StructField, leading_trivia: Vec<Trivia>, depth: usize) -> Self {
+        let layout = Layout::default();
+        Self::render(field, leading_trivia, &layout, &layout.indent_unit.repeat(depth))
+    }
+    
+    fn render(field: &yass::StructField, leading_trivia: Vec<Trivia>, layout: &Layout, indent: &str) -> Self {
+        let mut value = Value::render(&field.value, layout, indent);
+        value.first_token_mut().leading_trivia = vec![Trivia::whitespace(" ")];
+        Self {
+            key: Token::with_trivia(leading_trivia, &field.key),
+            value: value,
+        }
+    }
+    
+    pub fn write(&self, output: &mut Vec<u8>) {
+        self.key.write(output);
+        self.value.write(output);
+    }
+}
+
+impl Document {
+    /// Converts to a `yass::Document`, dropping all trivia.
+    pub fn to_document(&self) -> yass::Document {
+        yass::Document {
+            header: self.header.text.clone(),
+            root_fields: self.root_fields.iter().map(|field| field.to_struct_field()).collect(),
+        }
+    }
+    
+    /// Converts from a `yass::Document`, with one root field per line.
+    pub fn from_document(document: &yass::Document) -> Self {
+        Self {
+            open: Token::new("("),
+            header: Token::new(&document.header),
+            close: Token::new(")"),
+            root_fields: document.root_fields.iter().map(|field| {
+                StructField::from_struct_field(field, vec![Trivia::line_break("\n")], 0)
+            }).collect(),
+            trailing_trivia: Vec::new(),
+            layout: Layout::default(),
+        }
+    }
+    
+    pub fn write(&self, output: &mut Vec<u8>) {
+        self.open.write(output);
+        self.header.write(output);
+        self.close.write(output);
+        for field in self.root_fields.iter() {
+            field.write(output);
+        }
+        write_trivia(&self.trailing_trivia, output);
+    }
+    
+    #[inline]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write(&mut output);
+        output
+    }
+}
+
+// Editing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    NotFound {
+        path: yass::Path,
+    },
+    MultipleMatches {
+        path: yass::Path,
+        count: usize,
+    },
+    IsRoot,
+    NotAStruct {
+        path: yass::Path,
+    },
+    NotAnArray {
+        path: yass::Path,
+    },
+    IndexOutOfRange {
+        path: yass::Path,
+        index: usize,
+    },
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            EditError::NotFound { ref path } => {
+                write!(f, "No value at path \"{}\"", path)
+            }
+            EditError::MultipleMatches { ref path, count } => {
+                write!(f, "Path \"{}\" matches {} values", path, count)
+            }
+            EditError::IsRoot => {
+                write!(f, "The document root cannot be replaced or removed")
+            }
+            EditError::NotAStruct { ref path } => {
+                write!(f, "Value at path \"{}\" is not a struct", path)
+            }
+            EditError::NotAnArray { ref path } => {
+                write!(f, "Value at path \"{}\" is not an array", path)
+            }
+            EditError::IndexOutOfRange { ref path, index } => {
+                write!(f, "Index {} out of range in array at path \"{}\"", index, path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {
+    fn description(&self) -> &str {
+        match *self {
+            EditError::NotFound { .. } => "No value at path",
+            EditError::MultipleMatches { .. } => "Path matches several values",
+            EditError::IsRoot => "The document root cannot be replaced or removed",
+            EditError::NotAStruct { .. } => "Value is not a struct",
+            EditError::NotAnArray { .. } => "Value is not an array",
+            EditError::IndexOutOfRange { .. } => "Index out of range",
+        }
+    }
+}
+
+/// Editing functions. They only modify the tokens of the edited value,
+/// so `to_bytes` gives back the original text everywhere else. New
+/// tokens use the line break and indentation found in the document
+/// when it was parsed.
+impl Document {
+    /// Replaces the value at `path`, which must match exactly one value.
+    pub fn set(&mut self, path: &yass::Path, value: &yass::Value) -> Result<(), EditError> {
+        let index_path = self.resolve_one(path)?;
+        let (&index, parent_path) = index_path.split_last().ok_or(EditError::IsRoot)?;
+        let layout = self.layout.clone();
+        let parent = self.container_mut(parent_path).unwrap();
+        let indent = parent.child_indent(index);
+        let old_value = parent.child_value_mut(index);
+        let leading_trivia = std::mem::take(&mut old_value.first_token_mut().leading_trivia);
+        *old_value = Value::render(value, &layout, &indent);
+        old_value.first_token_mut().leading_trivia = leading_trivia;
+        Ok(())
+    }
+    
+    /// Appends a field at the end of the struct at `path`. An empty
+    /// path means the root of the document.
+    pub fn insert_field(&mut self, path: &yass::Path, key: &str, value: &yass::Value) -> Result<(), EditError> {
+        let index_path = self.resolve_one(path)?;
+        let layout = self.layout.clone();
+        let mut container = self.container_mut(&index_path).ok_or_else(|| EditError::NotAStruct { path: path.clone() })?;
+        let (leading_trivia, indent) = container.new_child_layout(&layout);
+        match container.children {
+            Children::Fields(fields) => {
+                let field = yass::StructField { key: key.to_string(), value: Box::new(value.clone()) };
+                fields.push(StructField::render(&field, leading_trivia, &layout, &indent));
+                Ok(())
+            }
+            _ => Err(EditError::NotAStruct { path: path.clone() }),
+        }
+    }
+    
+    /// Inserts an item in the array at `path`, before the item at
+    /// `index`. If `index` is the length of the array, the item is
+    /// appended.
+    pub fn insert_item(&mut self, path: &yass::Path, index: usize, value: &yass::Value) -> Result<(), EditError> {
+        let index_path = self.resolve_one(path)?;
+        let layout = self.layout.clone();
+        let mut container = self.container_mut(&index_path).ok_or_else(|| EditError::NotAnArray { path: path.clone() })?;
+        let (mut leading_trivia, indent) = container.new_child_layout(&layout);
+        match container.children {
+            Children::Items(items) => {
+                if index > items.len() {
+                    return Err(EditError::IndexOutOfRange { path: path.clone(), index: index });
+                }
+                if index == 0 && !items.is_empty() {
+                    // The new item takes the place of the old first item.
+                    std::mem::swap(&mut leading_trivia, &mut items[0].first_token_mut().leading_trivia);
+                }
+                let mut item = Value::render(value, &layout, &indent);
+                item.first_token_mut().leading_trivia = leading_trivia;
+                items.insert(index, item);
+                Ok(())
+            }
+            _ => Err(EditError::NotAnArray { path: path.clone() }),
+        }
+    }
+    
+    /// Removes the struct field or array item at `path`, which must match
+    /// exactly one value. Comments in the lines before a field, or after
+    /// it in the same line, are removed with it.
+    pub fn remove(&mut self, path: &yass::Path) -> Result<(), EditError> {
+        let index_path = self.resolve_one(path)?;
+        let (&index, parent_path) = index_path.split_last().ok_or(EditError::IsRoot)?;
+        let parent = self.container_mut(parent_path).unwrap();
+        match parent.children {
+            Children::Fields(fields) => {
+                let removed = fields.remove(index);
+                match fields.get_mut(index) {
+                    Some(next) => join_trivia(removed.key.leading_trivia, &mut next.key.leading_trivia),
+                    None => join_trivia(removed.key.leading_trivia, parent.end_trivia),
+                }
+            }
+            Children::Items(items) => {
+                let mut removed = items.remove(index);
+                let removed_trivia = std::mem::take(&mut removed.first_token_mut().leading_trivia);
+                match items.get_mut(index) {
+                    Some(next) => join_trivia(removed_trivia, &mut next.first_token_mut().leading_trivia),
+                    None => join_trivia(removed_trivia, parent.end_trivia),
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    fn resolve_one(&self, path: &yass::Path) -> Result<Vec<usize>, EditError> {
+        let mut matches = path.resolve_node(Node::Document(self));
+        match matches.len() {
+            0 => Err(EditError::NotFound { path: path.clone() }),
+            1 => Ok(matches.pop().unwrap()),
+            count => Err(EditError::MultipleMatches { path: path.clone(), count: count }),
+        }
+    }
+    
+    /// Gets the struct or array at `index_path`, as returned by
+    /// `yass::Path::resolve`.
+    fn container_mut<'a>(&'a mut self, index_path: &[usize]) -> Option<Container<'a>> {
+        let container = Container {
+            children: Children::Fields(&mut self.root_fields),
+            end_trivia: &mut self.trailing_trivia,
+            indent: String::new(),
+            is_root: true,
+        };
+        container.descend(index_path)
+    }
+}
+
+/// Replaces the leading trivia of the token that follows a removed node.
+fn join_trivia(removed: Vec<Trivia>, next: &mut Vec<Trivia>) {
+    match next.iter().position(|trivia| trivia.kind == TriviaKind::LineBreak) {
+        // The next token is in another line: drop the rest of the
+        // current line, but keep the end of the line of the previous
+        // token.
+        Some(i) => {
+            let mut joined = match removed.iter().position(|trivia| trivia.kind == TriviaKind::LineBreak) {
+                Some(j) => removed[.. j].to_vec(),
+                None => Vec::new(),
+            };
+            joined.extend(next.drain(i ..));
+            *next = joined;
+        }
+        // The next token is in the same line: it takes the place of the
+        // removed node.
+        None => *next = removed,
+    }
+}
+
+/// Gets the indentation of the line where a token is, given the one of
+/// the previous token.
+fn line_indent(leading_trivia: &[Trivia], previous: &str) -> String {
+    match leading_trivia.iter().rposition(|trivia| trivia.kind == TriviaKind::LineBreak) {
+        Some(i) => {
+            match leading_trivia.get(i + 1) {
+                Some(trivia) if trivia.kind == TriviaKind::Whitespace => String::from_utf8(trivia.text.clone()).unwrap(),
+                _ => String::new(),
+            }
+        }
+        None => previous.to_string(),
+    }
+}
+
+enum Children<'a> {
+    Fields(&'a mut Vec<StructField>),
+    Items(&'a mut Vec<Value>),
+}
+
+struct Container<'a> {
+    children: Children<'a>,
+    /// Leading trivia of the closing token.
+    end_trivia: &'a mut Vec<Trivia>,
+    /// Indentation of the line where the container begins.
+    indent: String,
+    is_root: bool,
+}
+
+impl<'a> Container<'a> {
+    fn from_value(value: &'a mut Value, index_path: &[usize], indent: String) -> Option<Self> {
+        match *value {
+            Value::Atom(_) => None,
+            Value::Array(ref mut array) => {
+                let container = Container {
+                    children: Children::Items(&mut array.items),
+                    end_trivia: &mut array.close.leading_trivia,
+                    indent: indent,
+                    is_root: false,
+                };
+                container.descend(index_path)
+            }
+            Value::Tagged(ref mut tagged) => {
+                let index_path = match index_path.split_first() {
+                    Some((&0, rest)) => rest,
+                    Some(_) => return None,
+                    None => index_path,
+                };
+                Self::from_value(&mut tagged.value, index_path, indent)
+            }
+            Value::Struct(ref mut struct_) => {
+                let container = Container {
+                    children: Children::Fields(&mut struct_.fields),
+                    end_trivia: &mut struct_.close.leading_trivia,
+                    indent: indent,
+                    is_root: false,
+                };
+                container.descend(index_path)
+            }
+        }
+    }
+    
+    fn descend(self, index_path: &[usize]) -> Option<Self> {
+        match index_path.split_first() {
+            None => Some(self),
+            Some((&index, rest)) => {
+                let indent = self.child_indent(index);
+                let value = match self.children {
+                    Children::Fields(fields) => &mut fields.get_mut(index)?.value,
+                    Children::Items(items) => items.get_mut(index)?,
+                };
+                Self::from_value(value, rest, indent)
+            }
+        }
+    }
+    
+    fn child_leading_trivia(&self, index: usize) -> &[Trivia] {
+        match self.children {
+            Children::Fields(ref fields) => &fields[index].key.leading_trivia,
+            Children::Items(ref items) => &items[index].first_token().leading_trivia,
+        }
+    }
+    
+    fn child_value_mut(self, index: usize) -> &'a mut Value {
+        match self.children {
+            Children::Fields(fields) => &mut fields[index].value,
+            Children::Items(items) => &mut items[index],
+        }
+    }
+    
+    fn num_children(&self) -> usize {
+        match self.children {
+            Children::Fields(ref fields) => fields.len(),
+            Children::Items(ref items) => items.len(),
+        }
+    }
+    
+    /// Gets the indentation of the line where the child at `index` begins.
+    fn child_indent(&self, index: usize) -> String {
+        let mut indent = self.indent.clone();
+        for i in 0 .. index + 1 {
+            indent = line_indent(self.child_leading_trivia(i), &indent);
+        }
+        indent
+    }
+    
+    /// Gets the leading trivia and the indentation for a child appended
+    /// at the end. Children go in their own lines if the existing ones
+    /// do, and so do the fields of an empty struct.
+    fn new_child_layout(&mut self, layout: &Layout) -> (Vec<Trivia>, String) {
+        let num_children = self.num_children();
+        let has_line_breaks = (0 .. num_children).any(|i| {
+            self.child_leading_trivia(i).iter().any(|trivia| trivia.kind == TriviaKind::LineBreak)
+        });
+        let is_empty_struct = num_children == 0 && match self.children {
+            Children::Fields(_) => true,
+            Children::Items(_) => false,
+        };
+        
+        if self.is_root {
+            (layout.line_break_and_indent(""), String::new())
+        } else if has_line_breaks {
+            let indent = self.child_indent(num_children - 1);
+            (layout.line_break_and_indent(&indent), indent)
+        } else if is_empty_struct {
+            if !self.end_trivia.iter().any(|trivia| trivia.kind == TriviaKind::LineBreak) {
+                *self.end_trivia = layout.line_break_and_indent(&self.indent);
+            }
+            let indent = format!("{}{}", self.indent, layout.indent_unit);
+            (layout.line_break_and_indent(&indent), indent)
+        } else if num_children == 0 {
+            (Vec::new(), self.indent.clone())
+        } else {
+            (vec![Trivia::whitespace(" ")], self.indent.clone())
+        }
+    }
+}
+
+/// Node of the tree in which paths are resolved.
+#[derive(Copy, Clone)]
+enum Node<'a> {
+    Document(&'a Document),
+    Value(&'a Value),
+}
+
+impl<'a> yass::PathNode<'a> for Node<'a> {
+    fn tagged(self) -> Option<(&'a str, Self)> {
+        match self {
+            Node::Value(&Value::Tagged(ref tagged)) => Some((tagged.tag.text.as_str(), Node::Value(&tagged.value))),
+            _ => None,
+        }
+    }
+    
+    fn children(self) -> Vec<(Option<&'a str>, Self)> {
+        match self {
+            Node::Document(document) => fields_children(&document.root_fields),
+            Node::Value(&Value::Struct(ref struct_)) => fields_children(&struct_.fields),
+            Node::Value(&Value::Array(ref array)) => {
+                array.items.iter().map(|item| (None, Node::Value(item))).collect()
+            }
+            Node::Value(_) => Vec::new(),
+        }
+    }
+}
+
+fn fields_children<'a>(fields: &'a [StructField]) -> Vec<(Option<&'a str>, Node<'a>)> {
+    fields.iter().map(|field| (Some(field.key.text.as_str()), Node::Value(&field.value))).collect()
+}
+
+/// Line break and indentation used for new tokens.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Layout {
+    line_break: String,
+    indent_unit: String,
+}
+
+impl Layout {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            line_break: "\n".to_string(),
+            indent_unit: "  ".to_string(),
+        }
+    }
+    
+    /// Takes the first line break of `data` and the indentation of its
+    /// first indented line.
+    fn detect(data: &[u8]) -> Self {
+        let mut layout = Self::default();
+        
+        if let Some(i) = data.iter().position(|&chr| chr == b'\n' || chr == b'\r') {
+            layout.line_break = if data[i] == b'\n' {
+                "\n"
+            } else if data.get(i + 1) == Some(&b'\n') {
+                "\r\n"
+            } else {
+                "\r"
+            }.to_string();
+        }
+        
+        for line in data.split(|&chr| chr == b'\n' || chr == b'\r') {
+            let indent_len = line.iter().take_while(|&&chr| chr == b' ' || chr == b'\t').count();
+            if indent_len != 0 && indent_len != line.len() {
+                layout.indent_unit = String::from_utf8(line[.. indent_len].to_vec()).unwrap();
+                break;
+            }
+        }
+        
+        layout
+    }
+    
+    fn line_break_and_indent(&self, indent: &str) -> Vec<Trivia> {
+        let mut trivia = vec![Trivia::line_break(&self.line_break)];
+        if !indent.is_empty() {
+            trivia.push(Trivia::whitespace(indent));
+        }
+        trivia
+    }
+}
+
+// Parser
+/// Lexer that keeps the tokens of the current event with the trivia
+/// that precedes them.
+struct TriviaLexer<'a> {
+    lexer: Lexer<'a>,
+    /// Tokens lexed since they were last taken by `parse`.
+    current: Vec<Token>,
+}
+
+impl<'a> Tokens for TriviaLexer<'a> {
+    type Error = ParserError;
+    
+    fn next_token(&mut self, _max_len: usize) -> Result<(TokenKind, yass::Span), ParserError> {
+        let mut trivia = Vec::new();
+        let token = self.lexer.get_token_with_trivia(&mut trivia)?;
+        self.current.push(Token::with_trivia(trivia, self.lexer.text(&token.span)));
+        Ok((token.kind, token.span))
+    }
+    
+    #[inline]
+    fn text(&self, span: &yass::Span) -> &str {
+        self.lexer.text(span)
+    }
+}
+
+/// Value that is being built by `parse`.
+enum Partial {
+    Array(Token, Vec<Value>),
+    Struct(Token, Vec<StructField>),
+    Field(Token),
+    Tag(Token, Token, Token),
+}
+
+/// Parses `data` keeping all trivia. Limits and errors are the same as
+/// with `yass_parser::parse`.
+pub fn parse(limits: ParserLimits, data: &[u8]) -> Result<Document, ParserError> {
+    let lexer = TriviaLexer { lexer: Lexer::new(data, yass::SourceId::UNKNOWN), current: Vec::new() };
+    let mut events = Events::new(limits, lexer);
+    
+    events.next_event()?;
+    let (open, header, close) = {
+        let mut tokens = events.tokens.current.drain(..);
+        (tokens.next().unwrap(), tokens.next().unwrap(), tokens.next().unwrap())
+    };
+    
+    let mut root_fields = Vec::new();
+    let mut stack = Vec::new();
+    let trailing_trivia = loop {
+        let event = events.next_event()?;
+        let mut tokens = events.tokens.current.drain(..);
+        let mut value = match event {
+            None => break tokens.next().unwrap().leading_trivia,
+            Some(RawEvent::Key(_)) => {
+                stack.push(Partial::Field(tokens.next().unwrap()));
+                continue;
+            }
+            Some(RawEvent::Tag(..)) => {
+                let open = tokens.next().unwrap();
+                let tag = tokens.next().unwrap();
+                let close = tokens.next().unwrap();
+                stack.push(Partial::Tag(open, tag, close));
+                continue;
+            }
+            Some(RawEvent::BeginArray(_)) => {
+                stack.push(Partial::Array(tokens.next().unwrap(), Vec::new()));
+                continue;
+            }
+            Some(RawEvent::BeginStruct(_)) => {
+                stack.push(Partial::Struct(tokens.next().unwrap(), Vec::new()));
+                continue;
+            }
+            Some(RawEvent::Atom(_)) => Value::Atom(tokens.next().unwrap()),
+            Some(RawEvent::EndArray(_)) => match stack.pop() {
+                Some(Partial::Array(open, items)) => {
+                    Value::Array(Array { open: open, items: items, close: tokens.next().unwrap() })
+                }
+                _ => unreachable!(),
+            },
+            Some(RawEvent::EndStruct(_)) => match stack.pop() {
+                Some(Partial::Struct(open, fields)) => {
+                    Value::Struct(Struct { open: open, fields: fields, close: tokens.next().unwrap() })
+                }
+                _ => unreachable!(),
+            },
+            Some(RawEvent::Header(_)) => unreachable!(),
+        };
+        
+        // Add the value to the partial value that contains it.
+        loop {
+            if let Some(&mut Partial::Array(_, ref mut items)) = stack.last_mut() {
+                items.push(value);
+                break;
+            }
+            
+            match stack.pop() {
+                Some(Partial::Tag(open, tag, close)) => {
+                    value = Value::Tagged(Tagged { open: open, tag: tag, close: close, value: Box::new(value) });
+                }
+                Some(Partial::Field(key)) => {
+                    let field = StructField { key: key, value: value };
+                    match stack.last_mut() {
+                        Some(&mut Partial::Struct(_, ref mut fields)) => fields.push(field),
+                        None => root_fields.push(field),
+                        _ => unreachable!(),
+                    }
+                    break;
+                }
+                _ => unreachable!(),
+            }
+        }
+    };
+    
+    Ok(Document {
+        open: open,
+        header: header,
+        close: close,
+        root_fields: root_fields,
+        trailing_trivia: trailing_trivia,
+        layout: Layout::detect(data),
+    })
+}
@@ -0,0 +1,250 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+use yass;
+
+use super::{Lexer, ParserError, ParserLimits, TokenKind};
+use reader::{Events, RawEvent};
+use tokenizer::Tokenizer;
+
+/// A `ParserError` with the context needed to explain it.
+///
+/// When displayed, it shows the error message, the line where the
+/// error was found with the offending token underlined, the tokens
+/// that would have been valid there and the brackets and braces that
+/// were left open.
+///
+/// ```
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let text = b"(example)\nkey {\n  a [1 2}\n";
+///     let error = yass_parser::parse(yass_parser::ParserLimits::unlimited(), text).unwrap_err();
+///     let diagnostic = yass_parser::Diagnostic::new(text, &error);
+///     assert_eq!(diagnostic.to_string(), "\
+/// Unexpected token RightBrace at 3:9
+/// 3 |   a [1 2}
+///   |         ^
+///   = expected one of: atom, `(`, `[`, `{`, `]`
+///   = note: unclosed `[` opened at 3:5");
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Diagnostic<'a> {
+    data: &'a [u8],
+    error: ParserError,
+    expected: Vec<TokenKind>,
+    unclosed: Vec<(TokenKind, yass::Pos)>,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Creates the diagnostic of `error`, which was found when parsing
+    /// `data`.
+    ///
+    /// The context is found parsing `data` again up to the position of
+    /// the error, so it is only available for the first syntax error.
+    /// If `data` has several documents, as in `parse_documents`, the
+    /// document where the error was found is parsed again.
+    pub fn new(data: &'a [u8], error: &ParserError) -> Self {
+        let error_pos = error.pos();
+        let mut lexer = Lexer::new(data, error_pos.source);
+        if let Some(span) = document_start(data, error_pos) {
+            lexer.i = span.start_offset;
+            lexer.pos = span.start;
+        }
+        let mut events = Events::new(ParserLimits::unlimited(), lexer);
+        let mut open = Vec::new();
+        let found_error = loop {
+            let expected = events.expected_tokens();
+            match events.next_event() {
+                Ok(Some(raw_event)) => {
+                    let start = raw_event.first_span().start;
+                    if (start.line, start.column) >= (error_pos.line, error_pos.column) {
+                        break None;
+                    }
+                    match raw_event {
+                        RawEvent::BeginArray(span) => open.push((TokenKind::LeftBracket, span.start)),
+                        RawEvent::BeginStruct(span) => open.push((TokenKind::LeftBrace, span.start)),
+                        RawEvent::EndArray(_) | RawEvent::EndStruct(_) => {
+                            open.pop();
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(None) => break None,
+                Err(e) => break Some((e, expected)),
+            }
+        };
+        
+        let mut expected = Vec::new();
+        let mut unclosed = Vec::new();
+        match *error {
+            ParserError::ExpectedToken { token_kind, .. } => {
+                expected.push(token_kind);
+            }
+            ParserError::UnexpectedToken { token_kind, .. } => {
+                if let Some((ref found_error, found_expected)) = found_error {
+                    if found_error == error {
+                        expected.extend_from_slice(found_expected);
+                        match token_kind {
+                            TokenKind::Eof => {
+                                unclosed = open;
+                                unclosed.reverse();
+                            }
+                            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                                unclosed.extend(open.pop());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        
+        Self {
+            data: data,
+            error: error.clone(),
+            expected: expected,
+            unclosed: unclosed,
+        }
+    }
+    
+    #[inline]
+    pub fn error(&self) -> &ParserError {
+        &self.error
+    }
+    
+    /// Returns the kinds of the tokens that would have been valid at
+    /// the position of the error.
+    #[inline]
+    pub fn expected(&self) -> &[TokenKind] {
+        &self.expected
+    }
+    
+    /// Returns the brackets and braces that were open at the position of
+    /// the error, innermost first, when the error is caused by them.
+    #[inline]
+    pub fn unclosed(&self) -> &[(TokenKind, yass::Pos)] {
+        &self.unclosed
+    }
+}
+
+/// Finds the first token of the last document of `data` that begins
+/// before `pos`, as in `parse_documents`.
+fn document_start(data: &[u8], pos: yass::Pos) -> Option<yass::Span> {
+    let mut events = Events::new_documents(ParserLimits::unlimited(), Lexer::new(data, pos.source));
+    let mut start = None;
+    loop {
+        match events.begin_document() {
+            Ok(Some(span)) if (span.start.line, span.start.column) < (pos.line, pos.column) => start = Some(span),
+            _ => return start,
+        }
+        
+        loop {
+            match events.next_event() {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(_) => return start,
+            }
+        }
+    }
+}
+
+fn token_name(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Eof => "end of input",
+        TokenKind::LeftParen => "`(`",
+        TokenKind::RightParen => "`)`",
+        TokenKind::LeftBracket => "`[`",
+        TokenKind::RightBracket => "`]`",
+        TokenKind::LeftBrace => "`{`",
+        TokenKind::RightBrace => "`}`",
+        TokenKind::Atom => "atom",
+    }
+}
+
+/// Returns the offset of the beginning of the line `line` and the line
+/// itself, without line break. Line breaks are counted as the lexer
+/// does.
+fn find_line(data: &[u8], line: u32) -> (usize, &[u8]) {
+    let mut begin = 0;
+    let mut i = 0;
+    let mut current_line = 0;
+    while current_line != line && i < data.len() {
+        match data[i] {
+            b'\n' => {
+                i += 1;
+                current_line += 1;
+                begin = i;
+            }
+            b'\r' => {
+                i += 1;
+                if i < data.len() && data[i] == b'\n' {
+                    i += 1;
+                }
+                current_line += 1;
+                begin = i;
+            }
+            _ => i += 1,
+        }
+    }
+    
+    let end = data[begin ..].iter().position(|&chr| chr == b'\n' || chr == b'\r').map_or(data.len(), |len| begin + len);
+    (begin, &data[begin .. end])
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        yass::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl<'a> yass::DisplayWithSourceMap for Diagnostic<'a> {
+    fn fmt_with_source_map(&self, f: &mut fmt::Formatter, source_map: Option<&yass::SourceMap>) -> fmt::Result {
+        self.error.fmt_with_source_map(f, source_map)?;
+        
+        let pos = self.error.pos();
+        let (line_begin, line) = find_line(self.data, pos.line);
+        let column = (pos.column as usize).min(line.len());
+        
+        // Underline the token at the position of the error.
+        let token_len = Tokenizer::new(&self.data[(line_begin + column) ..]).next()
+            .and_then(|token| if token.is_trivia() { None } else { Some(token.text.len()) })
+            .unwrap_or(1)
+            .min(line.len() - column)
+            .max(1);
+        
+        let line_number = (pos.line + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+        write!(f, "\n{} | {}", line_number, String::from_utf8_lossy(line))?;
+        write!(f, "\n{} | ", gutter)?;
+        // Keep the tabulators so the caret is aligned.
+        for &chr in &line[.. column] {
+            f.write_str(if chr == b'\t' { "\t" } else { " " })?;
+        }
+        f.write_str(&"^".repeat(token_len))?;
+        
+        if !self.expected.is_empty() {
+            let names: Vec<_> = self.expected.iter().map(|&kind| token_name(kind)).collect();
+            if names.len() == 1 {
+                write!(f, "\n{} = expected {}", gutter, names[0])?;
+            } else {
+                write!(f, "\n{} = expected one of: {}", gutter, names.join(", "))?;
+            }
+        }
+        
+        for &(kind, open_pos) in &self.unclosed {
+            write!(f, "\n{} = note: unclosed {} opened at {}", gutter, token_name(kind), open_pos.display(source_map))?;
+        }
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Documents split across several files.
+//!
+//! A value `(include)"path"` is replaced by a struct with the root
+//! fields of the document in `path`, which is resolved relative to the
+//! directory of the including file. The header of the included document
+//! is ignored. Included files can include other files, as long as no
+//! file includes itself, directly or indirectly.
+//!
+//! Example
+//! -------
+//!
+//! ```no_run
+//! extern crate yass;
+//! extern crate yass_parser;
+//!
+//! use yass_parser::include;
+//!
+//! fn main() {
+//!     // main.yass:
+//!     // (config)
+//!     // name example
+//!     // database (include)"database.yass"
+//!     let loaded = include::load_file(
+//!         yass_parser::ParserLimits::unlimited(),
+//!         include::IncludeLimits::unlimited(),
+//!         &"main.yass",
+//!     ).unwrap();
+//!     
+//!     // The fields of `database` come from the second loaded file.
+//!     let database = loaded.file_map.child(1).unwrap();
+//!     assert_eq!(loaded.files[database.file()].to_str(), Some("main.yass"));
+//!     assert_eq!(loaded.files[database.child(0).unwrap().file()].to_str(), Some("database.yass"));
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use super::{parse_file_with_source, ParseFileError, ParserLimits};
+
+/// Tag of the include directive.
+pub const INCLUDE_TAG: &str = "include";
+
+#[derive(Copy, Clone, Debug)]
+pub struct IncludeLimits {
+    /// Maximum nesting of included files. The root file has depth 0.
+    pub max_depth: usize,
+    /// Maximum number of resolved include directives.
+    pub max_includes: usize,
+}
+
+impl IncludeLimits {
+    #[inline]
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: usize::max_value(),
+            max_includes: usize::max_value(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file could not be opened, read or parsed.
+    File {
+        path: PathBuf,
+        error: ParseFileError,
+    },
+    /// The value of an include directive is not a string.
+    InvalidInclude {
+        file: PathBuf,
+        pos: Option<yass::Pos>,
+    },
+    /// `included` includes itself, directly or indirectly.
+    Cycle {
+        file: PathBuf,
+        pos: Option<yass::Pos>,
+        included: PathBuf,
+    },
+    TooDeep {
+        file: PathBuf,
+        pos: Option<yass::Pos>,
+    },
+    TooManyIncludes {
+        file: PathBuf,
+        pos: Option<yass::Pos>,
+    },
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            LoadError::File { ref path, ref error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::InvalidInclude { ref file, pos } => {
+                write_location(f, file, pos)?;
+                write!(f, "Include path must be a string")
+            }
+            LoadError::Cycle { ref file, pos, ref included } => {
+                write_location(f, file, pos)?;
+                write!(f, "Cyclic include of {}", included.display())
+            }
+            LoadError::TooDeep { ref file, pos } => {
+                write_location(f, file, pos)?;
+                write!(f, "Includes nested too deep")
+            }
+            LoadError::TooManyIncludes { ref file, pos } => {
+                write_location(f, file, pos)?;
+                write!(f, "Too many includes")
+            }
+        }
+    }
+}
+
+fn write_location(f: &mut std::fmt::Formatter, file: &Path, pos: Option<yass::Pos>) -> std::fmt::Result {
+    match pos {
+        Some(pos) => write!(f, "{}:{}:{}: ", file.display(), pos.line + 1, pos.column + 1),
+        None => write!(f, "{}: ", file.display()),
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::File { .. } => "Failed to load file",
+            LoadError::InvalidInclude { .. } => "Invalid include",
+            LoadError::Cycle { .. } => "Cyclic include",
+            LoadError::TooDeep { .. } => "Includes nested too deep",
+            LoadError::TooManyIncludes { .. } => "Too many includes",
+        }
+    }
+    
+    fn cause(&self) -> Option<&std::error::Error> {
+        match *self {
+            LoadError::File { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Maps each node of a loaded document with the file it was written in.
+///
+/// It has the same shape as a `PosMap`. The spans of a node in the
+/// `PosMap` of the loaded document refer to the text of its file. An
+/// included struct is written in the including file (its spans are the
+/// ones of the directive), while its fields come from the included file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FileMap {
+    file: usize,
+    children: Vec<FileMap>,
+}
+
+impl FileMap {
+    /// Index of the file in `Loaded::files`.
+    #[inline]
+    pub fn file(&self) -> usize {
+        self.file
+    }
+    
+    /// Gets the map of the child with the given index.
+    #[inline]
+    pub fn child(&self, index: usize) -> Option<&FileMap> {
+        self.children.get(index)
+    }
+    
+    /// Gets the map of a descendant, following `index_path` from this
+    /// map.
+    pub fn get(&self, index_path: &[usize]) -> Option<&FileMap> {
+        let mut current = self;
+        for &index in index_path {
+            current = current.child(index)?;
+        }
+        Some(current)
+    }
+    
+    #[inline]
+    pub fn children(&self) -> &[FileMap] {
+        &self.children
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Loaded {
+    /// The document with all includes resolved.
+    pub document: yass::Document,
+    pub pos_map: yass::PosMap,
+    /// Loaded files. The first one is the root file. A file included
+    /// several times (without cycles) appears only once.
+    pub files: Vec<PathBuf>,
+    pub file_map: FileMap,
+    /// Names the sources of the positions in `pos_map`. The file with
+    /// index `i` in `files` has source `SourceId(i + 1)`.
+    pub source_map: yass::SourceMap,
+}
+
+struct Loader {
+    parser_limits: ParserLimits,
+    include_limits: IncludeLimits,
+    files: Vec<PathBuf>,
+    canonical_files: Vec<PathBuf>,
+    source_map: yass::SourceMap,
+    stack: Vec<usize>,
+    num_includes: usize,
+}
+
+impl Loader {
+    fn add_file(&mut self, path: PathBuf) -> Result<usize, LoadError> {
+        let canonical = match std::fs::canonicalize(&path) {
+            Ok(canonical) => canonical,
+            Err(io_error) => return Err(LoadError::File { path: path, error: ParseFileError::OpenError(io_error) }),
+        };
+        match self.canonical_files.iter().position(|file| *file == canonical) {
+            Some(index) => Ok(index),
+            None => {
+                self.source_map.add(path.display().to_string());
+                self.files.push(path);
+                self.canonical_files.push(canonical);
+                Ok(self.files.len() - 1)
+            }
+        }
+    }
+    
+    fn load(&mut self, file: usize) -> Result<(yass::Document, yass::PosMap, FileMap), LoadError> {
+        let path = self.files[file].clone();
+        let (_, mut document, mut pos_map) = parse_file_with_source(self.parser_limits, &path, yass::SourceId(file as u32 + 1))
+            .map_err(|e| LoadError::File { path: path.clone(), error: e })?;
+        
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.stack.push(file);
+        let mut file_map = FileMap { file: file, children: Vec::new() };
+        for (i, field) in document.root_fields.iter_mut().enumerate() {
+            let child = self.resolve(&mut field.value, &mut pos_map.children_mut()[i], file, &dir)?;
+            file_map.children.push(child);
+        }
+        self.stack.pop();
+        
+        Ok((document, pos_map, file_map))
+    }
+    
+    fn resolve(&mut self, value: &mut yass::Value, pos_map: &mut yass::PosMap, file: usize, dir: &Path)
+        -> Result<FileMap, LoadError>
+    {
+        let mut file_map = FileMap { file: file, children: Vec::new() };
+        let include = match *value {
+            yass::Value::Atom(_) => None,
+            yass::Value::Array(ref mut items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    let child = self.resolve(item, &mut pos_map.children_mut()[i], file, dir)?;
+                    file_map.children.push(child);
+                }
+                None
+            }
+            yass::Value::Tagged(ref tag, ref sub_value) if tag == INCLUDE_TAG => {
+                match sub_value.as_str_unescaped() {
+                    Ok(include_path) => Some(dir.join(include_path)),
+                    Err(_) => {
+                        return Err(LoadError::InvalidInclude {
+                            file: self.files[file].clone(),
+                            pos: pos_map.child(0).get_value_pos(),
+                        });
+                    }
+                }
+            }
+            yass::Value::Tagged(_, ref mut sub_value) => {
+                let child = self.resolve(sub_value, &mut pos_map.children_mut()[0], file, dir)?;
+                file_map.children.push(child);
+                None
+            }
+            yass::Value::Struct(ref mut fields) => {
+                for (i, field) in fields.iter_mut().enumerate() {
+                    let child = self.resolve(&mut field.value, &mut pos_map.children_mut()[i], file, dir)?;
+                    file_map.children.push(child);
+                }
+                None
+            }
+        };
+        
+        if let Some(include_path) = include {
+            let pos = pos_map.get_value_pos();
+            if self.num_includes >= self.include_limits.max_includes {
+                return Err(LoadError::TooManyIncludes { file: self.files[file].clone(), pos: pos });
+            }
+            if self.stack.len() > self.include_limits.max_depth {
+                return Err(LoadError::TooDeep { file: self.files[file].clone(), pos: pos });
+            }
+            self.num_includes += 1;
+            
+            let included = self.add_file(include_path)?;
+            if self.stack.contains(&included) {
+                return Err(LoadError::Cycle {
+                    file: self.files[file].clone(),
+                    pos: pos,
+                    included: self.files[included].clone(),
+                });
+            }
+            
+            let (document, included_pos_map, included_file_map) = self.load(included)?;
+            *value = yass::Value::Struct(document.root_fields);
+            *pos_map.children_mut() = included_pos_map.children().to_vec();
+            file_map.children = included_file_map.children;
+        }
+        
+        Ok(file_map)
+    }
+}
+
+/// Parses the file in `file_path` and resolves its include directives.
+pub fn load_file<P: AsRef<Path>>(parser_limits: ParserLimits, include_limits: IncludeLimits, file_path: &P)
+    -> Result<Loaded, LoadError>
+{
+    let mut loader = Loader {
+        parser_limits: parser_limits,
+        include_limits: include_limits,
+        files: Vec::new(),
+        canonical_files: Vec::new(),
+        source_map: yass::SourceMap::new(),
+        stack: Vec::new(),
+        num_includes: 0,
+    };
+    let root = loader.add_file(file_path.as_ref().to_path_buf())?;
+    let (document, pos_map, file_map) = loader.load(root)?;
+    Ok(Loaded {
+        document: document,
+        pos_map: pos_map,
+        files: loader.files,
+        file_map: file_map,
+        source_map: loader.source_map,
+    })
+}
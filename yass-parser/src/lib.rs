@@ -42,6 +42,20 @@ extern crate yass;
 #[cfg(test)]
 mod tests;
 
+pub mod cst;
+pub mod include;
+pub mod tokenizer;
+mod diagnostic;
+mod reader;
+mod recover;
+mod stream;
+
+pub use diagnostic::Diagnostic;
+pub use reader::{Event, Reader};
+pub use recover::{parse_recovering, parse_recovering_with_source};
+pub use stream::{parse_reader, parse_reader_with_source, StreamReader};
+use reader::{Events, RawEvent, Tokens};
+
 // Token
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TokenKind {
@@ -58,7 +72,7 @@ pub enum TokenKind {
 struct Token<'a> {
     kind: TokenKind,
     data: &'a [u8],
-    pos: yass::Pos,
+    span: yass::Span,
 }
 
 // Parser
@@ -107,47 +121,56 @@ pub enum ParserError {
     },
 }
 
-impl std::fmt::Display for ParserError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl ParserError {
+    /// Gets the position where the error was found.
+    pub fn pos(&self) -> yass::Pos {
         match *self {
-            ParserError::IllegalChr { pos, chr } => {
-                write!(f, "Illegal character 0x{:02X} at {}:{}", chr, pos.line + 1, pos.column + 1)
-            }
-            ParserError::IllegalChrInString { pos, chr } => {
-                write!(f, "Illegal character 0x{:02X} in string at {}:{}", chr, pos.line + 1, pos.column + 1)
-            }
-            ParserError::UnfinishedString { pos } => {
-                write!(f, "Unfinished string at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::IllegalChrAfterAtom { pos, chr } => {
-                write!(f, "Illegal character 0x{:02X} after atom at {}:{}", chr, pos.line + 1, pos.column + 1)
-            }
-            ParserError::UnexpectedToken { pos, token_kind } => {
-                write!(f, "Unexpected token {:?} at {}:{}", token_kind, pos.line + 1, pos.column + 1)
-            }
-            ParserError::ExpectedToken { pos, token_kind } => {
-                write!(f, "Expected token {:?} at {}:{}", token_kind, pos.line + 1, pos.column + 1)
-            }
-            ParserError::TooDeep { pos } => {
-                write!(f, "Maximum depth exceeded at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::AtomTooLong { pos } => {
-                write!(f, "Atom too long at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::TagTooLong { pos } => {
-                write!(f, "Tag too long at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::KeyTooLong { pos } => {
-                write!(f, "Struct key too long at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::ArrayTooBig { pos } => {
-                write!(f, "Array too big at {}:{}", pos.line + 1, pos.column + 1)
-            }
-            ParserError::StructTooBig { pos } => {
-                write!(f, "Struct too big at {}:{}", pos.line + 1, pos.column + 1)
-            }
+            ParserError::IllegalChr { pos, .. } |
+            ParserError::IllegalChrInString { pos, .. } |
+            ParserError::UnfinishedString { pos } |
+            ParserError::IllegalChrAfterAtom { pos, .. } |
+            ParserError::UnexpectedToken { pos, .. } |
+            ParserError::ExpectedToken { pos, .. } |
+            ParserError::TooDeep { pos } |
+            ParserError::AtomTooLong { pos } |
+            ParserError::TagTooLong { pos } |
+            ParserError::KeyTooLong { pos } |
+            ParserError::ArrayTooBig { pos } |
+            ParserError::StructTooBig { pos } => pos,
         }
     }
+    
+    /// Gets the message of the error, like the `Display` output but
+    /// without the position.
+    pub fn message(&self) -> String {
+        match *self {
+            ParserError::IllegalChr { chr, .. } => format!("Illegal character 0x{:02X}", chr),
+            ParserError::IllegalChrInString { chr, .. } => format!("Illegal character 0x{:02X} in string", chr),
+            ParserError::UnfinishedString { .. } => "Unfinished string".to_string(),
+            ParserError::IllegalChrAfterAtom { chr, .. } => format!("Illegal character 0x{:02X} after atom", chr),
+            ParserError::UnexpectedToken { token_kind, .. } => format!("Unexpected token {:?}", token_kind),
+            ParserError::ExpectedToken { token_kind, .. } => format!("Expected token {:?}", token_kind),
+            ParserError::TooDeep { .. } => "Maximum depth exceeded".to_string(),
+            ParserError::AtomTooLong { .. } => "Atom too long".to_string(),
+            ParserError::TagTooLong { .. } => "Tag too long".to_string(),
+            ParserError::KeyTooLong { .. } => "Struct key too long".to_string(),
+            ParserError::ArrayTooBig { .. } => "Array too big".to_string(),
+            ParserError::StructTooBig { .. } => "Struct too big".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParserError {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        yass::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl yass::DisplayWithSourceMap for ParserError {
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&yass::SourceMap>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message(), self.pos().display(source_map))
+    }
 }
 
 impl std::error::Error for ParserError {
@@ -193,183 +216,450 @@ impl ParserLimits {
     }
 }
 
-struct Parser<'a> {
-    limits: ParserLimits,
-    lexer: Lexer<'a>,
-    depth: usize,
-    pos_map: yass::PosMap,
+/// Builds the document produced by `build`, taking the text of the
+/// tokens from `T`.
+trait Builder<T> {
+    type Value;
+    type Items;
+    type Fields;
+    type Document;
+    /// Text of an atom, key, tag or header.
+    type Text;
+    
+    fn text(&mut self, tokens: &T, span: &yass::Span) -> Self::Text;
+    fn atom(&mut self, atom: Self::Text, span: yass::Span) -> Self::Value;
+    fn new_items(&mut self) -> Self::Items;
+    fn push_item(&mut self, items: &mut Self::Items, item: Self::Value);
+    fn array(&mut self, items: Self::Items, span: yass::Span) -> Self::Value;
+    fn tagged(&mut self, tag: Self::Text, tag_span: yass::Span, value: Self::Value, span: yass::Span) -> Self::Value;
+    fn new_fields(&mut self) -> Self::Fields;
+    fn push_field(&mut self, fields: &mut Self::Fields, key: Self::Text, key_span: yass::Span, value: Self::Value, span: yass::Span);
+    fn struct_value(&mut self, fields: Self::Fields, span: yass::Span) -> Self::Value;
+    fn document(&mut self, header: Self::Text, header_span: yass::Span, root_fields: Self::Fields) -> Self::Document;
 }
 
-impl<'a> Parser<'a> {
+/// Builds a `yass::Document` and its `yass::PosMap`.
+struct OwnedBuilder;
+
+impl<T: Tokens> Builder<T> for OwnedBuilder {
+    type Value = (Box<yass::Value>, yass::PosMap);
+    type Items = (Vec<Box<yass::Value>>, yass::PosMap);
+    type Fields = (Vec<yass::StructField>, yass::PosMap);
+    type Document = (yass::Document, yass::PosMap);
+    type Text = String;
+    
     #[inline]
-    fn parse(limits: ParserLimits, data: &'a [u8]) -> Result<(yass::Document, yass::PosMap), ParserError> {
-        let mut parser = Self {
-            limits: limits,
-            lexer: Lexer::new(data),
-            depth: 0,
-            pos_map: yass::PosMap::new(),
-        };
-        
-        let token = parser.lexer.get_token()?;
-        if token.kind != TokenKind::LeftParen {
-            return Err(ParserError::ExpectedToken { pos: token.pos, token_kind: TokenKind::LeftParen });
-        }
-        
-        let token = parser.lexer.get_token()?;
-        if token.kind != TokenKind::Atom {
-            return Err(ParserError::ExpectedToken { pos: token.pos, token_kind: TokenKind::Atom });
-        }
-        let header = std::str::from_utf8(token.data).unwrap().to_string();
-        
-        let token = parser.lexer.get_token()?;
-        if token.kind != TokenKind::RightParen {
-            return Err(ParserError::ExpectedToken { pos: token.pos, token_kind: TokenKind::RightParen });
-        }
-        
-        let mut root_fields = Vec::new();
-        loop {
-            let token = parser.lexer.get_token()?;
-            if token.kind == TokenKind::Eof {
-                break;
+    fn text(&mut self, tokens: &T, span: &yass::Span) -> Self::Text {
+        tokens.text(span).to_string()
+    }
+    
+    #[inline]
+    fn atom(&mut self, atom: Self::Text, span: yass::Span) -> Self::Value {
+        let mut pos_map = yass::PosMap::new();
+        pos_map.set_value_span(span);
+        (Box::new(yass::Value::Atom(atom)), pos_map)
+    }
+    
+    #[inline]
+    fn new_items(&mut self) -> Self::Items {
+        (Vec::new(), yass::PosMap::new())
+    }
+    
+    #[inline]
+    fn push_item(&mut self, items: &mut Self::Items, item: Self::Value) {
+        items.0.push(item.0);
+        items.1.push_child(item.1);
+    }
+    
+    #[inline]
+    fn array(&mut self, items: Self::Items, span: yass::Span) -> Self::Value {
+        let (items, mut pos_map) = items;
+        pos_map.set_value_span(span);
+        (Box::new(yass::Value::Array(items)), pos_map)
+    }
+    
+    #[inline]
+    fn tagged(&mut self, tag: Self::Text, tag_span: yass::Span, value: Self::Value, span: yass::Span) -> Self::Value {
+        let (sub_value, sub_pos_map) = value;
+        let mut pos_map = yass::PosMap::new();
+        pos_map.set_value_span(span);
+        pos_map.set_tag_span(tag_span);
+        pos_map.push_child(sub_pos_map);
+        (Box::new(yass::Value::Tagged(tag, sub_value)), pos_map)
+    }
+    
+    #[inline]
+    fn new_fields(&mut self) -> Self::Fields {
+        (Vec::new(), yass::PosMap::new())
+    }
+    
+    #[inline]
+    fn push_field(&mut self, fields: &mut Self::Fields, key: Self::Text, key_span: yass::Span, value: Self::Value, span: yass::Span) {
+        let (value, mut pos_map) = value;
+        pos_map.set_struct_field_span(span);
+        pos_map.set_key_span(key_span);
+        fields.0.push(yass::StructField { key: key, value: value });
+        fields.1.push_child(pos_map);
+    }
+    
+    #[inline]
+    fn struct_value(&mut self, fields: Self::Fields, span: yass::Span) -> Self::Value {
+        let (fields, mut pos_map) = fields;
+        pos_map.set_value_span(span);
+        (Box::new(yass::Value::Struct(fields)), pos_map)
+    }
+    
+    #[inline]
+    fn document(&mut self, header: Self::Text, header_span: yass::Span, root_fields: Self::Fields) -> Self::Document {
+        let (root_fields, mut pos_map) = root_fields;
+        pos_map.set_header_span(header_span);
+        (yass::Document { header: header, root_fields: root_fields }, pos_map)
+    }
+}
+
+/// Builds a `yass::BorrowedDocument`, without positions.
+struct BorrowedBuilder;
+
+impl<'a> Builder<Lexer<'a>> for BorrowedBuilder {
+    type Value = yass::BorrowedValue<'a>;
+    type Items = Vec<yass::BorrowedValue<'a>>;
+    type Fields = Vec<yass::BorrowedStructField<'a>>;
+    type Document = yass::BorrowedDocument<'a>;
+    type Text = &'a str;
+    
+    #[inline]
+    fn text(&mut self, lexer: &Lexer<'a>, span: &yass::Span) -> Self::Text {
+        lexer.text(span)
+    }
+    
+    #[inline]
+    fn atom(&mut self, atom: Self::Text, _span: yass::Span) -> Self::Value {
+        yass::BorrowedValue::Atom(atom)
+    }
+    
+    #[inline]
+    fn new_items(&mut self) -> Self::Items {
+        Vec::new()
+    }
+    
+    #[inline]
+    fn push_item(&mut self, items: &mut Self::Items, item: Self::Value) {
+        items.push(item);
+    }
+    
+    #[inline]
+    fn array(&mut self, items: Self::Items, _span: yass::Span) -> Self::Value {
+        yass::BorrowedValue::Array(items)
+    }
+    
+    #[inline]
+    fn tagged(&mut self, tag: Self::Text, _tag_span: yass::Span, value: Self::Value, _span: yass::Span) -> Self::Value {
+        yass::BorrowedValue::Tagged(tag, Box::new(value))
+    }
+    
+    #[inline]
+    fn new_fields(&mut self) -> Self::Fields {
+        Vec::new()
+    }
+    
+    #[inline]
+    fn push_field(&mut self, fields: &mut Self::Fields, key: Self::Text, _key_span: yass::Span, value: Self::Value, _span: yass::Span) {
+        fields.push(yass::BorrowedStructField { key: key, value: value });
+    }
+    
+    #[inline]
+    fn struct_value(&mut self, fields: Self::Fields, _span: yass::Span) -> Self::Value {
+        yass::BorrowedValue::Struct(fields)
+    }
+    
+    #[inline]
+    fn document(&mut self, header: Self::Text, _header_span: yass::Span, root_fields: Self::Fields) -> Self::Document {
+        yass::BorrowedDocument { header: header, root_fields: root_fields }
+    }
+}
+
+/// Builds a `yass::FlatDocument`, without positions.
+struct FlatBuilder {
+    builder: yass::flat::FlatDocumentBuilder,
+}
+
+impl<'a> Builder<Lexer<'a>> for FlatBuilder {
+    type Value = yass::flat::NodeId;
+    type Items = yass::flat::Mark;
+    type Fields = yass::flat::Mark;
+    type Document = yass::FlatDocument;
+    type Text = &'a str;
+    
+    #[inline]
+    fn text(&mut self, lexer: &Lexer<'a>, span: &yass::Span) -> Self::Text {
+        lexer.text(span)
+    }
+    
+    #[inline]
+    fn atom(&mut self, atom: Self::Text, _span: yass::Span) -> Self::Value {
+        self.builder.atom(atom)
+    }
+    
+    #[inline]
+    fn new_items(&mut self) -> Self::Items {
+        self.builder.mark()
+    }
+    
+    #[inline]
+    fn push_item(&mut self, _items: &mut Self::Items, item: Self::Value) {
+        self.builder.push_item(item);
+    }
+    
+    #[inline]
+    fn array(&mut self, items: Self::Items, _span: yass::Span) -> Self::Value {
+        self.builder.array(items)
+    }
+    
+    #[inline]
+    fn tagged(&mut self, tag: Self::Text, _tag_span: yass::Span, value: Self::Value, _span: yass::Span) -> Self::Value {
+        self.builder.tagged(tag, value)
+    }
+    
+    #[inline]
+    fn new_fields(&mut self) -> Self::Fields {
+        self.builder.mark()
+    }
+    
+    #[inline]
+    fn push_field(&mut self, _fields: &mut Self::Fields, key: Self::Text, _key_span: yass::Span, value: Self::Value, _span: yass::Span) {
+        self.builder.push_field(key, value);
+    }
+    
+    #[inline]
+    fn struct_value(&mut self, fields: Self::Fields, _span: yass::Span) -> Self::Value {
+        self.builder.struct_value(fields)
+    }
+    
+    #[inline]
+    fn document(&mut self, header: Self::Text, _header_span: yass::Span, root_fields: Self::Fields) -> Self::Document {
+        let builder = std::mem::replace(&mut self.builder, yass::flat::FlatDocumentBuilder::new());
+        builder.finish(header, root_fields)
+    }
+}
+
+/// Value that is being built by `build`.
+enum Partial<I, F, S> {
+    Items(I, yass::Span),
+    Fields(F, yass::Span),
+    Field(S, yass::Span),
+    Tag(S, yass::Span, yass::Span),
+}
+
+/// Builds the document whose events are produced by `events`, which
+/// must be at the beginning of a document.
+fn build<T: Tokens, B: Builder<T>>(events: &mut Events<T>, builder: &mut B) -> Result<B::Document, T::Error> {
+    let (header, header_span) = match events.next_event()? {
+        Some(RawEvent::Header(span)) => (builder.text(&events.tokens, &span), span),
+        _ => unreachable!(),
+    };
+    
+    // The span of the root fields is not used.
+    let mut stack = vec![Partial::Fields(builder.new_fields(), header_span)];
+    while let Some(raw_event) = events.next_event()? {
+        let value = match raw_event {
+            RawEvent::Key(span) => {
+                stack.push(Partial::Field(builder.text(&events.tokens, &span), span));
+                continue;
             }
-            
-            if token.kind != TokenKind::Atom {
-                return Err(ParserError::UnexpectedToken { pos: token.pos, token_kind: token.kind });
+            RawEvent::Tag(tag_span, span) => {
+                stack.push(Partial::Tag(builder.text(&events.tokens, &tag_span), tag_span, span));
+                continue;
             }
-            
-            if token.data.len() > parser.limits.max_key_length {
-                return Err(ParserError::KeyTooLong { pos: token.pos });
+            RawEvent::BeginArray(span) => {
+                stack.push(Partial::Items(builder.new_items(), span));
+                continue;
             }
-            
-            let field_pos = token.pos;
-            let key = String::from_utf8(token.data.to_vec()).unwrap();
-            let value_1st_token = parser.lexer.get_token()?;
-            let value = parser.parse_value(value_1st_token)?;
-            parser.pos_map.set_struct_field_pos(&value, field_pos);
-            
-            root_fields.push(yass::StructField { key: key, value: value });
-        }
-        
-        Ok((yass::Document { header: header, root_fields: root_fields }, parser.pos_map))
-    }
-    
-    fn parse_value(&mut self, token: Token<'a>) -> Result<Box<yass::Value>, ParserError> {
-        match token.kind {
-            TokenKind::Atom => {
-                if token.data.len() > self.limits.max_atom_length {
-                    return Err(ParserError::AtomTooLong { pos: token.pos });
-                }
-                
-                let value = Box::new(yass::Value::Atom(std::str::from_utf8(token.data).unwrap().to_string()));
-                self.pos_map.set_value_pos(&value, token.pos);
-                Ok(value)
+            RawEvent::BeginStruct(span) => {
+                stack.push(Partial::Fields(builder.new_fields(), span));
+                continue;
             }
-            TokenKind::LeftBracket => {
-                if self.depth == self.limits.max_depth {
-                    return Err(ParserError::TooDeep { pos: token.pos });
+            RawEvent::Atom(span) => {
+                let atom = builder.text(&events.tokens, &span);
+                (builder.atom(atom, span), span)
+            }
+            RawEvent::EndArray(end_span) => match stack.pop() {
+                Some(Partial::Items(items, begin_span)) => {
+                    let span = begin_span.to(&end_span);
+                    (builder.array(items, span), span)
                 }
-                
-                let begin_pos = token.pos;
-                let mut items = Vec::new();
-                self.depth += 1;
-                loop {
-                    let token = self.lexer.get_token()?;
-                    if token.kind == TokenKind::RightBracket {
-                        break;
-                    }
-                    
-                    if items.len() == self.limits.max_array_size {
-                        return Err(ParserError::ArrayTooBig { pos: token.pos });
-                    }
-                    
-                    let value = self.parse_value(token)?;
-                    items.push(value);
+                _ => unreachable!(),
+            },
+            RawEvent::EndStruct(end_span) => match stack.pop() {
+                Some(Partial::Fields(fields, begin_span)) => {
+                    let span = begin_span.to(&end_span);
+                    (builder.struct_value(fields, span), span)
                 }
-                self.depth -= 1;
-                
-                let value = Box::new(yass::Value::Array(items));
-                self.pos_map.set_value_pos(&value, begin_pos);
-                Ok(value)
+                _ => unreachable!(),
+            },
+            RawEvent::Header(_) => unreachable!(),
+        };
+        
+        // Add the value to the partial value that contains it.
+        let (mut value, mut span) = value;
+        loop {
+            if let Some(&mut Partial::Items(ref mut items, _)) = stack.last_mut() {
+                builder.push_item(items, value);
+                break;
             }
-            TokenKind::LeftBrace => {
-                if self.depth == self.limits.max_depth {
-                    return Err(ParserError::TooDeep { pos: token.pos });
+            
+            match stack.pop() {
+                Some(Partial::Tag(tag, tag_span, begin_span)) => {
+                    span = begin_span.to(&span);
+                    value = builder.tagged(tag, tag_span, value, span);
                 }
-                
-                let begin_pos = token.pos;
-                let mut fields = Vec::new();
-                self.depth += 1;
-                loop {
-                    let token = self.lexer.get_token()?;
-                    if token.kind == TokenKind::RightBrace {
-                        break;
-                    }
-                    
-                    if token.kind != TokenKind::Atom {
-                        return Err(ParserError::UnexpectedToken { pos: token.pos, token_kind: token.kind });
-                    }
-                    
-                    if fields.len() == self.limits.max_struct_size {
-                        return Err(ParserError::StructTooBig { pos: token.pos });
-                    }
-                    
-                    if token.data.len() > self.limits.max_key_length {
-                        return Err(ParserError::KeyTooLong { pos: token.pos });
+                Some(Partial::Field(key, key_span)) => {
+                    match stack.last_mut() {
+                        Some(&mut Partial::Fields(ref mut fields, _)) => {
+                            builder.push_field(fields, key, key_span, value, key_span.to(&span));
+                        }
+                        _ => unreachable!(),
                     }
-                    
-                    let field_pos = token.pos;
-                    let key = String::from_utf8(token.data.to_vec()).unwrap();
-                    let value_1st_token = self.lexer.get_token()?;
-                    let value = self.parse_value(value_1st_token)?;
-                    self.pos_map.set_struct_field_pos(&value, field_pos);
-                    
-                    fields.push(yass::StructField { key: key, value: value });
+                    break;
                 }
-                self.depth -= 1;
-                
-                let value = Box::new(yass::Value::Struct(fields));
-                self.pos_map.set_value_pos(&value, begin_pos);
-                Ok(value)
+                _ => unreachable!(),
             }
-            TokenKind::LeftParen => {
-                if self.depth == self.limits.max_depth {
-                    return Err(ParserError::TooDeep { pos: token.pos });
-                }
-                
-                let begin_pos = token.pos;
-                let token = self.lexer.get_token()?;
-                if token.kind != TokenKind::Atom {
-                    return Err(ParserError::ExpectedToken { pos: token.pos, token_kind: TokenKind::Atom });
-                }
-                if token.data.len() > self.limits.max_tag_length {
-                    return Err(ParserError::TagTooLong { pos: token.pos });
-                }
-                let tag = std::str::from_utf8(token.data).unwrap().to_string();
-                
-                let token = self.lexer.get_token()?;
-                if token.kind != TokenKind::RightParen {
-                    return Err(ParserError::ExpectedToken { pos: token.pos, token_kind: TokenKind::RightParen });
-                }
-                
-                let value_1st_token = self.lexer.get_token()?;
-                self.depth += 1;
-                let sub_value = self.parse_value(value_1st_token)?;
-                self.depth -= 1;
-                
-                let value = Box::new(yass::Value::Tagged(tag, sub_value));
-                self.pos_map.set_value_pos(&value, begin_pos);
-                Ok(value)
+        }
+    }
+    
+    match stack.pop() {
+        Some(Partial::Fields(root_fields, _)) => Ok(builder.document(header, header_span, root_fields)),
+        _ => unreachable!(),
+    }
+}
+
+#[inline]
+pub fn parse(limits: ParserLimits, data: &[u8]) -> Result<(yass::Document, yass::PosMap), ParserError> {
+    parse_with_source(limits, data, yass::SourceId::UNKNOWN)
+}
+
+/// Like `parse`, but the produced positions (in the `PosMap` and in
+/// errors) have `source` as their source.
+///
+/// ```
+/// extern crate yass;
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let mut source_map = yass::SourceMap::new();
+///     let source = source_map.add("example.yass");
+///     let error = yass_parser::parse_with_source(yass_parser::ParserLimits::unlimited(), b"(example)\nkey [", source)
+///         .unwrap_err();
+///     assert_eq!(error.pos().source, source);
+///     assert_eq!(source_map.display(&error).to_string(), "Unexpected token Eof at example.yass:2:6");
+/// }
+/// ```
+#[inline]
+pub fn parse_with_source(limits: ParserLimits, data: &[u8], source: yass::SourceId)
+    -> Result<(yass::Document, yass::PosMap), ParserError>
+{
+    build(&mut Events::new(limits, Lexer::new(data, source)), &mut OwnedBuilder)
+}
+
+/// Like `parse`, but produces a `yass::BorrowedDocument` whose atoms,
+/// keys and tags are slices of `data`, avoiding a copy of each of them.
+///
+/// ```
+/// extern crate yass;
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let text = b"(example)\nkey1 \"value1\"\nkey2 [1 2]";
+///     let parsed = yass_parser::parse_borrowed(yass_parser::ParserLimits::unlimited(), text).unwrap();
+///     assert_eq!(parsed.header, "example");
+///     assert_eq!(parsed.root_fields[0].value, yass::BorrowedValue::Atom("\"value1\""));
+/// }
+/// ```
+#[inline]
+pub fn parse_borrowed<'a>(limits: ParserLimits, data: &'a [u8]) -> Result<yass::BorrowedDocument<'a>, ParserError> {
+    build(&mut Events::new(limits, Lexer::new(data, yass::SourceId::UNKNOWN)), &mut BorrowedBuilder)
+}
+
+/// Like `parse`, but produces a `yass::FlatDocument`.
+#[inline]
+pub fn parse_flat(limits: ParserLimits, data: &[u8]) -> Result<yass::FlatDocument, ParserError> {
+    let mut builder = FlatBuilder { builder: yass::flat::FlatDocumentBuilder::new() };
+    build(&mut Events::new(limits, Lexer::new(data, yass::SourceId::UNKNOWN)), &mut builder)
+}
+
+/// Iterator over the documents of a stream, returned by
+/// `parse_documents`.
+///
+/// Each document begins with its `(header)` and finishes where the
+/// next header begins, since a `(` cannot be a root key, or at the end
+/// of the input. An input without tokens has no documents. Positions
+/// are relative to the beginning of the whole input. After an error,
+/// the iterator does not return more items.
+pub struct Documents<'a> {
+    events: Events<Lexer<'a>>,
+    finished: bool,
+}
+
+impl<'a> Iterator for Documents<'a> {
+    type Item = Result<(yass::Document, yass::PosMap), ParserError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        
+        match self.events.begin_document() {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                self.finished = true;
+                return None;
             }
-            _ => {
-                Err(ParserError::UnexpectedToken { pos: token.pos, token_kind: token.kind })
+            Err(e) => {
+                self.finished = true;
+                return Some(Err(e));
             }
         }
+        
+        let result = build(&mut self.events, &mut OwnedBuilder);
+        if result.is_err() {
+            self.finished = true;
+        }
+        Some(result)
     }
 }
 
+/// Parses a stream of several documents.
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let text = b"(record)\nid 1\n\n(record)\nid 2\n(end)";
+///     let documents = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), text)
+///         .map(|result| result.map(|(document, _)| document))
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     assert_eq!(documents, [
+///         yass_document!(("record") "id": "1"),
+///         yass_document!(("record") "id": "2"),
+///         yass_document!(("end")),
+///     ]);
+/// }
+/// ```
 #[inline]
-pub fn parse(limits: ParserLimits, data: &[u8]) -> Result<(yass::Document, yass::PosMap), ParserError> {
-    Parser::parse(limits, data)
+pub fn parse_documents<'a>(limits: ParserLimits, data: &'a [u8]) -> Documents<'a> {
+    parse_documents_with_source(limits, data, yass::SourceId::UNKNOWN)
+}
+
+/// Like `parse_documents`, but the produced positions have `source` as
+/// their source.
+#[inline]
+pub fn parse_documents_with_source<'a>(limits: ParserLimits, data: &'a [u8], source: yass::SourceId) -> Documents<'a> {
+    Documents {
+        events: Events::new_documents(limits, Lexer::new(data, source)),
+        finished: false,
+    }
 }
 
 #[derive(Debug)]
@@ -407,6 +697,8 @@ impl std::error::Error for ParseStreamError {
     }
 }
 
+/// Reads the whole stream and parses it. The data is returned with the
+/// document. See `parse_reader` to parse the data as it is read.
 #[inline]
 pub fn parse_stream(limits: ParserLimits, stream: &mut std::io::Read)
     -> Result<(Vec<u8>, yass::Document, yass::PosMap), ParseStreamError>
@@ -461,11 +753,21 @@ impl std::error::Error for ParseFileError {
 pub fn parse_file<P: std::convert::AsRef<std::path::Path>>(limits: ParserLimits, file_path: &P)
     -> Result<(Vec<u8>, yass::Document, yass::PosMap), ParseFileError>
 {
+    parse_file_with_source(limits, file_path, yass::SourceId::UNKNOWN)
+}
+
+/// Like `parse_file`, but the produced positions have `source` as
+/// their source.
+pub fn parse_file_with_source<P: std::convert::AsRef<std::path::Path>>(
+    limits: ParserLimits,
+    file_path: &P,
+    source: yass::SourceId,
+) -> Result<(Vec<u8>, yass::Document, yass::PosMap), ParseFileError> {
     let mut file = std::fs::OpenOptions::new().read(true).open(file_path.as_ref()).map_err(|e| ParseFileError::OpenError(e))?;
     let mut data = Vec::new();
     std::io::Read::read_to_end(&mut file, &mut data).map_err(|e| ParseFileError::ReadError(e))?;
     std::mem::drop(file);
-    parse(limits, &data).map_err(|e| ParseFileError::ParserError(e)).map(|(doc, pos_map)| (data, doc, pos_map))
+    parse_with_source(limits, &data, source).map_err(|e| ParseFileError::ParserError(e)).map(|(doc, pos_map)| (data, doc, pos_map))
 }
 
 // Lexer
@@ -477,26 +779,33 @@ struct Lexer<'a> {
 
 impl<'a> Lexer<'a> {
     #[inline]
-    fn new(data: &'a [u8]) -> Self {
+    fn new(data: &'a [u8], source: yass::SourceId) -> Self {
         Self {
             data: data,
             i: 0,
-            pos: yass::Pos::new(0, 0),
+            pos: yass::Pos::with_source(0, 0, source),
         }
     }
     
+    /// Gets the text of a token.
+    #[inline]
+    fn text(&self, span: &yass::Span) -> &'a str {
+        std::str::from_utf8(&self.data[span.start_offset .. span.end_offset]).unwrap()
+    }
+    
     #[inline]
     fn make_token(&mut self, kind: TokenKind, len: u32) -> Token<'a> {
-        let token = Token {
-            kind: kind,
-            data: &self.data[self.i .. (self.i + len as usize)],
-            pos: self.pos,
-        };
+        let begin_i = self.i;
+        let begin_pos = self.pos;
         
         self.i += len as usize;
         self.pos.column += len;
         
-        token
+        Token {
+            kind: kind,
+            data: &self.data[begin_i .. self.i],
+            span: yass::Span::new(begin_pos, self.pos, begin_i, self.i),
+        }
     }
     
     fn skip_comment(&mut self) {
@@ -511,44 +820,109 @@ impl<'a> Lexer<'a> {
         }
     }
     
-    fn skip_spaces_and_comments(&mut self) {
-        let mut last_was_cr = false;
-        
+    /// Skips an atom that contains a bad string. Strings end at their
+    /// closing quote or at the end of the line.
+    fn skip_bad_atom(&mut self) {
+        let mut in_string = false;
+        let mut last_was_backslash = false;
+        while self.i < self.data.len() {
+            let chr = self.data[self.i];
+            if chr == b'\n' || chr == b'\r' {
+                break;
+            }
+            if in_string {
+                if last_was_backslash {
+                    last_was_backslash = false;
+                } else if chr == b'\\' {
+                    last_was_backslash = true;
+                } else if chr == b'"' {
+                    in_string = false;
+                }
+            } else if chr == b'"' {
+                in_string = true;
+            } else if !yass::is_atom_chr(chr) {
+                break;
+            }
+            self.i += 1;
+            self.pos.column += 1;
+        }
+    }
+    
+    /// Skips a run of bytes that cannot start a token.
+    fn skip_illegal_chrs(&mut self) {
         while self.i < self.data.len() {
             match self.data[self.i] {
-                b' ' | b'\t' => {
+                b' ' | b'\t' | b'\n' | b'\r' | b'\\' | b'"' | b'(' | b')' | b'[' | b']' | b'{' | b'}' => break,
+                chr if yass::is_atom_chr(chr) => break,
+                _ => {
                     self.i += 1;
                     self.pos.column += 1;
-                    last_was_cr = false;
                 }
-                b'\n' => {
-                    self.i += 1;
-                    if !last_was_cr {
-                        self.pos.line += 1;
-                        self.pos.column = 0;
-                    } else {
-                        last_was_cr = false;
-                    }
-                }
-                b'\r' => {
+            }
+        }
+    }
+    
+    fn get_trivia(&mut self) -> Option<(cst::TriviaKind, &'a [u8])> {
+        if self.i == self.data.len() {
+            return None;
+        }
+        
+        let begin_i = self.i;
+        let kind = match self.data[self.i] {
+            b' ' | b'\t' => {
+                while self.i < self.data.len() && (self.data[self.i] == b' ' || self.data[self.i] == b'\t') {
                     self.i += 1;
-                    self.pos.line += 1;
-                    self.pos.column = 0;
-                    last_was_cr = true;
+                    self.pos.column += 1;
                 }
-                b'\\' => {
+                cst::TriviaKind::Whitespace
+            }
+            b'\n' => {
+                self.i += 1;
+                self.pos.line += 1;
+                self.pos.column = 0;
+                cst::TriviaKind::LineBreak
+            }
+            b'\r' => {
+                self.i += 1;
+                if self.i < self.data.len() && self.data[self.i] == b'\n' {
                     self.i += 1;
-                    self.pos.column += 1;
-                    self.skip_comment();
                 }
-                _ => break,
+                self.pos.line += 1;
+                self.pos.column = 0;
+                cst::TriviaKind::LineBreak
             }
-        }
+            b'\\' => {
+                self.i += 1;
+                self.pos.column += 1;
+                self.skip_comment();
+                cst::TriviaKind::Comment
+            }
+            _ => return None,
+        };
+        
+        Some((kind, &self.data[begin_i .. self.i]))
     }
     
+    fn skip_spaces_and_comments(&mut self) {
+        while self.get_trivia().is_some() {}
+    }
+    
+    #[inline]
     fn get_token(&mut self) -> Result<Token<'a>, ParserError> {
         self.skip_spaces_and_comments();
-        
+        self.lex_token()
+    }
+    
+    /// Like `get_token`, but appends the skipped spaces, line breaks
+    /// and comments to `trivia`.
+    fn get_token_with_trivia(&mut self, trivia: &mut Vec<cst::Trivia>) -> Result<Token<'a>, ParserError> {
+        while let Some((kind, text)) = self.get_trivia() {
+            trivia.push(cst::Trivia { kind: kind, text: text.to_vec() });
+        }
+        self.lex_token()
+    }
+    
+    fn lex_token(&mut self) -> Result<Token<'a>, ParserError> {
         if self.i == self.data.len() {
             return Ok(self.make_token(TokenKind::Eof, 0));
         }
@@ -560,7 +934,7 @@ impl<'a> Lexer<'a> {
             b']' => Ok(self.make_token(TokenKind::RightBracket, 1)),
             b'{' => Ok(self.make_token(TokenKind::LeftBrace, 1)),
             b'}' => Ok(self.make_token(TokenKind::RightBrace, 1)),
-            chr if Self::is_atom_chr(chr) || chr == b'"' => {
+            chr if yass::is_atom_chr(chr) || chr == b'"' => {
                 let left = self.data.len() - self.i;
                 let mut len: u32 = 0;
                 while (len as usize) != left {
@@ -575,8 +949,8 @@ impl<'a> Lexer<'a> {
                             
                             let chr = self.data[self.i + len as usize];
                             
-                            if !Self::is_string_chr(chr) {
-                                let err_pos = yass::Pos::new(self.pos.line, self.pos.column + len);
+                            if !yass::is_string_chr(chr) {
+                                let err_pos = yass::Pos::with_source(self.pos.line, self.pos.column + len, self.pos.source);
                                 return Err(ParserError::IllegalChrInString { pos: err_pos, chr: chr, });
                             }
                             
@@ -593,7 +967,7 @@ impl<'a> Lexer<'a> {
                             
                             len += 1;
                         }
-                    } else if Self::is_atom_chr(chr) {
+                    } else if yass::is_atom_chr(chr) {
                         len += 1;
                     } else {
                         break;
@@ -604,20 +978,4 @@ impl<'a> Lexer<'a> {
             chr => Err(ParserError::IllegalChr { pos: self.pos, chr: chr })
         }
     }
-    
-    fn is_atom_chr(chr: u8) -> bool {
-        match chr {
-            b'_' | b'.' | b',' | b':' | b';' | b'+' | b'-' | b'*' | b'/' => true,
-            b'|' | b'$' | b'#' | b'@' | b'%' | b'=' | b'<' | b'>' => true,
-            b'a' ... b'z' | b'A' ... b'Z' | b'0' ... b'9' | b'?' | b'!' => true,
-            _ => false,
-        }
-    }
-    
-    fn is_string_chr(chr: u8) -> bool {
-        match chr {
-            0x00 ... 0x1F | 0x7F ... 0xFF => false,
-            _ => true,
-        }
-    }
 }
@@ -0,0 +1,461 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::{Lexer, ParserError, ParserLimits, TokenKind};
+
+/// Event produced by a `Reader`.
+///
+/// Each event carries the span of its token. Atoms, keys and tags
+/// are slices of the input, with quotes and escape sequences.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Header of the document. Always the first event.
+    Header(&'a str, yass::Span),
+    /// Key of a struct field. Followed by the events of its value.
+    Key(&'a str, yass::Span),
+    Atom(&'a str, yass::Span),
+    /// Tag of a tagged value. Followed by the events of the inner
+    /// value.
+    Tag(&'a str, yass::Span),
+    BeginArray(yass::Span),
+    EndArray(yass::Span),
+    BeginStruct(yass::Span),
+    EndStruct(yass::Span),
+}
+
+/// Event without the text of its tokens, produced by `Events`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum RawEvent {
+    Header(yass::Span),
+    Key(yass::Span),
+    Atom(yass::Span),
+    /// Spans of the tag and of the left parenthesis before it.
+    Tag(yass::Span, yass::Span),
+    BeginArray(yass::Span),
+    EndArray(yass::Span),
+    BeginStruct(yass::Span),
+    EndStruct(yass::Span),
+}
+
+impl RawEvent {
+    /// Returns the span of the first token of the event.
+    #[inline]
+    pub(crate) fn first_span(&self) -> yass::Span {
+        match *self {
+            RawEvent::Tag(_, span) |
+            RawEvent::Header(span) |
+            RawEvent::Key(span) |
+            RawEvent::Atom(span) |
+            RawEvent::BeginArray(span) |
+            RawEvent::EndArray(span) |
+            RawEvent::BeginStruct(span) |
+            RawEvent::EndStruct(span) => span,
+        }
+    }
+    
+    /// Adds the text of the tokens, taken from `text`.
+    pub(crate) fn with_text<'a, F: FnOnce(&yass::Span) -> &'a str>(self, text: F) -> Event<'a> {
+        match self {
+            RawEvent::Header(span) => Event::Header(text(&span), span),
+            RawEvent::Key(span) => Event::Key(text(&span), span),
+            RawEvent::Atom(span) => Event::Atom(text(&span), span),
+            RawEvent::Tag(span, _) => Event::Tag(text(&span), span),
+            RawEvent::BeginArray(span) => Event::BeginArray(span),
+            RawEvent::EndArray(span) => Event::EndArray(span),
+            RawEvent::BeginStruct(span) => Event::BeginStruct(span),
+            RawEvent::EndStruct(span) => Event::EndStruct(span),
+        }
+    }
+}
+
+/// Source of tokens for `Events`.
+pub(crate) trait Tokens {
+    type Error: From<ParserError>;
+    
+    /// Returns the kind and the span of the next token. Atoms longer
+    /// than `max_len` bytes may be cut, as long as they are still longer
+    /// than `max_len`.
+    fn next_token(&mut self, max_len: usize) -> Result<(TokenKind, yass::Span), Self::Error>;
+    
+    /// Gets the text of a token of the current event.
+    fn text(&self, span: &yass::Span) -> &str;
+    
+    /// Called before the tokens of each event are requested, so the
+    /// tokens of the previous ones can be dropped.
+    #[inline]
+    fn begin_event(&mut self) {}
+}
+
+impl<'a> Tokens for Lexer<'a> {
+    type Error = ParserError;
+    
+    #[inline]
+    fn next_token(&mut self, _max_len: usize) -> Result<(TokenKind, yass::Span), ParserError> {
+        self.get_token().map(|token| (token.kind, token.span))
+    }
+    
+    #[inline]
+    fn text(&self, span: &yass::Span) -> &str {
+        Lexer::text(self, span)
+    }
+}
+
+/// An array, struct or tagged value that has not been finished yet.
+enum Frame {
+    Array(usize),
+    Struct(usize),
+    Tag,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Header,
+    /// Expecting a key or the end of a struct.
+    Field,
+    /// Expecting an array item or the end of an array.
+    Item,
+    Value,
+    Finished,
+}
+
+/// Produces the events of a document from a source of tokens. The
+/// tokens of each event are requested in the same call to
+/// `next_event`.
+pub(crate) struct Events<T> {
+    limits: ParserLimits,
+    /// Maximum length of keys, atoms and tags.
+    max_len: usize,
+    pub(crate) tokens: T,
+    stack: Vec<Frame>,
+    state: State,
+    /// Whether a `(` in place of a root key begins the next document.
+    documents: bool,
+    /// First token of the next document, read at the end of the
+    /// previous one.
+    next_document: Option<(TokenKind, yass::Span)>,
+}
+
+impl<T: Tokens> Events<T> {
+    pub(crate) fn new(limits: ParserLimits, tokens: T) -> Self {
+        Self {
+            limits: limits,
+            max_len: limits.max_atom_length.max(limits.max_key_length).max(limits.max_tag_length),
+            tokens: tokens,
+            stack: Vec::new(),
+            state: State::Header,
+            documents: false,
+            next_document: None,
+        }
+    }
+    
+    /// Like `new`, but produces the events of a stream of several
+    /// documents, as in `parse_documents`. `begin_document` must be
+    /// called before the events of each document, and `next_event`
+    /// returns `None` at the end of each of them.
+    pub(crate) fn new_documents(limits: ParserLimits, tokens: T) -> Self {
+        Self {
+            state: State::Finished,
+            documents: true,
+            .. Self::new(limits, tokens)
+        }
+    }
+    
+    /// Prepares the events of the next document. Returns the span of its
+    /// first token, or `None` if there are no more documents.
+    pub(crate) fn begin_document(&mut self) -> Result<Option<yass::Span>, T::Error> {
+        let (kind, span) = match self.next_document.take() {
+            Some(token) => token,
+            None => self.next_token()?,
+        };
+        if kind == TokenKind::Eof {
+            return Ok(None);
+        }
+        
+        self.next_document = Some((kind, span));
+        self.state = State::Header;
+        Ok(Some(span))
+    }
+    
+    #[inline]
+    pub(crate) fn depth(&self) -> usize {
+        self.stack.len()
+    }
+    
+    /// Returns the kinds of the tokens that the next call to
+    /// `next_event` accepts as first token.
+    pub(crate) fn expected_tokens(&self) -> &'static [TokenKind] {
+        const VALUE: &[TokenKind] = &[TokenKind::Atom, TokenKind::LeftParen, TokenKind::LeftBracket, TokenKind::LeftBrace];
+        const ITEM: &[TokenKind] = &[
+            TokenKind::Atom,
+            TokenKind::LeftParen,
+            TokenKind::LeftBracket,
+            TokenKind::LeftBrace,
+            TokenKind::RightBracket,
+        ];
+        match self.state {
+            State::Header => &[TokenKind::LeftParen],
+            State::Field if self.stack.is_empty() && self.documents => &[TokenKind::Atom, TokenKind::LeftParen, TokenKind::Eof],
+            State::Field if self.stack.is_empty() => &[TokenKind::Atom, TokenKind::Eof],
+            State::Field => &[TokenKind::Atom, TokenKind::RightBrace],
+            State::Item => ITEM,
+            State::Value => VALUE,
+            State::Finished => &[],
+        }
+    }
+    
+    pub(crate) fn next_event(&mut self) -> Result<Option<RawEvent>, T::Error> {
+        self.tokens.begin_event();
+        let result = match self.state {
+            State::Header => self.read_header().map(Some),
+            State::Field => self.read_field(),
+            State::Item => self.read_item().map(Some),
+            State::Value => self.next_token().and_then(|token| self.read_value(token)).map(Some),
+            State::Finished => return Ok(None),
+        };
+        if result.is_err() {
+            self.state = State::Finished;
+        }
+        result
+    }
+    
+    #[inline]
+    fn next_token(&mut self) -> Result<(TokenKind, yass::Span), T::Error> {
+        self.tokens.next_token(self.max_len)
+    }
+    
+    fn read_header(&mut self) -> Result<RawEvent, T::Error> {
+        let (kind, span) = match self.next_document.take() {
+            Some(token) => token,
+            None => self.next_token()?,
+        };
+        if kind != TokenKind::LeftParen {
+            return Err(ParserError::ExpectedToken { pos: span.start, token_kind: TokenKind::LeftParen }.into());
+        }
+        
+        // The length of the header is not limited.
+        let (kind, header_span) = self.tokens.next_token(usize::max_value())?;
+        if kind != TokenKind::Atom {
+            return Err(ParserError::ExpectedToken { pos: header_span.start, token_kind: TokenKind::Atom }.into());
+        }
+        
+        let (kind, span) = self.next_token()?;
+        if kind != TokenKind::RightParen {
+            return Err(ParserError::ExpectedToken { pos: span.start, token_kind: TokenKind::RightParen }.into());
+        }
+        
+        self.state = State::Field;
+        Ok(RawEvent::Header(header_span))
+    }
+    
+    fn read_field(&mut self) -> Result<Option<RawEvent>, T::Error> {
+        let (kind, span) = self.next_token()?;
+        match self.stack.last_mut() {
+            None => {
+                if kind == TokenKind::Eof || (self.documents && kind == TokenKind::LeftParen) {
+                    if self.documents {
+                        self.next_document = Some((kind, span));
+                    }
+                    self.state = State::Finished;
+                    return Ok(None);
+                }
+                if kind != TokenKind::Atom {
+                    return Err(ParserError::UnexpectedToken { pos: span.start, token_kind: kind }.into());
+                }
+            }
+            Some(&mut Frame::Struct(ref mut num_fields)) => {
+                if kind == TokenKind::RightBrace {
+                    self.stack.pop();
+                    self.end_value();
+                    return Ok(Some(RawEvent::EndStruct(span)));
+                }
+                if kind != TokenKind::Atom {
+                    return Err(ParserError::UnexpectedToken { pos: span.start, token_kind: kind }.into());
+                }
+                if *num_fields == self.limits.max_struct_size {
+                    return Err(ParserError::StructTooBig { pos: span.start }.into());
+                }
+                *num_fields += 1;
+            }
+            Some(_) => unreachable!(),
+        }
+        
+        if span.len() > self.limits.max_key_length {
+            return Err(ParserError::KeyTooLong { pos: span.start }.into());
+        }
+        
+        self.state = State::Value;
+        Ok(Some(RawEvent::Key(span)))
+    }
+    
+    fn read_item(&mut self) -> Result<RawEvent, T::Error> {
+        let (kind, span) = self.next_token()?;
+        if kind == TokenKind::RightBracket {
+            self.stack.pop();
+            self.end_value();
+            return Ok(RawEvent::EndArray(span));
+        }
+        
+        match self.stack.last_mut() {
+            Some(&mut Frame::Array(ref mut num_items)) => {
+                if *num_items == self.limits.max_array_size {
+                    return Err(ParserError::ArrayTooBig { pos: span.start }.into());
+                }
+                *num_items += 1;
+            }
+            _ => unreachable!(),
+        }
+        
+        self.read_value((kind, span))
+    }
+    
+    fn read_value(&mut self, token: (TokenKind, yass::Span)) -> Result<RawEvent, T::Error> {
+        let (kind, span) = token;
+        match kind {
+            TokenKind::Atom => {
+                if span.len() > self.limits.max_atom_length {
+                    return Err(ParserError::AtomTooLong { pos: span.start }.into());
+                }
+                
+                self.end_value();
+                Ok(RawEvent::Atom(span))
+            }
+            TokenKind::LeftBracket => {
+                self.begin_value(&span, Frame::Array(0))?;
+                self.state = State::Item;
+                Ok(RawEvent::BeginArray(span))
+            }
+            TokenKind::LeftBrace => {
+                self.begin_value(&span, Frame::Struct(0))?;
+                self.state = State::Field;
+                Ok(RawEvent::BeginStruct(span))
+            }
+            TokenKind::LeftParen => {
+                self.begin_value(&span, Frame::Tag)?;
+                
+                let (kind, tag_span) = self.next_token()?;
+                if kind != TokenKind::Atom {
+                    return Err(ParserError::ExpectedToken { pos: tag_span.start, token_kind: TokenKind::Atom }.into());
+                }
+                if tag_span.len() > self.limits.max_tag_length {
+                    return Err(ParserError::TagTooLong { pos: tag_span.start }.into());
+                }
+                
+                let (kind, right_paren_span) = self.next_token()?;
+                if kind != TokenKind::RightParen {
+                    return Err(ParserError::ExpectedToken { pos: right_paren_span.start, token_kind: TokenKind::RightParen }.into());
+                }
+                
+                self.state = State::Value;
+                Ok(RawEvent::Tag(tag_span, span))
+            }
+            _ => {
+                Err(ParserError::UnexpectedToken { pos: span.start, token_kind: kind }.into())
+            }
+        }
+    }
+    
+    fn begin_value(&mut self, span: &yass::Span, frame: Frame) -> Result<(), ParserError> {
+        if self.stack.len() == self.limits.max_depth {
+            return Err(ParserError::TooDeep { pos: span.start });
+        }
+        self.stack.push(frame);
+        Ok(())
+    }
+    
+    /// Finishes the tagged values that contained the value that has
+    /// just finished and updates the state.
+    fn end_value(&mut self) {
+        loop {
+            match self.stack.last() {
+                Some(&Frame::Tag) => {
+                    self.stack.pop();
+                }
+                Some(&Frame::Array(_)) => {
+                    self.state = State::Item;
+                    break;
+                }
+                Some(&Frame::Struct(_)) | None => {
+                    self.state = State::Field;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Pull parser that produces the events of a document one by one,
+/// without building it in memory.
+///
+/// The `ParserLimits` are checked as in `parse`. The memory used does
+/// not depend on the size of the document, but on its depth. After an
+/// error, the reader does not produce more events.
+///
+/// ```
+/// extern crate yass_parser;
+///
+/// use yass_parser::Event;
+///
+/// fn main() {
+///     let text = b"(example)\nkey1 [1 2]\nkey2 (tag)3";
+///     let reader = yass_parser::Reader::new(yass_parser::ParserLimits::unlimited(), text);
+///     let mut num_atoms = 0;
+///     for event in reader {
+///         match event.unwrap() {
+///             Event::Atom(..) => num_atoms += 1,
+///             Event::Tag(tag, _) => assert_eq!(tag, "tag"),
+///             _ => {}
+///         }
+///     }
+///     assert_eq!(num_atoms, 3);
+/// }
+/// ```
+pub struct Reader<'a> {
+    events: Events<Lexer<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    pub fn new(limits: ParserLimits, data: &'a [u8]) -> Self {
+        Self::with_source(limits, data, yass::SourceId::UNKNOWN)
+    }
+    
+    /// Like `new`, but the produced positions have `source` as their
+    /// source.
+    #[inline]
+    pub fn with_source(limits: ParserLimits, data: &'a [u8], source: yass::SourceId) -> Self {
+        Self {
+            events: Events::new(limits, Lexer::new(data, source)),
+        }
+    }
+    
+    /// Returns the number of arrays, structs and tagged values that
+    /// have begun and have not finished yet.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.events.depth()
+    }
+    
+    /// Returns the next event, or `None` at the end of the document.
+    pub fn next_event(&mut self) -> Result<Option<Event<'a>>, ParserError> {
+        let raw_event = self.events.next_event()?;
+        let lexer = &self.events.tokens;
+        Ok(raw_event.map(|raw_event| raw_event.with_text(|span| lexer.text(span))))
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>, ParserError>;
+    
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_event() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Error-recovering parser.
+//!
+//! Errors are recorded and parsing continues, so a single run reports
+//! every error of the input. The parser resynchronizes as follows:
+//!
+//! * A bad string skips the rest of the string (or of the line, if it
+//!   is not closed) and a run of illegal characters is skipped as a
+//!   whole.
+//! * A value with an error is dropped, together with its struct field.
+//! * An array or struct that is not closed ends at the first token that
+//!   cannot continue it. If that token is a closing bracket or brace,
+//!   it is left for the enclosing array or struct, so `{a [1 2}` reports
+//!   only the missing `]`.
+//! * Unexpected tokens in place of a struct key are skipped. An opening
+//!   bracket, brace or parenthesis is skipped with its whole value.
+//!
+//! An error at the same position as the previous one is not reported
+//! again.
+
+use super::{Builder, Lexer, OwnedBuilder, ParserError, ParserLimits, Token, TokenKind};
+
+impl<'a> Lexer<'a> {
+    /// Like `get_token`, but records errors in `errors` and skips the
+    /// offending text. An atom with a bad string is returned as an atom
+    /// token without data.
+    fn get_token_recovering(&mut self, errors: &mut Vec<ParserError>) -> Token<'a> {
+        loop {
+            self.skip_spaces_and_comments();
+            let begin_i = self.i;
+            let begin_pos = self.pos;
+            match self.lex_token() {
+                Ok(token) => return token,
+                Err(error @ ParserError::IllegalChr { .. }) => {
+                    errors.push(error);
+                    self.skip_illegal_chrs();
+                }
+                Err(error) => {
+                    errors.push(error);
+                    self.skip_bad_atom();
+                    return Token {
+                        kind: TokenKind::Atom,
+                        data: &[],
+                        span: yass::Span::new(begin_pos, self.pos, begin_i, self.i),
+                    };
+                }
+            }
+        }
+    }
+}
+
+struct RecoveringParser<'a, B> {
+    limits: ParserLimits,
+    lexer: Lexer<'a>,
+    depth: usize,
+    builder: B,
+    errors: Vec<ParserError>,
+    /// Token that was read but not used yet.
+    pending: Option<Token<'a>>,
+}
+
+impl<'a, B: Builder<Lexer<'a>>> RecoveringParser<'a, B> {
+    fn error(&mut self, error: ParserError) {
+        if self.errors.last().map(ParserError::pos) != Some(error.pos()) {
+            self.errors.push(error);
+        }
+    }
+    
+    #[inline]
+    fn next_token(&mut self) -> Token<'a> {
+        match self.pending.take() {
+            Some(token) => token,
+            None => self.lexer.get_token_recovering(&mut self.errors),
+        }
+    }
+    
+    fn parse(limits: ParserLimits, data: &'a [u8], source: yass::SourceId, builder: B) -> (B::Document, Vec<ParserError>) {
+        let mut parser = Self {
+            limits: limits,
+            lexer: Lexer::new(data, source),
+            depth: 0,
+            builder: builder,
+            errors: Vec::new(),
+            pending: None,
+        };
+        
+        let (header, header_span) = parser.parse_header();
+        
+        let mut root_fields = parser.builder.new_fields();
+        loop {
+            let token = parser.next_token();
+            match token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Atom => {
+                    parser.parse_struct_field(token, &mut root_fields, true);
+                }
+                TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                    parser.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind });
+                    parser.parse_value(token);
+                }
+                _ => parser.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind }),
+            }
+        }
+        
+        let document = parser.builder.document(header, header_span, root_fields);
+        (document, parser.errors)
+    }
+    
+    /// Parses the header. A missing header is empty.
+    fn parse_header(&mut self) -> (B::Text, yass::Span) {
+        let token = self.next_token();
+        let empty_span = yass::Span::new(token.span.start, token.span.start, token.span.start_offset, token.span.start_offset);
+        if token.kind != TokenKind::LeftParen {
+            self.error(ParserError::ExpectedToken { pos: token.span.start, token_kind: TokenKind::LeftParen });
+            self.pending = Some(token);
+            return (self.builder.text(&self.lexer, &empty_span), empty_span);
+        }
+        
+        let token = self.next_token();
+        if token.kind != TokenKind::Atom || token.data.is_empty() {
+            if token.kind != TokenKind::Atom {
+                self.error(ParserError::ExpectedToken { pos: token.span.start, token_kind: TokenKind::Atom });
+            }
+            if token.kind != TokenKind::RightParen {
+                self.pending = Some(token);
+            }
+            return (self.builder.text(&self.lexer, &empty_span), empty_span);
+        }
+        let header = self.builder.text(&self.lexer, &token.span);
+        let header_span = token.span;
+        
+        let token = self.next_token();
+        if token.kind != TokenKind::RightParen {
+            self.error(ParserError::ExpectedToken { pos: token.span.start, token_kind: TokenKind::RightParen });
+            self.pending = Some(token);
+        }
+        (header, header_span)
+    }
+    
+    /// Parses a struct field and returns the span of its last token.
+    fn parse_struct_field(&mut self, key_token: Token<'a>, fields: &mut B::Fields, keep: bool) -> yass::Span {
+        let mut keep = keep && !key_token.data.is_empty();
+        if key_token.data.len() > self.limits.max_key_length {
+            self.error(ParserError::KeyTooLong { pos: key_token.span.start });
+            keep = false;
+        }
+        
+        let value_1st_token = self.next_token();
+        match self.parse_value(value_1st_token) {
+            Some((value, value_span)) => {
+                if keep {
+                    let key = self.builder.text(&self.lexer, &key_token.span);
+                    self.builder.push_field(fields, key, key_token.span, value, key_token.span.to(&value_span));
+                }
+                value_span
+            }
+            None => key_token.span,
+        }
+    }
+    
+    /// Skips the tokens of a value that is nested too deep, up to its
+    /// closing bracket, brace or parenthesis.
+    fn skip_nested(&mut self) {
+        let mut depth = 1;
+        while depth != 0 {
+            let token = self.next_token();
+            match token.kind {
+                TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => depth -= 1,
+                TokenKind::Eof => {
+                    self.pending = Some(token);
+                    break;
+                }
+                TokenKind::Atom => {}
+            }
+        }
+    }
+    
+    /// Parses a value. Returns `None` if it had errors.
+    fn parse_value(&mut self, token: Token<'a>) -> Option<(B::Value, yass::Span)> {
+        match token.kind {
+            TokenKind::Atom => {
+                if token.data.is_empty() {
+                    return None;
+                }
+                if token.data.len() > self.limits.max_atom_length {
+                    self.error(ParserError::AtomTooLong { pos: token.span.start });
+                    return None;
+                }
+                
+                let atom = self.builder.text(&self.lexer, &token.span);
+                Some((self.builder.atom(atom, token.span), token.span))
+            }
+            TokenKind::LeftBracket => {
+                if self.depth == self.limits.max_depth {
+                    self.error(ParserError::TooDeep { pos: token.span.start });
+                    self.skip_nested();
+                    return None;
+                }
+                
+                let begin_span = token.span;
+                let mut end_span = token.span;
+                let mut items = self.builder.new_items();
+                let mut num_items = 0;
+                self.depth += 1;
+                loop {
+                    let token = self.next_token();
+                    match token.kind {
+                        TokenKind::RightBracket => {
+                            end_span = token.span;
+                            break;
+                        }
+                        TokenKind::RightParen | TokenKind::RightBrace | TokenKind::Eof => {
+                            self.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind });
+                            self.pending = Some(token);
+                            break;
+                        }
+                        _ => {}
+                    }
+                    
+                    if num_items == self.limits.max_array_size {
+                        self.error(ParserError::ArrayTooBig { pos: token.span.start });
+                    }
+                    
+                    if let Some((value, value_span)) = self.parse_value(token) {
+                        end_span = value_span;
+                        if num_items < self.limits.max_array_size {
+                            self.builder.push_item(&mut items, value);
+                        }
+                    }
+                    num_items += 1;
+                }
+                self.depth -= 1;
+                
+                let span = begin_span.to(&end_span);
+                Some((self.builder.array(items, span), span))
+            }
+            TokenKind::LeftBrace => {
+                if self.depth == self.limits.max_depth {
+                    self.error(ParserError::TooDeep { pos: token.span.start });
+                    self.skip_nested();
+                    return None;
+                }
+                
+                let begin_span = token.span;
+                let mut end_span = token.span;
+                let mut fields = self.builder.new_fields();
+                let mut num_fields = 0;
+                self.depth += 1;
+                loop {
+                    let token = self.next_token();
+                    match token.kind {
+                        TokenKind::RightBrace => {
+                            end_span = token.span;
+                            break;
+                        }
+                        TokenKind::RightParen | TokenKind::RightBracket | TokenKind::Eof => {
+                            self.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind });
+                            self.pending = Some(token);
+                            break;
+                        }
+                        TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                            self.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind });
+                            if let Some((_, value_span)) = self.parse_value(token) {
+                                end_span = value_span;
+                            }
+                            continue;
+                        }
+                        TokenKind::Atom => {}
+                    }
+                    
+                    if num_fields == self.limits.max_struct_size {
+                        self.error(ParserError::StructTooBig { pos: token.span.start });
+                    }
+                    
+                    let keep = num_fields < self.limits.max_struct_size;
+                    end_span = self.parse_struct_field(token, &mut fields, keep);
+                    num_fields += 1;
+                }
+                self.depth -= 1;
+                
+                let span = begin_span.to(&end_span);
+                Some((self.builder.struct_value(fields, span), span))
+            }
+            TokenKind::LeftParen => {
+                if self.depth == self.limits.max_depth {
+                    self.error(ParserError::TooDeep { pos: token.span.start });
+                    self.skip_nested();
+                    return None;
+                }
+                
+                let begin_span = token.span;
+                let token = self.next_token();
+                if token.kind != TokenKind::Atom {
+                    self.error(ParserError::ExpectedToken { pos: token.span.start, token_kind: TokenKind::Atom });
+                    self.pending = Some(token);
+                    return None;
+                }
+                let mut keep = !token.data.is_empty();
+                if token.data.len() > self.limits.max_tag_length {
+                    self.error(ParserError::TagTooLong { pos: token.span.start });
+                    keep = false;
+                }
+                let tag_token = token;
+                
+                let token = self.next_token();
+                if token.kind != TokenKind::RightParen {
+                    self.error(ParserError::ExpectedToken { pos: token.span.start, token_kind: TokenKind::RightParen });
+                    self.pending = Some(token);
+                    return None;
+                }
+                
+                let value_1st_token = self.next_token();
+                self.depth += 1;
+                let sub_value = self.parse_value(value_1st_token);
+                self.depth -= 1;
+                
+                match sub_value {
+                    Some((sub_value, sub_span)) if keep => {
+                        let span = begin_span.to(&sub_span);
+                        let tag = self.builder.text(&self.lexer, &tag_token.span);
+                        Some((self.builder.tagged(tag, tag_token.span, sub_value, span), span))
+                    }
+                    _ => None,
+                }
+            }
+            _ => {
+                self.error(ParserError::UnexpectedToken { pos: token.span.start, token_kind: token.kind });
+                self.pending = Some(token);
+                None
+            }
+        }
+    }
+}
+
+/// Parses a document, recovering from errors.
+///
+/// Returns the parts of the document that could be parsed and every
+/// error found, in order of appearance. The input is valid only if
+/// there are no errors. See the `recover` module for the recovery rules.
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let text = b"(example)\nkey1 [1 2}\nkey2 \"unclosed\nkey3 ok";
+///     let (document, _, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), text);
+///     assert_eq!(document, yass_document!(("example") "key1": ["1", "2"], "key3": "ok"));
+///     assert_eq!(errors.len(), 2);
+/// }
+/// ```
+#[inline]
+pub fn parse_recovering(limits: ParserLimits, data: &[u8]) -> (yass::Document, yass::PosMap, Vec<ParserError>) {
+    parse_recovering_with_source(limits, data, yass::SourceId::UNKNOWN)
+}
+
+/// Like `parse_recovering`, but the produced positions (in the `PosMap`
+/// and in errors) have `source` as their source.
+pub fn parse_recovering_with_source(limits: ParserLimits, data: &[u8], source: yass::SourceId)
+    -> (yass::Document, yass::PosMap, Vec<ParserError>)
+{
+    let ((document, pos_map), errors) = RecoveringParser::parse(limits, data, source, OwnedBuilder);
+    (document, pos_map, errors)
+}
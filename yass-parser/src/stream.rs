@@ -0,0 +1,287 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io::Read;
+
+use super::{build, cst, Event, Lexer, OwnedBuilder, ParseStreamError, ParserError, ParserLimits, TokenKind};
+use reader::{Events, Tokens};
+
+/// Minimum number of bytes requested from the reader at once.
+const CHUNK_SIZE: usize = 8192;
+
+/// Lexer over a buffer that is filled in chunks from a reader.
+///
+/// A token is lexed when its end is in the buffer, so tokens can span
+/// chunk boundaries. The bytes before the mark are dropped when the
+/// buffer is filled again.
+struct StreamLexer<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Offset in the stream of `buf[0]`.
+    buf_offset: usize,
+    /// Index in `buf` of the first byte that has not been lexed.
+    i: usize,
+    /// Index in `buf` of the first byte that must be kept.
+    mark: usize,
+    /// Position of `buf[i]`.
+    pos: yass::Pos,
+    /// Whether `buf[i]` is in the middle of a comment.
+    in_comment: bool,
+    /// Whether a token has been lexed since the mark was set. Until
+    /// then, the mark follows the skipped spaces and comments, so they
+    /// are not kept.
+    lexed_token: bool,
+    eof: bool,
+}
+
+impl<R: Read> StreamLexer<R> {
+    fn new(reader: R, source: yass::SourceId) -> Self {
+        Self {
+            reader: reader,
+            buf: Vec::new(),
+            buf_offset: 0,
+            i: 0,
+            mark: 0,
+            pos: yass::Pos::with_source(0, 0, source),
+            in_comment: false,
+            lexed_token: false,
+            eof: false,
+        }
+    }
+    
+    /// Drops the bytes before the mark and reads more data. At least
+    /// as many bytes as the ones that have not been lexed are requested,
+    /// so lexing a long token takes linear time.
+    fn fill(&mut self) -> Result<(), ParseStreamError> {
+        if self.mark != 0 {
+            self.buf.drain(.. self.mark);
+            self.buf_offset += self.mark;
+            self.i -= self.mark;
+            self.mark = 0;
+        }
+        
+        let old_len = self.buf.len();
+        let request = CHUNK_SIZE.max(old_len - self.i);
+        self.buf.resize(old_len + request, 0);
+        loop {
+            match self.reader.read(&mut self.buf[old_len ..]) {
+                Ok(n) => {
+                    self.buf.truncate(old_len + n);
+                    self.eof = n == 0;
+                    return Ok(());
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.buf.truncate(old_len);
+                    return Err(ParseStreamError::ReadError(e));
+                }
+            }
+        }
+    }
+    
+    /// Skips spaces and comments. Returns false if more data is needed
+    /// to find the end of them.
+    fn skip_spaces_and_comments(&mut self) -> bool {
+        let mut lexer = Lexer { data: &self.buf, i: self.i, pos: self.pos };
+        let mut finished = true;
+        if self.in_comment {
+            lexer.skip_comment();
+            self.in_comment = lexer.i == lexer.data.len() && !self.eof;
+            finished = !self.in_comment;
+        }
+        
+        while finished {
+            let (last_i, last_pos) = (lexer.i, lexer.pos);
+            match lexer.get_trivia() {
+                Some((kind, _)) => {
+                    if lexer.i == lexer.data.len() && !self.eof {
+                        match kind {
+                            cst::TriviaKind::Comment => self.in_comment = true,
+                            cst::TriviaKind::Whitespace => {}
+                            // A "\r" may be followed by a "\n".
+                            cst::TriviaKind::LineBreak => {
+                                lexer.i = last_i;
+                                lexer.pos = last_pos;
+                            }
+                        }
+                        finished = false;
+                    }
+                }
+                None => break,
+            }
+        }
+        
+        self.i = lexer.i;
+        self.pos = lexer.pos;
+        if !self.lexed_token {
+            self.mark = self.i;
+        }
+        finished
+    }
+}
+
+impl<R: Read> Tokens for StreamLexer<R> {
+    type Error = ParseStreamError;
+    
+    fn next_token(&mut self, max_len: usize) -> Result<(TokenKind, yass::Span), ParseStreamError> {
+        loop {
+            if !self.skip_spaces_and_comments() {
+                self.fill()?;
+                continue;
+            }
+            
+            let mut lexer = Lexer { data: &self.buf, i: self.i, pos: self.pos };
+            let result = lexer.lex_token();
+            let at_end = match result {
+                Ok(ref token) => token.span.end_offset == lexer.data.len(),
+                Err(ParserError::UnfinishedString { .. }) => true,
+                Err(_) => false,
+            };
+            
+            let (kind, mut span) = if at_end && !self.eof {
+                // The token may continue after the end of the buffer.
+                let len = lexer.data.len() - self.i;
+                if len <= max_len {
+                    self.fill()?;
+                    continue;
+                }
+                
+                // Too long, cut it.
+                let begin_i = self.i;
+                let begin_pos = self.pos;
+                self.i = lexer.data.len();
+                self.pos.column += len as u32;
+                (TokenKind::Atom, yass::Span::new(begin_pos, self.pos, begin_i, self.i))
+            } else {
+                let token = result?;
+                self.i = lexer.i;
+                self.pos = lexer.pos;
+                (token.kind, token.span)
+            };
+            
+            span.start_offset += self.buf_offset;
+            span.end_offset += self.buf_offset;
+            self.lexed_token = true;
+            return Ok((kind, span));
+        }
+    }
+    
+    /// Gets the text of a token lexed after the mark.
+    #[inline]
+    fn text(&self, span: &yass::Span) -> &str {
+        let data = &self.buf[(span.start_offset - self.buf_offset) .. (span.end_offset - self.buf_offset)];
+        std::str::from_utf8(data).unwrap()
+    }
+    
+    /// Keeps the tokens lexed from now on until the next call.
+    #[inline]
+    fn begin_event(&mut self) {
+        self.mark = self.i;
+        self.lexed_token = false;
+    }
+}
+
+impl From<ParserError> for ParseStreamError {
+    #[inline]
+    fn from(e: ParserError) -> Self {
+        ParseStreamError::ParserError(e)
+    }
+}
+
+/// Like `Reader`, but reads the data from a `std::io::Read` in chunks.
+///
+/// Only the tokens of the current event are kept in memory, so the
+/// memory used depends on the depth of the document and the length
+/// of its atoms, but not on its size. Atoms longer than allowed by
+/// the `ParserLimits` are reported before reading them completely.
+///
+/// ```
+/// extern crate yass_parser;
+///
+/// use yass_parser::Event;
+///
+/// fn main() {
+///     let text: &[u8] = b"(example)\nkey1 [1 2]\nkey2 (tag)3";
+///     let mut reader = yass_parser::StreamReader::new(yass_parser::ParserLimits::unlimited(), text);
+///     let mut num_atoms = 0;
+///     while let Some(event) = reader.next_event().unwrap() {
+///         if let Event::Atom(..) = event {
+///             num_atoms += 1;
+///         }
+///     }
+///     assert_eq!(num_atoms, 3);
+/// }
+/// ```
+pub struct StreamReader<R> {
+    events: Events<StreamLexer<R>>,
+}
+
+impl<R: Read> StreamReader<R> {
+    #[inline]
+    pub fn new(limits: ParserLimits, reader: R) -> Self {
+        Self::with_source(limits, reader, yass::SourceId::UNKNOWN)
+    }
+    
+    /// Like `new`, but the produced positions have `source` as their
+    /// source.
+    #[inline]
+    pub fn with_source(limits: ParserLimits, reader: R, source: yass::SourceId) -> Self {
+        Self {
+            events: Events::new(limits, StreamLexer::new(reader, source)),
+        }
+    }
+    
+    /// Returns the number of arrays, structs and tagged values that
+    /// have begun and have not finished yet.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.events.depth()
+    }
+    
+    /// Returns the next event, or `None` at the end of the document.
+    /// The event borrows the reader because its text is kept only until
+    /// the next call.
+    pub fn next_event<'a>(&'a mut self) -> Result<Option<Event<'a>>, ParseStreamError> {
+        let raw_event = self.events.next_event()?;
+        let lexer = &self.events.tokens;
+        Ok(raw_event.map(|raw_event| raw_event.with_text(|span| lexer.text(span))))
+    }
+    
+    /// Returns the number of bytes kept in the buffer.
+    #[cfg(test)]
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.events.tokens.buf.len()
+    }
+
+}
+
+/// Like `parse_stream`, but reads the data in chunks as it is parsed,
+/// so errors are found without reading the rest of the stream, and
+/// the data is not kept in memory.
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+/// extern crate yass_parser;
+///
+/// fn main() {
+///     let text: &[u8] = b"(example)\nkey1 [1 2]\nkey2 (tag)3";
+///     let (document, _) = yass_parser::parse_reader(yass_parser::ParserLimits::unlimited(), text).unwrap();
+///     assert_eq!(document, yass_document!(("example") "key1": ["1", "2"], "key2": (as "tag": "3")));
+/// }
+/// ```
+pub fn parse_reader<R: Read>(limits: ParserLimits, reader: R) -> Result<(yass::Document, yass::PosMap), ParseStreamError> {
+    parse_reader_with_source(limits, reader, yass::SourceId::UNKNOWN)
+}
+
+/// Like `parse_reader`, but the produced positions (in the `PosMap`
+/// and in errors) have `source` as their source.
+pub fn parse_reader_with_source<R: Read>(limits: ParserLimits, reader: R, source: yass::SourceId)
+    -> Result<(yass::Document, yass::PosMap), ParseStreamError>
+{
+    build(&mut Events::new(limits, StreamLexer::new(reader, source)), &mut OwnedBuilder)
+}
@@ -28,21 +28,21 @@ trait GatherPositions {
 
 impl GatherPositions for yass::Value {
     fn gather_positions(&self, pos_map: &yass::PosMap, dst: &mut Vec<yass::Pos>) {
-        dst.push(pos_map.get_value_pos(self).unwrap());
+        dst.push(pos_map.get_value_pos().unwrap());
         match *self {
             yass::Value::Atom(_) => {}
             yass::Value::Array(ref array) => {
-                for item in array {
-                    item.gather_positions(pos_map, dst);
+                for (i, item) in array.iter().enumerate() {
+                    item.gather_positions(pos_map.child(i), dst);
                 }
             }
             yass::Value::Struct(ref fields) => {
-                for field in fields {
-                    field.gather_positions(pos_map, dst);
+                for (i, field) in fields.iter().enumerate() {
+                    field.gather_positions(pos_map.child(i), dst);
                 }
             }
             yass::Value::Tagged(_, ref sub_value) => {
-                sub_value.gather_positions(pos_map, dst);
+                sub_value.gather_positions(pos_map.child(0), dst);
             }
         }
     }
@@ -50,15 +50,15 @@ impl GatherPositions for yass::Value {
 
 impl GatherPositions for yass::StructField {
     fn gather_positions(&self, pos_map: &yass::PosMap, dst: &mut Vec<yass::Pos>) {
-        dst.push(pos_map.get_struct_field_pos(&self.value).unwrap());
+        dst.push(pos_map.get_struct_field_pos().unwrap());
         self.value.gather_positions(pos_map, dst);
     }
 }
 
 impl GatherPositions for yass::Document {
     fn gather_positions(&self, pos_map: &yass::PosMap, dst: &mut Vec<yass::Pos>) {
-        for field in self.root_fields.iter() {
-            field.gather_positions(pos_map, dst);
+        for (i, field) in self.root_fields.iter().enumerate() {
+            field.gather_positions(pos_map.child(i), dst);
         }
     }
 }
@@ -253,6 +253,549 @@ fn test_comment_3() {
     assert_eq!(result_doc.gather_positions_to_vec(&pos_map), expected_positions);
 }
 
+#[test]
+fn test_parse_with_source() {
+    let mut source_map = yass::SourceMap::new();
+    let first = source_map.add("first.yass");
+    let second = source_map.add("second.yass");
+    assert_eq!(source_map.name(second), Some("second.yass"));
+    
+    let (_, pos_map) = yass_parser::parse_with_source(yass_parser::ParserLimits::unlimited(), b"(test)\na [1]", second).unwrap();
+    assert_eq!(pos_map.get(&[0, 0]).get_value_pos(), Some(yass::Pos::with_source(1, 3, second)));
+    
+    let error = yass_parser::parse_with_source(yass_parser::ParserLimits::unlimited(), b"(test)\na \"b", first).unwrap_err();
+    assert_eq!(error.pos(), yass::Pos::with_source(1, 2, first));
+    assert_eq!(error.to_string(), "Unfinished string at 2:3");
+    assert_eq!(error.message(), "Unfinished string");
+    assert_eq!(source_map.display(&error).to_string(), "Unfinished string at first.yass:2:3");
+    
+    let error = yass_parser::parse(yass_parser::ParserLimits::unlimited(), b"(test)\na \"b").unwrap_err();
+    assert_eq!(error.pos().source, yass::SourceId::UNKNOWN);
+    assert_eq!(source_map.display(&error).to_string(), "Unfinished string at 2:3");
+}
+
+#[test]
+fn test_validate_with_pos_map() {
+    let src_data = b"(test)\na [1 (t)2]\nb {c 3}";
+    let (document, pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(document.validate_with_pos_map(&pos_map), Ok(()));
+    
+    let mut edited = document.clone();
+    edited.header = "a b".to_string();
+    assert_eq!(edited.validate_with_pos_map(&pos_map).unwrap_err().pos, Some(yass::Pos::new(0, 1)));
+    
+    let mut edited = document.clone();
+    edited.root_fields[1].key = "b{".to_string();
+    assert_eq!(edited.validate_with_pos_map(&pos_map).unwrap_err().pos, Some(yass::Pos::new(2, 0)));
+    
+    let mut edited = document.clone();
+    *edited.select_mut("a/[1]").unwrap()[0] = *yass_value!((as "t(": "2"));
+    let error = edited.validate_with_pos_map(&pos_map).unwrap_err();
+    assert_eq!(error.part, yass::AtomPart::Tag);
+    assert_eq!(error.pos, Some(yass::Pos::new(1, 6)));
+    
+    let mut edited = document.clone();
+    *edited.select_mut("b/c").unwrap()[0] = yass::Value::Atom("".to_string());
+    let error = edited.validate_with_pos_map(&pos_map).unwrap_err();
+    assert_eq!(error.pos, Some(yass::Pos::new(2, 5)));
+    assert_eq!(error.to_string(), "Invalid atom at \"b/c\" (3:6): Empty atom");
+    assert_eq!(edited.validate().unwrap_err().pos, None);
+}
+
+#[test]
+fn test_parse_documents() {
+    let src_data = b"\\ comment\n(a)\nx 1\ny [2]\n\n(b)(c) z {w (t)3}\n";
+    let mut documents = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), src_data);
+    
+    let (document, pos_map) = documents.next().unwrap().unwrap();
+    assert_eq!(document, yass_document!(("a") "x": "1", "y": ["2"]));
+    assert_eq!(document.gather_positions_to_vec(&pos_map), pos_array![(2, 0), (2, 2), (3, 0), (3, 2), (3, 3)]);
+    let (document, pos_map) = documents.next().unwrap().unwrap();
+    assert_eq!(document, yass_document!(("b")));
+    assert_eq!(pos_map.get_header_span().unwrap().start, yass::Pos::new(5, 1));
+    let (document, _) = documents.next().unwrap().unwrap();
+    assert_eq!(document, yass_document!(("c") "z": {"w": (as "t": "3")}));
+    assert!(documents.next().is_none());
+    assert!(documents.next().is_none());
+    
+    assert_eq!(yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), b"").count(), 0);
+    assert_eq!(yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), b" \\ only a comment\n").count(), 0);
+    
+    // A single document cannot be followed by other documents.
+    match yass_parser::parse(yass_parser::ParserLimits::unlimited(), b"(a) x 1 (b)") {
+        Err(yass_parser::ParserError::UnexpectedToken { pos, token_kind: yass_parser::TokenKind::LeftParen }) => {
+            assert_eq!(pos, yass::Pos::new(0, 8));
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_parse_documents_fail() {
+    let mut documents = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), b"(a) x 1 (b) y [ (c)");
+    assert!(documents.next().unwrap().is_ok());
+    assert_eq!(
+        documents.next().unwrap().unwrap_err(),
+        yass_parser::ParserError::UnexpectedToken { pos: yass::Pos::new(0, 19), token_kind: yass_parser::TokenKind::Eof }
+    );
+    assert!(documents.next().is_none());
+    
+    let mut documents = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), b"x (a)");
+    assert_eq!(
+        documents.next().unwrap().unwrap_err(),
+        yass_parser::ParserError::ExpectedToken { pos: yass::Pos::new(0, 0), token_kind: yass_parser::TokenKind::LeftParen }
+    );
+    assert!(documents.next().is_none());
+}
+
+#[test]
+fn test_parse_recovering() {
+    let src_data = b"(test) a [1 {b 2}] c (t)d";
+    let (document, pos_map, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    let (expected_document, expected_pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(document, expected_document);
+    assert_eq!(pos_map, expected_pos_map);
+    assert!(errors.is_empty());
+    
+    let src_data = b"(test)\na [1 2}\nb \"x\ny {c 1 ]\nd ok";
+    let (document, pos_map, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(document, yass_document!(("test") "a": ["1", "2"], "y": {"c": "1"}, "d": "ok"));
+    assert_eq!(pos_map.child(2).get_struct_field_pos(), Some(yass::Pos::new(4, 0)));
+    assert_eq!(errors, [
+        yass_parser::ParserError::UnexpectedToken { pos: yass::Pos::new(1, 6), token_kind: yass_parser::TokenKind::RightBrace },
+        yass_parser::ParserError::IllegalChrInString { pos: yass::Pos::new(2, 4), chr: b'\n' },
+        yass_parser::ParserError::UnexpectedToken { pos: yass::Pos::new(3, 7), token_kind: yass_parser::TokenKind::RightBracket },
+    ]);
+    
+    let src_data = b"(test) a 1 & b 2 ~~ c (t";
+    let (document, _, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(document, yass_document!(("test") "a": "1", "b": "2"));
+    assert_eq!(errors, [
+        yass_parser::ParserError::IllegalChr { pos: yass::Pos::new(0, 11), chr: b'&' },
+        yass_parser::ParserError::IllegalChr { pos: yass::Pos::new(0, 17), chr: b'~' },
+        yass_parser::ParserError::ExpectedToken { pos: yass::Pos::new(0, 24), token_kind: yass_parser::TokenKind::RightParen },
+    ]);
+    
+    let src_data = b"(test) a 1 &&\\ comment\nb 2";
+    let (document, _, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(document, yass_document!(("test") "a": "1", "b": "2"));
+    assert_eq!(errors, [
+        yass_parser::ParserError::IllegalChr { pos: yass::Pos::new(0, 11), chr: b'&' },
+    ]);
+    
+    let mut source_map = yass::SourceMap::new();
+    let source = source_map.add("test.yass");
+    let (_, pos_map, errors) = yass_parser::parse_recovering_with_source(yass_parser::ParserLimits::unlimited(), b"(test) a 1 & b 2", source);
+    assert_eq!(pos_map.child(1).get_struct_field_pos(), Some(yass::Pos::with_source(0, 13, source)));
+    assert_eq!(errors, [yass_parser::ParserError::IllegalChr { pos: yass::Pos::with_source(0, 11, source), chr: b'&' }]);
+}
+
+#[test]
+fn test_parse_recovering_resync() {
+    let src_data = b"a 1 [x] b {2 {} c 3} d";
+    let (document, _, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(document, yass_document!(("") "a": "1", "b": {"2": {}, "c": "3"}));
+    assert_eq!(errors, [
+        yass_parser::ParserError::ExpectedToken { pos: yass::Pos::new(0, 0), token_kind: yass_parser::TokenKind::LeftParen },
+        yass_parser::ParserError::UnexpectedToken { pos: yass::Pos::new(0, 4), token_kind: yass_parser::TokenKind::LeftBracket },
+        yass_parser::ParserError::UnexpectedToken { pos: yass::Pos::new(0, 22), token_kind: yass_parser::TokenKind::Eof },
+    ]);
+    
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 1;
+    limits.max_array_size = 2;
+    let src_data = b"(test) a [[1] 2] b [1 2 3 4] c 5";
+    let (document, _, errors) = yass_parser::parse_recovering(limits, src_data);
+    assert_eq!(document, yass_document!(("test") "a": ["2"], "b": ["1", "2"], "c": "5"));
+    assert_eq!(errors, [
+        yass_parser::ParserError::TooDeep { pos: yass::Pos::new(0, 10) },
+        yass_parser::ParserError::ArrayTooBig { pos: yass::Pos::new(0, 24) },
+    ]);
+}
+
+fn read_value<'a>(reader: &mut yass_parser::Reader<'a>, event: yass_parser::Event<'a>) -> yass::Value {
+    use self::yass_parser::Event;
+    match event {
+        Event::Atom(atom, _) => yass::Value::Atom(atom.to_string()),
+        Event::Tag(tag, _) => {
+            let event = reader.next().unwrap().unwrap();
+            yass::Value::Tagged(tag.to_string(), Box::new(read_value(reader, event)))
+        }
+        Event::BeginArray(_) => {
+            let mut items = Vec::new();
+            loop {
+                match reader.next().unwrap().unwrap() {
+                    Event::EndArray(_) => break,
+                    event => items.push(Box::new(read_value(reader, event))),
+                }
+            }
+            yass::Value::Array(items)
+        }
+        Event::BeginStruct(_) => yass::Value::Struct(read_fields(reader)),
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+fn read_fields(reader: &mut yass_parser::Reader) -> Vec<yass::StructField> {
+    let mut fields = Vec::new();
+    while let Some(event) = reader.next() {
+        match event.unwrap() {
+            yass_parser::Event::Key(key, _) => {
+                let event = reader.next().unwrap().unwrap();
+                fields.push(yass::StructField {
+                    key: key.to_string(),
+                    value: Box::new(read_value(reader, event)),
+                });
+            }
+            yass_parser::Event::EndStruct(_) => break,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    fields
+}
+
+#[test]
+fn test_reader() {
+    let src_data = b"(test) a 1 b [x (t)[] {}] c {d (t1)(t2){e \"f\"} g []}";
+    let mut reader = yass_parser::Reader::new(yass_parser::ParserLimits::unlimited(), src_data);
+    let header = match reader.next().unwrap().unwrap() {
+        yass_parser::Event::Header(header, span) => {
+            assert_eq!(span.start, yass::Pos::new(0, 1));
+            header
+        }
+        event => panic!("unexpected event {:?}", event),
+    };
+    let document = yass::Document {
+        header: header.to_string(),
+        root_fields: read_fields(&mut reader),
+    };
+    assert!(reader.next().is_none());
+    assert_eq!(reader.depth(), 0);
+    
+    let (expected_document, _) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(document, expected_document);
+    
+    let mut reader = yass_parser::Reader::new(yass_parser::ParserLimits::unlimited(), b"(test) a (t)[1]");
+    assert_eq!(reader.nth(3).unwrap().unwrap(), yass_parser::Event::BeginArray(
+        yass::Span::new(yass::Pos::new(0, 12), yass::Pos::new(0, 13), 12, 13),
+    ));
+    assert_eq!(reader.depth(), 2);
+    assert_eq!(reader.next().unwrap().unwrap(), yass_parser::Event::Atom(
+        "1",
+        yass::Span::new(yass::Pos::new(0, 13), yass::Pos::new(0, 14), 13, 14),
+    ));
+    assert_eq!(reader.next().unwrap().unwrap(), yass_parser::Event::EndArray(
+        yass::Span::new(yass::Pos::new(0, 14), yass::Pos::new(0, 15), 14, 15),
+    ));
+    assert_eq!(reader.depth(), 0);
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_reader_fail() {
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 2;
+    limits.max_atom_length = 3;
+    limits.max_key_length = 3;
+    limits.max_tag_length = 3;
+    limits.max_array_size = 2;
+    limits.max_struct_size = 2;
+    
+    let inputs: &[&[u8]] = &[
+        b"",
+        b"()",
+        b"(a b)",
+        b"(a) x",
+        b"(a) x ]",
+        b"(a) x [1 2",
+        b"(a) x {y}",
+        b"(a) x [1 2 3]",
+        b"(a) x {y 1 z 2 w 3}",
+        b"(a) x [[[1]]]",
+        b"(a) x ((t)1)",
+        b"(a) x (t)(u)(v)1",
+        b"(a) x (tags)1",
+        b"(a) keys 1",
+        b"(a) x atoms",
+        b"(a) x { [ }",
+        b"(a) x (t 1",
+        b"(a) x 1 (b)",
+        b"(a) x \"abc",
+    ];
+    for input in inputs {
+        let expected_error = yass_parser::parse(limits, input).unwrap_err();
+        let mut reader = yass_parser::Reader::new(limits, input);
+        let error = loop {
+            match reader.next().unwrap() {
+                Ok(_) => {}
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(error, expected_error);
+        assert!(reader.next().is_none());
+    }
+}
+
+/// Reader that returns one byte at a time.
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> std::io::Read for ByteReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1 ..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn test_parse_reader() {
+    let inputs: &[&[u8]] = &[
+        b"(test)",
+        b"(test) \\ comment",
+        b"(test)\r\na \"x\\\"y\" \\ comment\r\n\r\nb [1 (t)2 {}]\n\tc {d (t1)(t2)[e \"\"]}\r",
+        b"(test)a\"1 2\"b[]c{}d(t)e",
+    ];
+    for input in inputs {
+        let expected = yass_parser::parse(yass_parser::ParserLimits::unlimited(), input).unwrap();
+        let result = yass_parser::parse_reader(yass_parser::ParserLimits::unlimited(), *input).unwrap();
+        assert_eq!(result, expected);
+        let result = yass_parser::parse_reader(yass_parser::ParserLimits::unlimited(), ByteReader(input)).unwrap();
+        assert_eq!(result, expected);
+    }
+    
+    let source = yass::SourceId(1);
+    let (_, pos_map) = yass_parser::parse_reader_with_source(yass_parser::ParserLimits::unlimited(), ByteReader(b"(test) a 1"), source).unwrap();
+    assert_eq!(pos_map.child(0).get_value_pos(), Some(yass::Pos::with_source(0, 9, source)));
+}
+
+#[test]
+fn test_parse_reader_fail() {
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 2;
+    limits.max_atom_length = 3;
+    limits.max_key_length = 3;
+    limits.max_tag_length = 3;
+    limits.max_array_size = 2;
+    limits.max_struct_size = 2;
+    
+    let inputs: &[&[u8]] = &[
+        b"",
+        b"(a b)",
+        b"(a) x [1 2",
+        b"(a) x [1 2 3]",
+        b"(a) x [[[1]]]",
+        b"(a) x (tags)1",
+        b"(a) keys 1",
+        b"(a) x atoms",
+        b"(a) x \"ab",
+        b"(a) x \"a\nb\"",
+        b"(a) x 1 ~",
+    ];
+    for input in inputs {
+        let expected_error = yass_parser::parse(limits, input).unwrap_err();
+        match yass_parser::parse_reader(limits, ByteReader(input)) {
+            Err(yass_parser::ParseStreamError::ParserError(error)) => assert_eq!(error, expected_error),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+    
+    // An endless atom is reported without reading all of it.
+    let endless = std::io::Read::chain(&b"(a) x [1 2] y "[..], std::io::repeat(b'a'));
+    match yass_parser::parse_reader(limits, endless) {
+        Err(yass_parser::ParseStreamError::ParserError(error)) => {
+            assert_eq!(error, yass_parser::ParserError::AtomTooLong { pos: yass::Pos::new(0, 14) });
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    
+    // Long runs of spaces or comments between tokens are not kept.
+    fn check_trivia<R: std::io::Read>(trivia: R) {
+        let input = std::io::Read::chain(std::io::Read::chain(&b"(a) x 1 "[..], trivia), &b"\ny 2"[..]);
+        let mut reader = yass_parser::StreamReader::new(yass_parser::ParserLimits::unlimited(), input);
+        let mut num_events = 0;
+        while reader.next_event().unwrap().is_some() {
+            assert!(reader.buffered_len() < 1 << 16);
+            num_events += 1;
+        }
+        assert_eq!(num_events, 5);
+    }
+    check_trivia(std::io::Read::take(std::io::repeat(b' '), 1 << 20));
+    check_trivia(std::io::Read::chain(&b"\\ "[..], std::io::Read::take(std::io::repeat(b'c'), 1 << 20)));
+    
+    struct FailingReader;
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "failed"))
+        }
+    }
+    let failing = std::io::Read::chain(&b"(a) x 1"[..], FailingReader);
+    match yass_parser::parse_reader(limits, failing) {
+        Err(yass_parser::ParseStreamError::ReadError(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_stream_reader() {
+    let input = b"(test) a [x (t)1]";
+    let mut reader = yass_parser::StreamReader::new(yass_parser::ParserLimits::unlimited(), ByteReader(input));
+    let mut expected_events = yass_parser::Reader::new(yass_parser::ParserLimits::unlimited(), input);
+    while let Some(event) = reader.next_event().unwrap() {
+        assert_eq!(event, expected_events.next().unwrap().unwrap());
+        assert_eq!(reader.depth(), expected_events.depth());
+    }
+    assert!(expected_events.next().is_none());
+}
+
+#[test]
+fn test_tokenizer() {
+    use self::yass_parser::tokenizer::{AtomKind, Kind, Tokenizer};
+    use self::yass_parser::cst::TriviaKind;
+    
+    let src_data = b"(test)\r\na [\"x\" b\"\\xFF\"]\t\\ comment\nb {}";
+    let tokens: Vec<_> = Tokenizer::new(src_data).collect();
+    let kinds: Vec<_> = tokens.iter().map(|token| token.kind.clone()).collect();
+    assert_eq!(kinds, [
+        Kind::LeftParen,
+        Kind::Atom(AtomKind::Bare),
+        Kind::RightParen,
+        Kind::Trivia(TriviaKind::LineBreak),
+        Kind::Atom(AtomKind::Bare),
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::LeftBracket,
+        Kind::Atom(AtomKind::String),
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::Atom(AtomKind::String),
+        Kind::RightBracket,
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::Trivia(TriviaKind::Comment),
+        Kind::Trivia(TriviaKind::LineBreak),
+        Kind::Atom(AtomKind::Bare),
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::LeftBrace,
+        Kind::RightBrace,
+    ]);
+    assert_eq!(tokens[9].text, b"b\"\\xFF\"");
+    assert_eq!(tokens[9].span, yass::Span::new(yass::Pos::new(1, 7), yass::Pos::new(1, 14), 15, 22));
+    let text: Vec<u8> = tokens.iter().flat_map(|token| token.text.iter().cloned()).collect();
+    assert_eq!(text, src_data.to_vec());
+}
+
+#[test]
+fn test_tokenizer_errors() {
+    use self::yass_parser::tokenizer::{AtomKind, Kind, Tokenizer};
+    use self::yass_parser::cst::TriviaKind;
+    
+    let src_data = b"a &~\\c\n\"x\ty\" z\n\"open";
+    let tokens: Vec<_> = Tokenizer::new(src_data).collect();
+    let kinds: Vec<_> = tokens.iter().map(|token| token.kind.clone()).collect();
+    assert_eq!(kinds, [
+        Kind::Atom(AtomKind::Bare),
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::Error(yass_parser::ParserError::IllegalChr { pos: yass::Pos::new(0, 2), chr: b'&' }),
+        Kind::Trivia(TriviaKind::Comment),
+        Kind::Trivia(TriviaKind::LineBreak),
+        Kind::Error(yass_parser::ParserError::IllegalChrInString { pos: yass::Pos::new(1, 2), chr: b'\t' }),
+        Kind::Trivia(TriviaKind::Whitespace),
+        Kind::Atom(AtomKind::Bare),
+        Kind::Trivia(TriviaKind::LineBreak),
+        Kind::Error(yass_parser::ParserError::UnfinishedString { pos: yass::Pos::new(2, 0) }),
+    ]);
+    assert_eq!(tokens[2].text, b"&~");
+    assert_eq!(tokens[5].text, b"\"x\ty\"");
+    let text: Vec<u8> = tokens.iter().flat_map(|token| token.text.iter().cloned()).collect();
+    assert_eq!(text, src_data.to_vec());
+}
+
+fn diagnostic_string(limits: yass_parser::ParserLimits, src_data: &[u8]) -> String {
+    let error = yass_parser::parse(limits, src_data).unwrap_err();
+    yass_parser::Diagnostic::new(src_data, &error).to_string()
+}
+
+#[test]
+fn test_diagnostic() {
+    let limits = yass_parser::ParserLimits::unlimited();
+    assert_eq!(
+        diagnostic_string(limits, b"(t)\na {b [1\n"),
+        "Unexpected token Eof at 3:1\n\
+         3 | \n\
+         \x20 | ^\n\
+         \x20 = expected one of: atom, `(`, `[`, `{`, `]`\n\
+         \x20 = note: unclosed `[` opened at 2:6\n\
+         \x20 = note: unclosed `{` opened at 2:3",
+    );
+    assert_eq!(
+        diagnostic_string(limits, b"(t"),
+        "Expected token RightParen at 1:3\n1 | (t\n  |   ^\n  = expected `)`",
+    );
+    assert_eq!(
+        diagnostic_string(limits, b"abc\r\n"),
+        "Expected token LeftParen at 1:1\n1 | abc\n  | ^^^\n  = expected `(`",
+    );
+    assert_eq!(
+        diagnostic_string(limits, b"(t)\r\n\r\n\t[x]"),
+        "Unexpected token LeftBracket at 3:2\n3 | \t[x]\n  | \t^\n  = expected one of: atom, end of input",
+    );
+    assert_eq!(
+        diagnostic_string(limits, b"(t) a (b) \"x\ty\""),
+        "Illegal character 0x09 in string at 1:13\n1 | (t) a (b) \"x\ty\"\n  |             ^",
+    );
+    
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 1;
+    assert_eq!(
+        diagnostic_string(limits, b"(t) a [[x]"),
+        "Maximum depth exceeded at 1:8\n1 | (t) a [[x]\n  |        ^",
+    );
+}
+
+#[test]
+fn test_diagnostic_context() {
+    let src_data = b"(t)\na [1}\nb {c ]";
+    let (_, _, errors) = yass_parser::parse_recovering(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(errors.len(), 2);
+    
+    let diagnostic = yass_parser::Diagnostic::new(src_data, &errors[0]);
+    assert_eq!(diagnostic.error(), &errors[0]);
+    assert_eq!(diagnostic.unclosed(), [(yass_parser::TokenKind::LeftBracket, yass::Pos::new(1, 2))]);
+    assert_eq!(diagnostic.expected().len(), 5);
+    
+    // The context of later errors is not known.
+    let diagnostic = yass_parser::Diagnostic::new(src_data, &errors[1]);
+    assert!(diagnostic.unclosed().is_empty());
+    assert!(diagnostic.expected().is_empty());
+    assert_eq!(diagnostic.to_string(), "Unexpected token RightBracket at 3:6\n3 | b {c ]\n  |      ^");
+    
+    let mut source_map = yass::SourceMap::new();
+    let source = source_map.add("file.yass");
+    let src_data = b"(t) a {";
+    let error = yass_parser::parse_with_source(yass_parser::ParserLimits::unlimited(), src_data, source).unwrap_err();
+    let diagnostic = yass_parser::Diagnostic::new(src_data, &error);
+    assert_eq!(
+        source_map.display(&diagnostic).to_string(),
+        "Unexpected token Eof at file.yass:1:8\n\
+         1 | (t) a {\n\
+         \x20 |        ^\n\
+         \x20 = expected one of: atom, `}`\n\
+         \x20 = note: unclosed `{` opened at file.yass:1:7",
+    );
+    
+    // Errors in later documents of a stream.
+    let src_data = b"(a) x [1]\n(b) y [1 2}";
+    let error = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), src_data).nth(1).unwrap().unwrap_err();
+    let diagnostic = yass_parser::Diagnostic::new(src_data, &error);
+    assert_eq!(diagnostic.unclosed(), [(yass_parser::TokenKind::LeftBracket, yass::Pos::new(1, 6))]);
+    assert_eq!(diagnostic.expected().len(), 5);
+    
+    let src_data = b"(a) x 1 (b) y 2";
+    let error = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap_err();
+    let diagnostic = yass_parser::Diagnostic::new(src_data, &error);
+    assert_eq!(diagnostic.expected(), [yass_parser::TokenKind::Atom, yass_parser::TokenKind::Eof]);
+}
+
 #[test]
 fn test_fail_empty() {
     let src_data = b"";
@@ -354,3 +897,327 @@ fn test_fail_struct_too_big() {
     let expected_error = yass_parser::ParserError::StructTooBig { pos: yass::Pos::new(0, 18) };
     assert_eq!(yass_parser::parse(limits, src_data).unwrap_err(), expected_error);
 }
+
+fn span(start: (u32, u32), end: (u32, u32), start_offset: usize, end_offset: usize) -> yass::Span {
+    yass::Span::new(yass::Pos::new(start.0, start.1), yass::Pos::new(end.0, end.1), start_offset, end_offset)
+}
+
+#[test]
+fn test_spans_1() {
+    let src_data = b"(test)\na [1 (t)2]\nb {\n  c d\n}";
+    let (_, pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(pos_map.get_header_span(), Some(span((0, 1), (0, 5), 1, 5)));
+    
+    let field_a = pos_map.child(0);
+    assert_eq!(field_a.get_key_span(), Some(span((1, 0), (1, 1), 7, 8)));
+    assert_eq!(field_a.get_struct_field_span(), Some(span((1, 0), (1, 10), 7, 17)));
+    assert_eq!(field_a.get_value_span(), Some(span((1, 2), (1, 10), 9, 17)));
+    assert_eq!(field_a.child(0).get_value_span(), Some(span((1, 3), (1, 4), 10, 11)));
+    assert_eq!(field_a.child(1).get_value_span(), Some(span((1, 5), (1, 9), 12, 16)));
+    assert_eq!(field_a.child(1).get_tag_span(), Some(span((1, 6), (1, 7), 13, 14)));
+    assert_eq!(field_a.child(1).child(0).get_value_span(), Some(span((1, 8), (1, 9), 15, 16)));
+    
+    let field_b = pos_map.child(1);
+    assert_eq!(field_b.get_struct_field_span(), Some(span((2, 0), (4, 1), 18, 29)));
+    assert_eq!(field_b.get_value_span(), Some(span((2, 2), (4, 1), 20, 29)));
+    assert_eq!(field_b.child(0).get_key_span(), Some(span((3, 2), (3, 3), 24, 25)));
+    assert_eq!(field_b.child(0).get_struct_field_span(), Some(span((3, 2), (3, 5), 24, 27)));
+    assert_eq!(pos_map.get(&[1, 0]), field_b.child(0));
+}
+
+#[test]
+fn test_spans_string() {
+    let src_data = b"(test) a \"x y\"\\comment";
+    let (_, pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    let field = pos_map.child(0);
+    assert_eq!(field.get_value_span(), Some(span((0, 9), (0, 14), 9, 14)));
+    assert_eq!(field.get_value_pos(), Some(yass::Pos::new(0, 9)));
+    assert_eq!(field.get_struct_field_pos(), Some(yass::Pos::new(0, 7)));
+}
+
+#[test]
+fn test_pos_map_survives_clone() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    
+    let src_data = b"(test) a [1 2]";
+    let (result_doc, pos_map) = {
+        let (result_doc, pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+        (result_doc.clone(), pos_map.clone())
+    };
+    assert_send_sync(&pos_map);
+    assert_eq!(result_doc.gather_positions_to_vec(&pos_map), pos_array![(0, 7), (0, 9), (0, 10), (0, 12)]);
+    assert_eq!(pos_map.get(&[0, 5]).get_value_pos(), None);
+}
+
+#[test]
+fn test_parse_borrowed() {
+    let src_data = b"(test)\nint 1\nstr \"a b\" \\ comment\narray [x (t){k v}]\n";
+    let limits = yass_parser::ParserLimits::unlimited();
+    let borrowed = yass_parser::parse_borrowed(limits, src_data).unwrap();
+    assert_eq!(borrowed.root_fields[1], yass::BorrowedStructField { key: "str", value: yass::BorrowedValue::Atom("\"a b\"") });
+    let (owned, _) = yass_parser::parse(limits, src_data).unwrap();
+    assert_eq!(borrowed, owned.as_borrowed());
+    assert_eq!(borrowed.into_owned(), owned);
+}
+
+#[test]
+fn test_parse_borrowed_fail() {
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 1;
+    for &src_data in [&b"(test) a [[b]]"[..], b"(test) a \"b", b"(test) a [b"].iter() {
+        assert_eq!(yass_parser::parse_borrowed(limits, src_data).unwrap_err(), yass_parser::parse(limits, src_data).unwrap_err());
+    }
+}
+
+#[test]
+fn test_parse_flat() {
+    let src_data = b"(test)\nint 1\nstr \"a b\"\narray [x (t){k v} []]\nempty {}\n";
+    let limits = yass_parser::ParserLimits::unlimited();
+    let flat = yass_parser::parse_flat(limits, src_data).unwrap();
+    let (owned, _) = yass_parser::parse(limits, src_data).unwrap();
+    assert_eq!(flat.to_document(), owned);
+    assert_eq!(flat, yass::FlatDocument::from_document(&owned));
+    assert_eq!(flat.num_nodes(), 9);
+    
+    let tagged = flat.root_fields().get(2).unwrap().child(1).unwrap();
+    assert_eq!(tagged.child(0).unwrap().child(0).unwrap().key(), Some("k"));
+    assert_eq!(yass_parser::parse_flat(limits, b"(test) a [b").unwrap_err(), yass_parser::parse(limits, b"(test) a [b").unwrap_err());
+}
+
+#[test]
+fn test_cst_round_trip() {
+    let src_data = b"\\ leading comment\r\n(test)  \\ header\n\na  [1\t\"x y\"  (t) 2]\n\\ between\nb {\r  c 0x10 \\ hex\n  d{}\n}\n\n";
+    let cst = yass_parser::cst::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(cst.to_bytes(), src_data.to_vec());
+    
+    let (expected_doc, _) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    assert_eq!(cst.to_document(), expected_doc);
+    
+    let field_a = &cst.root_fields[0];
+    assert_eq!(field_a.key.leading_trivia, vec![
+        yass_parser::cst::Trivia::whitespace("  "),
+        yass_parser::cst::Trivia::comment("\\ header"),
+        yass_parser::cst::Trivia::line_break("\n"),
+        yass_parser::cst::Trivia::line_break("\n"),
+    ]);
+    assert_eq!(cst.root_fields[1].key.leading_trivia[1], yass_parser::cst::Trivia::comment("\\ between"));
+    assert_eq!(cst.trailing_trivia.len(), 2);
+}
+
+#[test]
+fn test_cst_from_document() {
+    let document = yass_document!(("test") "a": ["1", (as "t": {"b": "2"})], "c": {}, "d": []);
+    let cst = yass_parser::cst::Document::from_document(&document);
+    let expected_text = "(test)\na [\n  1\n  (t){\n    b 2\n  }\n]\nc {}\nd []";
+    assert_eq!(String::from_utf8(cst.to_bytes()).unwrap(), expected_text);
+    assert_eq!(cst.to_document(), document);
+}
+
+#[test]
+fn test_cst_fail() {
+    let src_data = b"(test) a [1 2";
+    let cst_result = yass_parser::cst::parse(yass_parser::ParserLimits::unlimited(), src_data);
+    let result = yass_parser::parse(yass_parser::ParserLimits::unlimited(), src_data);
+    assert_eq!(cst_result.unwrap_err(), result.unwrap_err());
+    
+    let src_data = b"(test) a [[1]]";
+    let mut limits = yass_parser::ParserLimits::unlimited();
+    limits.max_depth = 1;
+    assert_eq!(yass_parser::cst::parse(limits, src_data).unwrap_err(), yass_parser::ParserError::TooDeep { pos: yass::Pos::new(0, 10) });
+}
+
+fn cst_edit<F>(src_data: &str, f: F) -> String
+    where F: FnOnce(&mut yass_parser::cst::Document) -> Result<(), yass_parser::cst::EditError>
+{
+    let mut cst = yass_parser::cst::parse(yass_parser::ParserLimits::unlimited(), src_data.as_bytes()).unwrap();
+    f(&mut cst).unwrap();
+    let result = String::from_utf8(cst.to_bytes()).unwrap();
+    let (_, _) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), result.as_bytes()).unwrap();
+    result
+}
+
+fn path(text: &str) -> yass::Path {
+    yass::Path::parse(text).unwrap()
+}
+
+#[test]
+fn test_cst_set() {
+    let src_data = "\\ Version file\n(example-3)\nname \"x\"  \\ the name\nversion 1  \\ bump me\nitem (text){\n    data \"a\"\n}\n";
+    
+    let result = cst_edit(src_data, |cst| cst.set(&path("version"), &yass::Value::Atom("2".to_string())));
+    assert_eq!(result, src_data.replace("version 1", "version 2"));
+    
+    let result = cst_edit(src_data, |cst| cst.set(&path("item(text)/data"), &yass_value!(["1", {"k": "v"}])));
+    assert_eq!(result, src_data.replace("data \"a\"", "data [\n        1\n        {\n            k v\n        }\n    ]"));
+}
+
+#[test]
+fn test_cst_insert() {
+    let src_data = "(test) \\ header\r\na {\r\n\tb 1\r\n}\r\nc [1 2]\r\nd {}\r\n";
+    
+    let result = cst_edit(src_data, |cst| cst.insert_field(&path("a"), "x", &yass_value!({"y": "2"})));
+    assert_eq!(result, src_data.replace("\tb 1\r\n", "\tb 1\r\n\tx {\r\n\t\ty 2\r\n\t}\r\n"));
+    
+    let result = cst_edit(src_data, |cst| cst.insert_field(&yass::Path::new(), "e", &yass_value!("3")));
+    assert_eq!(result, src_data.replace("d {}\r\n", "d {}\r\ne 3\r\n"));
+    
+    let result = cst_edit(src_data, |cst| cst.insert_field(&path("d"), "x", &yass_value!("1")));
+    assert_eq!(result, src_data.replace("d {}", "d {\r\n\tx 1\r\n}"));
+    
+    let result = cst_edit(src_data, |cst| cst.insert_item(&path("c"), 0, &yass_value!("0")));
+    assert_eq!(result, src_data.replace("[1 2]", "[0 1 2]"));
+    
+    let result = cst_edit(src_data, |cst| cst.insert_item(&path("c"), 2, &yass_value!("3")));
+    assert_eq!(result, src_data.replace("[1 2]", "[1 2 3]"));
+    
+    // The layout is detected when parsing, so it is kept after the
+    // only indented line is removed.
+    let result = cst_edit(src_data, |cst| {
+        cst.remove(&path("a/b"))?;
+        cst.insert_field(&path("d"), "x", &yass_value!("1"))
+    });
+    assert_eq!(result, src_data.replace("a {\r\n\tb 1\r\n}", "a {\r\n}").replace("d {}", "d {\r\n\tx 1\r\n}"));
+}
+
+#[test]
+fn test_cst_remove() {
+    let src_data = "(test) \\ header\na 1  \\ about a\n\\ about b\nb {\n  c 1\n  d 2  \\ about d\n}\ne [1 2 3]\n";
+    
+    let result = cst_edit(src_data, |cst| cst.remove(&path("a")));
+    assert_eq!(result, "(test) \\ header\n\\ about b\nb {\n  c 1\n  d 2  \\ about d\n}\ne [1 2 3]\n");
+    
+    let result = cst_edit(src_data, |cst| cst.remove(&path("b")));
+    assert_eq!(result, "(test) \\ header\na 1  \\ about a\ne [1 2 3]\n");
+    
+    let result = cst_edit(src_data, |cst| cst.remove(&path("b/d")));
+    assert_eq!(result, "(test) \\ header\na 1  \\ about a\n\\ about b\nb {\n  c 1\n}\ne [1 2 3]\n");
+    
+    let result = cst_edit(src_data, |cst| cst.remove(&path("e/[0]")));
+    assert_eq!(result, src_data.replace("[1 2 3]", "[2 3]"));
+    
+    let result = cst_edit(src_data, |cst| cst.remove(&path("e/[1]")));
+    assert_eq!(result, src_data.replace("[1 2 3]", "[1 3]"));
+}
+
+#[test]
+fn test_cst_edit_fail() {
+    let src_data = b"(test) a 1 a 2 b [1]";
+    let mut cst = yass_parser::cst::parse(yass_parser::ParserLimits::unlimited(), src_data).unwrap();
+    let value = yass_value!("x");
+    assert_eq!(cst.set(&path("c"), &value), Err(yass_parser::cst::EditError::NotFound { path: path("c") }));
+    assert_eq!(cst.set(&path("a"), &value), Err(yass_parser::cst::EditError::MultipleMatches { path: path("a"), count: 2 }));
+    assert_eq!(cst.remove(&yass::Path::new()), Err(yass_parser::cst::EditError::IsRoot));
+    assert_eq!(cst.insert_field(&path("b"), "k", &value), Err(yass_parser::cst::EditError::NotAStruct { path: path("b") }));
+    assert_eq!(cst.insert_item(&path("a[0]"), 0, &value), Err(yass_parser::cst::EditError::NotAnArray { path: path("a[0]") }));
+    assert_eq!(cst.insert_item(&path("b"), 2, &value), Err(yass_parser::cst::EditError::IndexOutOfRange { path: path("b"), index: 2 }));
+    assert_eq!(cst.to_bytes(), src_data.to_vec());
+}
+
+fn include_test_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("yass-parser-test-{}-{}", name, std::process::id()));
+    for &(path, text) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_include() {
+    use self::yass_parser::include::{load_file, IncludeLimits};
+    
+    let dir = include_test_dir("include", &[
+        ("main.yass", "(main)\nname example\ndb (include)\"sub/db.yass\"\nlist [1 (include)\"sub/db.yass\"]"),
+        ("sub/db.yass", "(db)\nhost local\nport (include)\"port.yass\""),
+        ("sub/port.yass", "(port)\n\nnumber 80"),
+    ]);
+    let loaded = load_file(yass_parser::ParserLimits::unlimited(), IncludeLimits::unlimited(), &dir.join("main.yass")).unwrap();
+    assert_eq!(loaded.document, yass_document!(
+        ("main")
+        "name": "example",
+        "db": {"host": "local", "port": {"number": "80"}},
+        "list": ["1", {"host": "local", "port": {"number": "80"}}]
+    ));
+    assert_eq!(loaded.files, [dir.join("main.yass"), dir.join("sub/db.yass"), dir.join("sub/port.yass")]);
+    
+    // The included struct is written in the including file, its fields
+    // in the included file.
+    let db = loaded.file_map.child(1).unwrap();
+    assert_eq!(db.file(), 0);
+    assert_eq!(loaded.pos_map.child(1).get_value_pos(), Some(yass::Pos::with_source(2, 3, yass::SourceId(1))));
+    assert_eq!(db.child(0).unwrap().file(), 1);
+    assert_eq!(loaded.pos_map.get(&[1, 0]).get_struct_field_pos(), Some(yass::Pos::with_source(1, 0, yass::SourceId(2))));
+    assert_eq!(loaded.file_map.get(&[2, 1, 1, 0]).unwrap().file(), 2);
+    assert_eq!(loaded.pos_map.get(&[2, 1, 1, 0]).get_value_pos(), Some(yass::Pos::with_source(2, 7, yass::SourceId(3))));
+    assert_eq!(loaded.source_map.name(yass::SourceId(3)), Some(&*dir.join("sub/port.yass").display().to_string()));
+    
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_include_fail() {
+    use self::yass_parser::include::{load_file, IncludeLimits, LoadError};
+    
+    let dir = include_test_dir("include-fail", &[
+        ("a.yass", "(a)\nb (include)\"b.yass\""),
+        ("b.yass", "(b)\na (include)\"a.yass\""),
+        ("self.yass", "(self)\nx [(include)\"self.yass\"]"),
+        ("bad.yass", "(bad)\nx (include)[]"),
+        ("missing.yass", "(missing)\nx (include)\"none.yass\""),
+        ("broken.yass", "(broken)\nx ["),
+        ("uses-broken.yass", "(uses-broken)\nx (include)\"broken.yass\""),
+        ("twice.yass", "(twice)\nx (include)\"c.yass\"\ny (include)\"c.yass\""),
+        ("c.yass", "(c)\nz 1"),
+    ]);
+    let unlimited = IncludeLimits::unlimited();
+    let load = |name: &str, limits: IncludeLimits| load_file(yass_parser::ParserLimits::unlimited(), limits, &dir.join(name));
+    
+    match load("a.yass", unlimited) {
+        Err(LoadError::Cycle { file, pos, included }) => {
+            assert_eq!(file, dir.join("b.yass"));
+            assert_eq!(pos, Some(yass::Pos::with_source(1, 2, yass::SourceId(2))));
+            assert_eq!(included, dir.join("a.yass"));
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    match load("self.yass", unlimited) {
+        Err(LoadError::Cycle { ref file, pos: Some(pos), ref included }) if file == included => {
+            assert_eq!(pos, yass::Pos::with_source(1, 3, yass::SourceId(1)));
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    match load("bad.yass", unlimited) {
+        Err(LoadError::InvalidInclude { pos, .. }) => assert_eq!(pos, Some(yass::Pos::with_source(1, 11, yass::SourceId(1)))),
+        r => panic!("unexpected result {:?}", r),
+    }
+    match load("missing.yass", unlimited) {
+        Err(LoadError::File { path, error: yass_parser::ParseFileError::OpenError(_) }) => {
+            assert_eq!(path, dir.join("none.yass"));
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    match load("uses-broken.yass", unlimited) {
+        Err(error @ LoadError::File { error: yass_parser::ParseFileError::ParserError(_), .. }) => {
+            assert!(error.to_string().starts_with(&dir.join("broken.yass").display().to_string()));
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    
+    let depth_1 = IncludeLimits { max_depth: 1, max_includes: usize::max_value() };
+    assert!(load("twice.yass", depth_1).is_ok());
+    match load("a.yass", depth_1) {
+        Err(LoadError::TooDeep { file, .. }) => assert_eq!(file, dir.join("b.yass")),
+        r => panic!("unexpected result {:?}", r),
+    }
+    
+    let one_include = IncludeLimits { max_depth: usize::max_value(), max_includes: 1 };
+    match load("twice.yass", one_include) {
+        Err(LoadError::TooManyIncludes { pos, .. }) => assert_eq!(pos, Some(yass::Pos::with_source(2, 2, yass::SourceId(1)))),
+        r => panic!("unexpected result {:?}", r),
+    }
+    let twice = load("twice.yass", unlimited).unwrap();
+    assert_eq!(twice.files.len(), 2);
+    
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,154 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Tokenizer for syntax highlighters, formatters and similar tools.
+//!
+//! Every byte of the input belongs to exactly one token, including
+//! spaces, line breaks and comments. Lexical errors do not stop the
+//! tokenizer: the text that could not be lexed is returned as an error
+//! token and tokenizing continues after it.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! extern crate yass_parser;
+//!
+//! use yass_parser::tokenizer::{AtomKind, Kind, Tokenizer};
+//!
+//! fn main() {
+//!     let text = b"(example) key \"value\" \\ comment";
+//!     let kinds: Vec<_> = Tokenizer::new(text).filter(|token| !token.is_trivia()).map(|token| token.kind).collect();
+//!     assert_eq!(kinds, [
+//!         Kind::LeftParen,
+//!         Kind::Atom(AtomKind::Bare),
+//!         Kind::RightParen,
+//!         Kind::Atom(AtomKind::Bare),
+//!         Kind::Atom(AtomKind::String),
+//!     ]);
+//! }
+//! ```
+
+use yass;
+
+use super::{cst, Lexer, ParserError, TokenKind};
+
+/// Whether an atom has quoted parts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AtomKind {
+    /// An atom without quotes, such as `abc` or `-1.5`.
+    Bare,
+    /// An atom with at least a quoted part, such as `"abc"` or `b"\xFF"`.
+    String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    Atom(AtomKind),
+    Trivia(cst::TriviaKind),
+    /// Text that could not be lexed.
+    Error(ParserError),
+}
+
+/// A token produced by a `Tokenizer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: Kind,
+    pub text: &'a [u8],
+    pub span: yass::Span,
+}
+
+impl<'a> Token<'a> {
+    /// Returns whether the token is a space, line break or comment.
+    #[inline]
+    pub fn is_trivia(&self) -> bool {
+        match self.kind {
+            Kind::Trivia(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Iterator over the tokens of a text. Finishes at the end of the text.
+pub struct Tokenizer<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Tokenizer<'a> {
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_source(data, yass::SourceId::UNKNOWN)
+    }
+    
+    /// Like `new`, but the produced positions have `source` as their
+    /// source.
+    #[inline]
+    pub fn with_source(data: &'a [u8], source: yass::SourceId) -> Self {
+        Self {
+            lexer: Lexer::new(data, source),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+    
+    fn next(&mut self) -> Option<Token<'a>> {
+        let begin_i = self.lexer.i;
+        let begin_pos = self.lexer.pos;
+        if let Some((kind, text)) = self.lexer.get_trivia() {
+            return Some(Token {
+                kind: Kind::Trivia(kind),
+                text: text,
+                span: yass::Span::new(begin_pos, self.lexer.pos, begin_i, self.lexer.i),
+            });
+        }
+        
+        match self.lexer.lex_token() {
+            Ok(token) => {
+                let kind = match token.kind {
+                    TokenKind::Eof => return None,
+                    TokenKind::LeftParen => Kind::LeftParen,
+                    TokenKind::RightParen => Kind::RightParen,
+                    TokenKind::LeftBracket => Kind::LeftBracket,
+                    TokenKind::RightBracket => Kind::RightBracket,
+                    TokenKind::LeftBrace => Kind::LeftBrace,
+                    TokenKind::RightBrace => Kind::RightBrace,
+                    TokenKind::Atom => {
+                        if token.data.contains(&b'"') {
+                            Kind::Atom(AtomKind::String)
+                        } else {
+                            Kind::Atom(AtomKind::Bare)
+                        }
+                    }
+                };
+                Some(Token {
+                    kind: kind,
+                    text: token.data,
+                    span: token.span,
+                })
+            }
+            Err(error) => {
+                match error {
+                    ParserError::IllegalChr { .. } => self.lexer.skip_illegal_chrs(),
+                    _ => self.lexer.skip_bad_atom(),
+                }
+                Some(Token {
+                    kind: Kind::Error(error),
+                    text: &self.lexer.data[begin_i .. self.lexer.i],
+                    span: yass::Span::new(begin_pos, self.lexer.pos, begin_i, self.lexer.i),
+                })
+            }
+        }
+    }
+}
@@ -135,8 +135,55 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Gets the position of the value that caused the error, if known.
+    pub fn pos(&self) -> Option<yass::Pos> {
+        match *self {
+            Error::InvalidValueTypeForStruct { value_pos, .. } |
+            Error::InvalidValueTypeForTaggedUnion { value_pos, .. } |
+            Error::UnknownTaggedUnionVariant { value_pos, .. } |
+            Error::InvalidValueTypeForEnum { value_pos, .. } |
+            Error::UnknownEnumValue { value_pos, .. } |
+            Error::InvalidValueTypeForRawAtom { value_pos, .. } |
+            Error::InvalidValueTypeForBool { value_pos, .. } |
+            Error::InvalidBoolValue { value_pos, .. } |
+            Error::InvalidValueTypeForInt32 { value_pos, .. } |
+            Error::InvalidInt32Value { value_pos, .. } |
+            Error::InvalidValueTypeForUInt32 { value_pos, .. } |
+            Error::InvalidUInt32Value { value_pos, .. } |
+            Error::InvalidValueTypeForInt64 { value_pos, .. } |
+            Error::InvalidInt64Value { value_pos, .. } |
+            Error::InvalidValueTypeForUInt64 { value_pos, .. } |
+            Error::InvalidUInt64Value { value_pos, .. } |
+            Error::InvalidValueTypeForFloat { value_pos, .. } |
+            Error::InvalidFloatValue { value_pos, .. } |
+            Error::InvalidValueTypeForByteString { value_pos, .. } |
+            Error::InvalidByteStringValue { value_pos, .. } |
+            Error::InvalidValueTypeForUtf8String { value_pos, .. } |
+            Error::InvalidUtf8StringValue { value_pos, .. } |
+            Error::InvalidValueTypeForAsciiString { value_pos, .. } |
+            Error::InvalidAsciiStringValue { value_pos, .. } |
+            Error::InvalidValueTypeForArray { value_pos, .. } |
+            Error::InvalidValueTypeForTuple { value_pos, .. } |
+            Error::InvalidNumberOfTupleElements { value_pos, .. } |
+            Error::InvalidValueTypeForDictionary { value_pos, .. } => value_pos,
+            Error::UnknownStructField { field_pos, .. } |
+            Error::RepeatedStructField { field_pos, .. } => field_pos,
+            Error::MissingStructField { struct_pos, .. } => struct_pos,
+            Error::InvalidDocumentHeader { .. } => None,
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
+    #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        yass::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl yass::DisplayWithSourceMap for Error {
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&yass::SourceMap>) -> std::fmt::Result {
         match *self {
             Error::InvalidDocumentHeader { ref header } => {
                 write!(f, "Invalid document header {:?}", header)?;
@@ -146,28 +193,28 @@ impl std::fmt::Display for Error {
             Error::InvalidValueTypeForStruct { ref struct_name, value_pos } => {
                 write!(f, "Invalid value type for struct {:?}", struct_name)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::UnknownStructField { ref struct_name, ref field_name, field_pos } => {
                 write!(f, "Unknown field {:?} in struct {:?}", field_name, struct_name)?;
                 if let Some(field_pos) = field_pos {
-                    write!(f, " at {}:{}", field_pos.line + 1, field_pos.column + 1)?;
+                    write!(f, " at {}", field_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::MissingStructField { ref struct_name, ref field_name, struct_pos } => {
                 write!(f, "Missing field {:?} in struct {:?}", field_name, struct_name)?;
                 if let Some(struct_pos) = struct_pos {
-                    write!(f, " at {}:{}", struct_pos.line + 1, struct_pos.column + 1)?;
+                    write!(f, " at {}", struct_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::RepeatedStructField { ref struct_name, ref field_name, field_pos } => {
                 write!(f, "Repeated field {:?} in struct {:?}", field_name, struct_name)?;
                 if let Some(field_pos) = field_pos {
-                    write!(f, " at {}:{}", field_pos.line + 1, field_pos.column + 1)?;
+                    write!(f, " at {}", field_pos.display(source_map))?;
                 }
                 Ok(())
             }
@@ -175,14 +222,14 @@ impl std::fmt::Display for Error {
             Error::InvalidValueTypeForTaggedUnion { ref tagged_union_name, value_pos } => {
                 write!(f, "Invalid value type for tagged union {:?}", tagged_union_name)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::UnknownTaggedUnionVariant { ref tagged_union_name, ref variant_name, value_pos } => {
                 write!(f, "Unknown variant {:?} for tagged union {:?}", variant_name, tagged_union_name)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
@@ -190,14 +237,14 @@ impl std::fmt::Display for Error {
             Error::InvalidValueTypeForEnum { ref enum_name, value_pos } => {
                 write!(f, "Invalid value type for enum {:?}", enum_name)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::UnknownEnumValue { ref enum_name, ref value_name, value_pos } => {
                 write!(f, "Unknown value {:?} for enum {:?}", value_name, enum_name)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
@@ -205,154 +252,154 @@ impl std::fmt::Display for Error {
             Error::InvalidValueTypeForRawAtom { value_pos } => {
                 f.write_str("Invalid value type for raw atom")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForBool { value_pos } => {
                 f.write_str("Invalid value type for boolean")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidBoolValue { ref value, value_pos } => {
                 write!(f, "Invalid boolean value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForInt32 { value_pos } => {
                 f.write_str("Invalid value type for signed 32-bit integer")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidInt32Value { ref value, value_pos } => {
                 write!(f, "Invalid signed 32-bit integer value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForUInt32 { value_pos } => {
                 f.write_str("Invalid value type for unsigned 32-bit integer")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidUInt32Value { ref value, value_pos } => {
                 write!(f, "Invalid unsigned 32-bit integer value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForInt64 { value_pos } => {
                 f.write_str("Invalid value type for signed 64-bit integer")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidInt64Value { ref value, value_pos } => {
                 write!(f, "Invalid signed 64-bit integer value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForUInt64 { value_pos } => {
                 f.write_str("Invalid value type for unsigned 64-bit integer")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidUInt64Value { ref value, value_pos } => {
                 write!(f, "Invalid unsigned 64-bit integer value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForFloat { value_pos } => {
                 f.write_str("Invalid value type for floating point")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidFloatValue { ref value, value_pos } => {
                 write!(f, "Invalid floating point value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForByteString { value_pos } => {
                 f.write_str("Invalid value type for byte string")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidByteStringValue { ref value, value_pos } => {
                 write!(f, "Invalid byte string value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForUtf8String { value_pos } => {
                 f.write_str("Invalid value type for UTF-8 string")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidUtf8StringValue { ref value, value_pos } => {
                 write!(f, "Invalid UTF-8 string value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForAsciiString { value_pos } => {
                 f.write_str("Invalid value type for ASCII string")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidAsciiStringValue { ref value, value_pos } => {
                 write!(f, "Invalid ASCII string value {:?}", value)?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForArray { value_pos } => {
                 f.write_str("Invalid value type for array")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidValueTypeForTuple { value_pos } => {
                 f.write_str("Invalid value type for tuple")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
             Error::InvalidNumberOfTupleElements { num_elements, num_expected, value_pos } => {
                 f.write_str("Invalid number of tuple elements")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 write!(f, ", expected {}, found {}", num_expected, num_elements)?;
                 Ok(())
@@ -360,7 +407,7 @@ impl std::fmt::Display for Error {
             Error::InvalidValueTypeForDictionary { value_pos } => {
                 f.write_str("Invalid value type for dictionary")?;
                 if let Some(value_pos) = value_pos {
-                    write!(f, " at {}:{}", value_pos.line + 1, value_pos.column + 1)?;
+                    write!(f, " at {}", value_pos.display(source_map))?;
                 }
                 Ok(())
             }
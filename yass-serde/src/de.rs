@@ -0,0 +1,519 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::de::{self, IntoDeserializer};
+use yass::{AtomKind, PosMap, StructField, Value};
+
+use super::Error;
+
+fn unexpected<'a>(value: &'a Value) -> de::Unexpected<'a> {
+    match *value {
+        Value::Atom(ref atom) => de::Unexpected::Other(match AtomKind::of(atom) {
+            AtomKind::Bool => "boolean atom",
+            AtomKind::Int => "integer atom",
+            AtomKind::Float => "float atom",
+            AtomKind::String => "string atom",
+            AtomKind::Symbol => "symbol atom",
+        }),
+        Value::Array(_) => de::Unexpected::Seq,
+        Value::Tagged(..) => de::Unexpected::Other("tagged value"),
+        Value::Struct(_) => de::Unexpected::Map,
+    }
+}
+
+fn atom_error(error: yass::AtomError) -> Error {
+    de::Error::custom(error)
+}
+
+#[inline]
+fn is_unit(value: &Value) -> bool {
+    match *value {
+        Value::Struct(ref fields) => fields.is_empty(),
+        Value::Array(ref items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Deserializes a `yass::Value`, using a `yass::PosMap` to give
+/// positions to errors.
+pub struct ValueDeserializer<'de> {
+    value: &'de Value,
+    pos_map: &'de PosMap,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    #[inline]
+    pub fn new(value: &'de Value, pos_map: &'de PosMap) -> Self {
+        Self {
+            value: value,
+            pos_map: pos_map,
+        }
+    }
+    
+    fn deserialize_any_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Atom(ref atom) => match AtomKind::of(atom) {
+                AtomKind::Bool => visitor.visit_bool(self.value.as_bool().map_err(atom_error)?),
+                AtomKind::Int => if atom.starts_with('-') {
+                    visitor.visit_i64(self.value.as_i64().map_err(atom_error)?)
+                } else {
+                    visitor.visit_u64(self.value.as_u64().map_err(atom_error)?)
+                },
+                AtomKind::Float => visitor.visit_f64(self.value.as_f64().map_err(atom_error)?),
+                AtomKind::String => match yass_aux::parse_utf8_string(atom) {
+                    Some(string) => visitor.visit_string(string),
+                    None => match yass_aux::parse_byte_string(atom) {
+                        Some(bytes) => visitor.visit_byte_buf(bytes),
+                        None => Err(atom_error(yass::AtomError::InvalidString)),
+                    },
+                },
+                AtomKind::Symbol => visitor.visit_borrowed_str(atom),
+            },
+            Value::Array(ref items) => visitor.visit_seq(SeqAccess::new(items, self.pos_map)),
+            Value::Tagged(ref tag, ref sub_value) => {
+                visitor.visit_map(TaggedAccess {
+                    tag: Some(tag),
+                    tag_pos: self.pos_map.get_tag_span().map(|span| span.start),
+                    value: sub_value,
+                    pos_map: self.pos_map.child(0),
+                })
+            }
+            Value::Struct(ref fields) => visitor.visit_map(MapAccess::new(fields, self.pos_map)),
+        }
+    }
+    
+    fn deserialize_str_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Atom(ref atom) => match AtomKind::of(atom) {
+                AtomKind::String => visitor.visit_string(self.value.as_str_unescaped().map_err(atom_error)?),
+                AtomKind::Symbol => visitor.visit_borrowed_str(atom),
+                _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+            },
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+        }
+    }
+    
+    fn deserialize_bytes_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Atom(ref atom) if AtomKind::of(atom) == AtomKind::String => {
+                match yass_aux::parse_byte_string(atom) {
+                    Some(bytes) => visitor.visit_byte_buf(bytes),
+                    None => Err(atom_error(yass::AtomError::InvalidString)),
+                }
+            }
+            Value::Array(ref items) => visitor.visit_seq(SeqAccess::new(items, self.pos_map)),
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+        }
+    }
+    
+    fn deserialize_unit_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if is_unit(self.value) {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::invalid_type(unexpected(self.value), &visitor))
+        }
+    }
+    
+    fn deserialize_seq_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Array(ref items) => visitor.visit_seq(SeqAccess::new(items, self.pos_map)),
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+        }
+    }
+    
+    fn deserialize_map_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Struct(ref fields) => visitor.visit_map(MapAccess::new(fields, self.pos_map)),
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+        }
+    }
+    
+    fn deserialize_enum_at<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Tagged(ref tag, ref sub_value) => {
+                visitor.visit_enum(EnumAccess {
+                    tag: tag,
+                    tag_pos: self.pos_map.get_tag_span().map(|span| span.start),
+                    value: sub_value,
+                    pos_map: self.pos_map.child(0),
+                })
+            }
+            Value::Atom(ref atom) if AtomKind::of(atom) == AtomKind::Symbol => {
+                visitor.visit_enum(atom.as_str().into_deserializer())
+            }
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &visitor)),
+        }
+    }
+}
+
+macro_rules! deserialize_at_pos {
+    ($($method:ident => $inner:ident,)*) => {
+        $(
+            #[inline]
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let pos = self.pos_map.get_value_pos();
+                self.$inner(visitor).map_err(|error: Error| error.at(pos))
+            }
+        )*
+    }
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident => $get:ident, $visit:ident,)*) => {
+        $(
+            #[inline]
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let pos = self.pos_map.get_value_pos();
+                let value = self.value.$get().map_err(|error| atom_error(error).at(pos))?;
+                visitor.$visit(value).map_err(|error: Error| error.at(pos))
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+    
+    deserialize_at_pos! {
+        deserialize_any => deserialize_any_at,
+        deserialize_str => deserialize_str_at,
+        deserialize_string => deserialize_str_at,
+        deserialize_char => deserialize_str_at,
+        deserialize_identifier => deserialize_str_at,
+        deserialize_bytes => deserialize_bytes_at,
+        deserialize_byte_buf => deserialize_bytes_at,
+        deserialize_unit => deserialize_unit_at,
+        deserialize_seq => deserialize_seq_at,
+        deserialize_map => deserialize_map_at,
+    }
+    
+    deserialize_int! {
+        deserialize_bool => as_bool, visit_bool,
+        deserialize_i8 => as_i64, visit_i64,
+        deserialize_i16 => as_i64, visit_i64,
+        deserialize_i32 => as_i64, visit_i64,
+        deserialize_i64 => as_i64, visit_i64,
+        deserialize_u8 => as_u64, visit_u64,
+        deserialize_u16 => as_u64, visit_u64,
+        deserialize_u32 => as_u64, visit_u64,
+        deserialize_u64 => as_u64, visit_u64,
+        deserialize_f32 => as_f64, visit_f64,
+        deserialize_f64 => as_f64, visit_f64,
+    }
+    
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let pos = self.pos_map.get_value_pos();
+        match *self.value {
+            Value::Atom(ref atom) if atom == "none" => visitor.visit_none().map_err(|error: Error| error.at(pos)),
+            _ => visitor.visit_some(self),
+        }
+    }
+    
+    #[inline]
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+    
+    #[inline]
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    
+    #[inline]
+    fn deserialize_tuple<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    
+    #[inline]
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    
+    #[inline]
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+    
+    #[inline]
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let pos = self.pos_map.get_value_pos();
+        self.deserialize_enum_at(visitor).map_err(|error: Error| error.at(pos))
+    }
+    
+    #[inline]
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializes the root fields of a `yass::Document` as a map.
+pub struct DocumentDeserializer<'de> {
+    root_fields: &'de [StructField],
+    pos_map: &'de PosMap,
+}
+
+impl<'de> DocumentDeserializer<'de> {
+    #[inline]
+    pub fn new(document: &'de yass::Document, pos_map: &'de PosMap) -> Self {
+        Self {
+            root_fields: &document.root_fields,
+            pos_map: pos_map,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for DocumentDeserializer<'de> {
+    type Error = Error;
+    
+    #[inline]
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(MapAccess::new(self.root_fields, self.pos_map))
+    }
+    
+    #[inline]
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Deserializes keys and tags. Strings are unescaped, and numbers and
+/// booleans are parsed when requested.
+struct KeyDeserializer<'de> {
+    key: &'de str,
+    pos: Option<yass::Pos>,
+}
+
+macro_rules! deserialize_key_from_str {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            #[inline]
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.key.parse() {
+                    Ok(value) => visitor.$visit(value).map_err(|error: Error| error.at(self.pos)),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+    
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let result = if AtomKind::of(self.key) == AtomKind::String {
+            match yass_aux::parse_utf8_string(self.key) {
+                Some(key) => visitor.visit_string(key),
+                None => Err(atom_error(yass::AtomError::InvalidString)),
+            }
+        } else {
+            visitor.visit_borrowed_str(self.key)
+        };
+        result.map_err(|error: Error| error.at(self.pos))
+    }
+    
+    deserialize_key_from_str! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+    
+    #[inline]
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    
+    #[inline]
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.key.into_deserializer()).map_err(|error: Error| error.at(self.pos))
+    }
+    
+    forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'de> {
+    items: &'de [Box<Value>],
+    pos_map: &'de PosMap,
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> {
+    #[inline]
+    fn new(items: &'de [Box<Value>], pos_map: &'de PosMap) -> Self {
+        Self {
+            items: items,
+            pos_map: pos_map,
+            index: 0,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+    
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.index == self.items.len() {
+            return Ok(None);
+        }
+        let i = self.index;
+        self.index += 1;
+        seed.deserialize(ValueDeserializer::new(&self.items[i], self.pos_map.child(i))).map(Some)
+    }
+    
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len() - self.index)
+    }
+}
+
+struct MapAccess<'de> {
+    fields: &'de [StructField],
+    pos_map: &'de PosMap,
+    index: usize,
+}
+
+impl<'de> MapAccess<'de> {
+    #[inline]
+    fn new(fields: &'de [StructField], pos_map: &'de PosMap) -> Self {
+        Self {
+            fields: fields,
+            pos_map: pos_map,
+            index: 0,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+    
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.index == self.fields.len() {
+            return Ok(None);
+        }
+        let field_pos_map = self.pos_map.child(self.index);
+        let key_deserializer = KeyDeserializer {
+            key: &self.fields[self.index].key,
+            pos: field_pos_map.get_key_span().map(|span| span.start),
+        };
+        seed.deserialize(key_deserializer).map(Some)
+    }
+    
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let i = self.index;
+        self.index += 1;
+        seed.deserialize(ValueDeserializer::new(&self.fields[i].value, self.pos_map.child(i)))
+    }
+    
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len() - self.index)
+    }
+}
+
+/// Presents a tagged value as a map with a single entry.
+struct TaggedAccess<'de> {
+    tag: Option<&'de str>,
+    tag_pos: Option<yass::Pos>,
+    value: &'de Value,
+    pos_map: &'de PosMap,
+}
+
+impl<'de> de::MapAccess<'de> for TaggedAccess<'de> {
+    type Error = Error;
+    
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.tag.take() {
+            Some(tag) => seed.deserialize(KeyDeserializer { key: tag, pos: self.tag_pos }).map(Some),
+            None => Ok(None),
+        }
+    }
+    
+    #[inline]
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(ValueDeserializer::new(self.value, self.pos_map))
+    }
+}
+
+struct EnumAccess<'de> {
+    tag: &'de str,
+    tag_pos: Option<yass::Pos>,
+    value: &'de Value,
+    pos_map: &'de PosMap,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = ValueDeserializer<'de>;
+    
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, ValueDeserializer<'de>), Error> {
+        let variant = seed.deserialize(KeyDeserializer { key: self.tag, pos: self.tag_pos })?;
+        Ok((variant, ValueDeserializer::new(self.value, self.pos_map)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+    
+    fn unit_variant(self) -> Result<(), Error> {
+        if is_unit(self.value) {
+            Ok(())
+        } else {
+            let error: Error = de::Error::invalid_type(unexpected(self.value), &"empty struct");
+            Err(error.at(self.pos_map.get_value_pos()))
+        }
+    }
+    
+    #[inline]
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    
+    #[inline]
+    fn tuple_variant<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+    
+    #[inline]
+    fn struct_variant<V: de::Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
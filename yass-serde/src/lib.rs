@@ -0,0 +1,239 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! serde support for YASS
+//!
+//! The serde data model is mapped as follows:
+//!
+//! * Booleans, numbers, chars, strings and byte arrays are atoms,
+//!   escaped with `yass_aux`.
+//! * `None` is the atom `none`, and `Some(value)` is `value`. Hence
+//!   `Some(None)` cannot be serialized, because it would be read back
+//!   as `None`.
+//! * Units and unit structs are empty structs (`{}`).
+//! * Sequences, tuples and tuple structs are arrays. Newtype structs
+//!   are their inner value.
+//! * Structs and maps are structs. Map keys must be booleans, numbers,
+//!   chars or strings.
+//! * Enum variants are tagged values, with the name of the variant as
+//!   tag: `(Unit){}`, `(Newtype)value`, `(Tuple)[a b]` and
+//!   `(Struct){a 1}`. Unit variants can also be written as a plain atom
+//!   when deserializing.
+//!
+//! The root of a document must be a struct or a map.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! extern crate yass_serde;
+//! #[macro_use]
+//! extern crate serde_derive;
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! enum Shape {
+//!     Circle { radius: f64 },
+//!     Square(f64),
+//! }
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Drawing {
+//!     name: String,
+//!     shapes: Vec<Shape>,
+//! }
+//!
+//! fn main() {
+//!     let drawing = Drawing {
+//!         name: "example".to_string(),
+//!         shapes: vec![Shape::Circle { radius: 1.5 }, Shape::Square(2.0)],
+//!     };
+//!     let text = yass_serde::to_string("drawing", &drawing).unwrap();
+//!     assert_eq!(text, "(drawing)\nname \"example\"\nshapes [\n  (Circle){\n    radius 1.5\n  }\n  (Square)2.0\n]");
+//!     assert_eq!(yass_serde::from_str::<Drawing>(&text).unwrap(), drawing);
+//! }
+//! ```
+
+#[macro_use]
+extern crate serde;
+#[cfg_attr(test, macro_use)]
+extern crate yass;
+extern crate yass_aux;
+extern crate yass_parser;
+extern crate yass_serializer;
+
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(test)]
+mod tests;
+
+mod de;
+mod ser;
+
+pub use de::{ValueDeserializer, DocumentDeserializer};
+pub use ser::ValueSerializer;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    Parser(yass_parser::ParserError),
+    /// An error from a `Serialize` or `Deserialize` implementation, or a
+    /// value that does not match the expected type. `pos` is the position
+    /// of the value being deserialized, if known.
+    Message {
+        msg: String,
+        pos: Option<yass::Pos>,
+    },
+    /// The value serialized as a document is not a struct or a map.
+    RootNotStruct,
+    /// A map key cannot be serialized as an atom.
+    UnsupportedKey,
+    /// Infinite and NaN floats cannot be serialized.
+    NonFiniteFloat,
+    /// `Some(None)` cannot be serialized.
+    NestedNone,
+}
+
+impl Error {
+    /// Gets the position of the error in the parsed text, if known.
+    pub fn pos(&self) -> Option<yass::Pos> {
+        match *self {
+            Error::Parser(ref error) => Some(error.pos()),
+            Error::Message { pos, .. } => pos,
+            _ => None,
+        }
+    }
+    
+    /// Sets the position of the error, if it does not have one yet.
+    fn at(self, new_pos: Option<yass::Pos>) -> Self {
+        match self {
+            Error::Message { msg, pos: None } => Error::Message { msg: msg, pos: new_pos },
+            error => error,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        yass::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl yass::DisplayWithSourceMap for Error {
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&yass::SourceMap>) -> std::fmt::Result {
+        match *self {
+            Error::Parser(ref error) => error.fmt_with_source_map(f, source_map),
+            Error::Message { ref msg, pos: Some(pos) } => write!(f, "{} at {}", msg, pos.display(source_map)),
+            Error::Message { ref msg, pos: None } => f.write_str(msg),
+            Error::RootNotStruct => write!(f, "Document root must be a struct or a map"),
+            Error::UnsupportedKey => write!(f, "Map key must be a boolean, number, char or string"),
+            Error::NonFiniteFloat => write!(f, "Cannot serialize infinite or NaN float"),
+            Error::NestedNone => write!(f, "Cannot serialize `Some(None)`"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Parser(_) => "Parser error",
+            Error::Message { ref msg, .. } => msg,
+            Error::RootNotStruct => "Document root must be a struct or a map",
+            Error::UnsupportedKey => "Unsupported map key",
+            Error::NonFiniteFloat => "Non-finite float",
+            Error::NestedNone => "Nested none",
+        }
+    }
+    
+    fn cause(&self) -> Option<&std::error::Error> {
+        match *self {
+            Error::Parser(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<yass_parser::ParserError> for Error {
+    #[inline]
+    fn from(error: yass_parser::ParserError) -> Self {
+        Error::Parser(error)
+    }
+}
+
+impl serde::ser::Error for Error {
+    #[inline]
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Message { msg: msg.to_string(), pos: None }
+    }
+}
+
+impl serde::de::Error for Error {
+    #[inline]
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Message { msg: msg.to_string(), pos: None }
+    }
+}
+
+/// Serializes `value` as a `yass::Value`.
+#[inline]
+pub fn to_value<T: serde::Serialize + ?Sized>(value: &T) -> Result<yass::Value, Error> {
+    value.serialize(ValueSerializer)
+}
+
+/// Serializes `value` as the root fields of a document.
+pub fn to_document<T: serde::Serialize + ?Sized>(header: &str, value: &T) -> Result<yass::Document, Error> {
+    match to_value(value)? {
+        yass::Value::Struct(root_fields) => Ok(yass::Document { header: header.to_string(), root_fields: root_fields }),
+        _ => Err(Error::RootNotStruct),
+    }
+}
+
+/// Serializes `value` as a document with the given style.
+pub fn to_string_with_style<T: serde::Serialize + ?Sized>(
+    header: &str,
+    value: &T,
+    style: &yass_serializer::SerializeStyle,
+) -> Result<String, Error> {
+    let document = to_document(header, value)?;
+    Ok(style.serialize_as_string(&document))
+}
+
+/// Serializes `value` as a document, indented with two spaces.
+pub fn to_string<T: serde::Serialize + ?Sized>(header: &str, value: &T) -> Result<String, Error> {
+    let style = yass_serializer::SerializeStyle::Spaced {
+        line_break_type: yass_serializer::LineBreakType::Lf,
+        indent_type: yass_serializer::IndentType::Space,
+        indent_length: 2,
+    };
+    to_string_with_style(header, value, &style)
+}
+
+/// Deserializes a `yass::Value`. `pos_map` is used to give positions to
+/// errors, and can be empty.
+#[inline]
+pub fn from_value<'a, T: serde::Deserialize<'a>>(value: &'a yass::Value, pos_map: &'a yass::PosMap) -> Result<T, Error> {
+    T::deserialize(ValueDeserializer::new(value, pos_map))
+}
+
+/// Deserializes the root fields of a document, ignoring its header.
+#[inline]
+pub fn from_document<'a, T: serde::Deserialize<'a>>(document: &'a yass::Document, pos_map: &'a yass::PosMap) -> Result<T, Error> {
+    T::deserialize(DocumentDeserializer::new(document, pos_map))
+}
+
+/// Parses a document without limits and deserializes its root fields.
+pub fn from_slice<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+    let (document, pos_map) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), data)?;
+    from_document(&document, &pos_map)
+}
+
+/// Parses a document without limits and deserializes its root fields.
+#[inline]
+pub fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, Error> {
+    from_slice(s.as_bytes())
+}
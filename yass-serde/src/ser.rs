@@ -0,0 +1,523 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::ser;
+use yass::{StructField, Value};
+
+use super::Error;
+
+/// Converts a string to an atom usable as key or tag: kept as is if it
+/// is a valid atom that is not a string, otherwise quoted.
+pub(crate) fn key_atom(key: &str) -> String {
+    if yass::AtomKind::of(key) != yass::AtomKind::String && yass::check_atom(key).is_ok() {
+        key.to_string()
+    } else {
+        yass_aux::serialize_utf8_string_as_string(key)
+    }
+}
+
+#[inline]
+fn empty_struct() -> Value {
+    Value::Struct(Vec::new())
+}
+
+/// Serializes values as `yass::Value`.
+pub struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeStructVariant;
+    
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::bool(v))
+    }
+    
+    #[inline]
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok(Value::int(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok(Value::int(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok(Value::int(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::int(v))
+    }
+    
+    #[inline]
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(Value::uint(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok(Value::uint(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok(Value::uint(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok(Value::uint(v))
+    }
+    
+    #[inline]
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        self.serialize_f64(v.into())
+    }
+    
+    #[inline]
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Value::try_float(v).ok_or(Error::NonFiniteFloat)
+    }
+    
+    #[inline]
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        let mut buf = [0; 4];
+        Ok(Value::string(v.encode_utf8(&mut buf)))
+    }
+    
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::string(v))
+    }
+    
+    #[inline]
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::bytes(v))
+    }
+    
+    #[inline]
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Atom("none".to_string()))
+    }
+    
+    #[inline]
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        match value.serialize(self)? {
+            Value::Atom(ref atom) if atom == "none" => Err(Error::NestedNone),
+            value => Ok(value),
+        }
+    }
+    
+    #[inline]
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(empty_struct())
+    }
+    
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(empty_struct())
+    }
+    
+    #[inline]
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<Value, Error> {
+        Ok(Value::Tagged(key_atom(variant), Box::new(empty_struct())))
+    }
+    
+    #[inline]
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+    
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(Value::Tagged(key_atom(variant), Box::new(value.serialize(self)?)))
+    }
+    
+    #[inline]
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray { items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+    
+    #[inline]
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+    
+    #[inline]
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+    
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariant, Error> {
+        Ok(SerializeTupleVariant {
+            tag: key_atom(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+    
+    #[inline]
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            fields: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+    
+    #[inline]
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeStruct, Error> {
+        Ok(SerializeStruct { fields: Vec::with_capacity(len) })
+    }
+    
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeStructVariant, Error> {
+        Ok(SerializeStructVariant {
+            tag: key_atom(variant),
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+pub struct SerializeArray {
+    items: Vec<Box<Value>>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(Box::new(value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeTupleVariant {
+    tag: String,
+    items: Vec<Box<Value>>,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(Box::new(value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Tagged(self.tag, Box::new(Value::Array(self.items))))
+    }
+}
+
+pub struct SerializeMap {
+    fields: Vec<StructField>,
+    next_key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+    
+    #[inline]
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.next_key.take().expect("serialize_value called before serialize_key");
+        self.fields.push(StructField { key: key, value: Box::new(value.serialize(ValueSerializer)?) });
+        Ok(())
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Struct(self.fields))
+    }
+}
+
+pub struct SerializeStruct {
+    fields: Vec<StructField>,
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.fields.push(StructField { key: key_atom(key), value: Box::new(value.serialize(ValueSerializer)?) });
+        Ok(())
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Struct(self.fields))
+    }
+}
+
+pub struct SerializeStructVariant {
+    tag: String,
+    fields: Vec<StructField>,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+    type Error = Error;
+    
+    #[inline]
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.fields.push(StructField { key: key_atom(key), value: Box::new(value.serialize(ValueSerializer)?) });
+        Ok(())
+    }
+    
+    #[inline]
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Tagged(self.tag, Box::new(Value::Struct(self.fields))))
+    }
+}
+
+/// Serializes map keys as atoms.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+    
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<String, Error> {
+        Ok(yass_aux::serialize_bool(v).to_string())
+    }
+    
+    #[inline]
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Ok(yass_aux::serialize_i64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Ok(yass_aux::serialize_i64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Ok(yass_aux::serialize_i64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Ok(yass_aux::serialize_i64_as_string(v))
+    }
+    
+    #[inline]
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Ok(yass_aux::serialize_u64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Ok(yass_aux::serialize_u64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Ok(yass_aux::serialize_u64_as_string(v.into()))
+    }
+    
+    #[inline]
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Ok(yass_aux::serialize_u64_as_string(v))
+    }
+    
+    #[inline]
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        let mut buf = [0; 4];
+        Ok(key_atom(v.encode_utf8(&mut buf)))
+    }
+    
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(key_atom(v))
+    }
+    
+    #[inline]
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, _value: &T) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<String, Error> {
+        Ok(key_atom(variant))
+    }
+    
+    #[inline]
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+    
+    #[inline]
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::UnsupportedKey)
+    }
+    
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::UnsupportedKey)
+    }
+}
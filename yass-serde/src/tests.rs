@@ -0,0 +1,153 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+
+use yass;
+mod yass_serde {
+    pub use ::*;
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Unit;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Meters(f64);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Variant {
+    Unit,
+    Newtype(u8),
+    Tuple(i32, String),
+    Struct { a: bool },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Everything {
+    flag: bool,
+    small: i8,
+    big: u64,
+    negative: i64,
+    real: f32,
+    chr: char,
+    text: String,
+    bytes: Vec<u8>,
+    missing: Option<String>,
+    present: Option<Meters>,
+    pair: (u16, String),
+    unit: Unit,
+    variants: Vec<Variant>,
+    by_number: BTreeMap<u32, String>,
+    by_name: BTreeMap<String, i32>,
+}
+
+fn everything() -> Everything {
+    let mut by_number = BTreeMap::new();
+    by_number.insert(1, "one".to_string());
+    by_number.insert(20, "twenty".to_string());
+    let mut by_name = BTreeMap::new();
+    by_name.insert("plain".to_string(), 1);
+    by_name.insert("with space".to_string(), 2);
+    by_name.insert("\"quoted\"".to_string(), 3);
+    
+    Everything {
+        flag: true,
+        small: -5,
+        big: u64::max_value(),
+        negative: i64::min_value(),
+        real: 0.5,
+        chr: 'ñ',
+        text: "line\n\"quoted\"".to_string(),
+        bytes: vec![0, 1, 255],
+        missing: None,
+        present: Some(Meters(2.5)),
+        pair: (7, "seven".to_string()),
+        unit: Unit,
+        variants: vec![
+            Variant::Unit,
+            Variant::Newtype(3),
+            Variant::Tuple(-1, "x".to_string()),
+            Variant::Struct { a: false },
+        ],
+        by_number: by_number,
+        by_name: by_name,
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let value = everything();
+    let text = yass_serde::to_string("everything", &value).unwrap();
+    assert_eq!(yass_serde::from_str::<Everything>(&text).unwrap(), value);
+}
+
+#[test]
+fn test_to_document() {
+    let document = yass_serde::to_document("test", &Variant::Struct { a: true });
+    assert_eq!(document, Err(yass_serde::Error::RootNotStruct));
+    
+    let mut by_name = BTreeMap::new();
+    by_name.insert("a b", vec![Variant::Unit, Variant::Newtype(1)]);
+    let document = yass_serde::to_document("test", &by_name).unwrap();
+    assert_eq!(document, yass_document!(("test") "\"a b\"": [(as "Unit": {}), (as "Newtype": "1")]));
+    
+    assert_eq!(yass_serde::to_value(&std::f64::NAN), Err(yass_serde::Error::NonFiniteFloat));
+    assert_eq!(yass_serde::to_value(&Some(None::<i32>)), Err(yass_serde::Error::NestedNone));
+    assert_eq!(yass_serde::to_value(&Some(Some(1))), Ok(yass::Value::Atom("1".to_string())));
+    let mut by_float = BTreeMap::new();
+    by_float.insert(vec![1], 1);
+    assert_eq!(yass_serde::to_value(&by_float), Err(yass_serde::Error::UnsupportedKey));
+}
+
+#[test]
+fn test_from_str() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        name: String,
+        mode: Variant,
+        limits: Vec<u8>,
+    }
+    
+    // Symbols are accepted as strings and unit variants.
+    let config = yass_serde::from_str::<Config>("(config)\nname example\nmode Unit\nlimits [1 2]").unwrap();
+    assert_eq!(config, Config { name: "example".to_string(), mode: Variant::Unit, limits: vec![1, 2] });
+}
+
+#[test]
+fn test_from_str_fail() {
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        #[allow(dead_code)]
+        limits: Vec<u8>,
+    }
+    
+    let error = yass_serde::from_str::<Config>("(config)\nlimits [\n  1\n  300\n]").unwrap_err();
+    assert_eq!(error.pos(), Some(yass::Pos::new(3, 2)));
+    assert_eq!(error.to_string(), "invalid value: integer `300`, expected u8 at 4:3");
+    
+    let error = yass_serde::from_str::<Config>("(config)\nlimits {}").unwrap_err();
+    assert_eq!(error.pos(), Some(yass::Pos::new(1, 7)));
+    
+    let error = yass_serde::from_str::<Config>("(config)\nother 1").unwrap_err();
+    assert_eq!(error.to_string(), "missing field `limits`");
+    
+    #[derive(Debug, Deserialize)]
+    struct WithVariant {
+        #[allow(dead_code)]
+        mode: Variant,
+    }
+    
+    let error = yass_serde::from_str::<WithVariant>("(config)\nmode (Bad){}").unwrap_err();
+    assert_eq!(error.pos(), Some(yass::Pos::new(1, 6)));
+    
+    let error = yass_serde::from_str::<Config>("(config)\nlimits [").unwrap_err();
+    match error {
+        yass_serde::Error::Parser(_) => {}
+        _ => panic!("unexpected error {:?}", error),
+    }
+    assert_eq!(error.pos(), Some(yass::Pos::new(1, 8)));
+}
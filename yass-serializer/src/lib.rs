@@ -112,6 +112,64 @@ impl SerializeStyle {
         self.serialize(document, &mut output);
         output
     }
+    
+    /// Like `serialize`, but checks the document with
+    /// `yass::Document::validate` first, so it never writes output that
+    /// cannot be parsed back. Nothing is written if there is an error.
+    pub fn try_serialize(&self, document: &yass::Document, output: &mut String) -> Result<(), yass::ValidationError> {
+        document.validate()?;
+        self.serialize(document, output);
+        Ok(())
+    }
+    
+    #[inline]
+    pub fn try_serialize_as_string(&self, document: &yass::Document) -> Result<String, yass::ValidationError> {
+        let mut output = String::new();
+        self.try_serialize(document, &mut output)?;
+        Ok(output)
+    }
+    
+    /// Serializes several documents in a stream that can be read with
+    /// `yass_parser::parse_documents`. Documents are separated by a
+    /// space in the compact style and by an empty line in the spaced
+    /// style.
+    pub fn serialize_documents<'a, I>(&self, documents: I, output: &mut String)
+        where I: IntoIterator<Item = &'a yass::Document>
+    {
+        for (i, document) in documents.into_iter().enumerate() {
+            if i != 0 {
+                match *self {
+                    SerializeStyle::Compact => output.push(' '),
+                    SerializeStyle::Spaced { line_break_type, .. } => {
+                        output.push_str(line_break_type.to_str());
+                        output.push_str(line_break_type.to_str());
+                    }
+                }
+            }
+            self.serialize(document, output);
+        }
+    }
+    
+    #[inline]
+    pub fn serialize_documents_as_string<'a, I>(&self, documents: I) -> String
+        where I: IntoIterator<Item = &'a yass::Document>
+    {
+        let mut output = String::new();
+        self.serialize_documents(documents, &mut output);
+        output
+    }
+    
+    /// Like `serialize_documents`, but validates all the documents
+    /// first. Nothing is written if there is an error.
+    pub fn try_serialize_documents(&self, documents: &[yass::Document], output: &mut String)
+        -> Result<(), yass::ValidationError>
+    {
+        for document in documents.iter() {
+            document.validate()?;
+        }
+        self.serialize_documents(documents, output);
+        Ok(())
+    }
 }
 
 // CompactSerializer
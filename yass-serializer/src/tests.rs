@@ -108,3 +108,65 @@ fn test_tagged_1() {
     assert_eq!(serialized_compact, expected_result_compact);
     assert_eq!(serialized_spaced, expected_result_spaced);
 }
+
+#[test]
+fn test_escaped_atoms_round_trip() {
+    let document = yass::Document {
+        header: "test".to_string(),
+        root_fields: vec![
+            yass::StructField { key: "a".to_string(), value: Box::new(yass::Value::string("line 1\nline \"2\"")) },
+            yass::StructField { key: "b".to_string(), value: Box::new(yass::Value::bytes(b"\x00\xFF")) },
+            yass::StructField { key: "c".to_string(), value: Box::new(yass::Value::try_float(-1.5e-10).unwrap()) },
+            yass::StructField { key: "d".to_string(), value: Box::new(yass::Value::from(true)) },
+        ],
+    };
+    for style in [compact_style(), spaced_style()].iter() {
+        let serialized = style.serialize_as_string(&document);
+        let (parsed, _) = yass_parser::parse(yass_parser::ParserLimits::unlimited(), serialized.as_bytes()).unwrap();
+        assert_eq!(parsed, document);
+    }
+}
+
+#[test]
+fn test_try_serialize() {
+    let document = yass_document!(("test") "a": "1", "b": {"c": "\"x\""});
+    assert_eq!(compact_style().try_serialize_as_string(&document), Ok("(test) a 1 b {c \"x\"}".to_string()));
+    
+    let document = yass_document!(("test") "a": "1", "b": {"c": "a b"});
+    let error = compact_style().try_serialize_as_string(&document).unwrap_err();
+    assert_eq!(error.path, yass::Path::parse("b/c").unwrap());
+    assert_eq!(error.part, yass::AtomPart::Value);
+    assert_eq!(error.error, yass::AtomSyntaxError::IllegalChr { offset: 1, chr: b' ' });
+    
+    let mut output = String::new();
+    assert!(spaced_style().try_serialize(&document, &mut output).is_err());
+    assert_eq!(output, "");
+}
+
+#[test]
+fn test_serialize_documents() {
+    let documents = [
+        yass_document!(("record") "id": "1", "tags": ["a"]),
+        yass_document!(("empty")),
+        yass_document!(("record") "id": "2"),
+    ];
+    assert_eq!(compact_style().serialize_documents_as_string(&documents), "(record) id 1 tags [a] (empty) (record) id 2");
+    assert_eq!(
+        spaced_style().serialize_documents_as_string(&documents),
+        "(record)\nid 1\ntags [\n\ta\n]\n\n(empty)\n\n(record)\nid 2"
+    );
+    assert_eq!(compact_style().serialize_documents_as_string(&[]), "");
+    
+    for style in [compact_style(), spaced_style()].iter() {
+        let serialized = style.serialize_documents_as_string(&documents);
+        let parsed = yass_parser::parse_documents(yass_parser::ParserLimits::unlimited(), serialized.as_bytes())
+            .map(|result| result.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, documents);
+    }
+    
+    let invalid = [yass_document!(("record") "id": "1"), yass_document!(("record") "id": "")];
+    let mut output = String::new();
+    assert!(compact_style().try_serialize_documents(&invalid, &mut output).is_err());
+    assert_eq!(output, "");
+}
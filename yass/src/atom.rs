@@ -0,0 +1,281 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use yass_aux;
+
+use super::Value;
+
+/// Kind of an atom, according to its spelling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AtomKind {
+    /// `true` or `false`.
+    Bool,
+    /// An optional sign followed by decimal digits, such as `-12`.
+    Int,
+    /// A decimal number with a fractional part or an exponent, such as
+    /// `1.5` or `1e-3`.
+    Float,
+    /// A quoted string, such as `"text"`.
+    String,
+    /// Any other atom, such as `center` or `2018-04-01`.
+    Symbol,
+}
+
+impl AtomKind {
+    /// Classifies `atom`, which must be a valid atom.
+    pub fn of(atom: &str) -> Self {
+        if yass_aux::parse_bool(atom).is_some() {
+            AtomKind::Bool
+        } else if is_int(atom) {
+            AtomKind::Int
+        } else if yass_aux::parse_f64(atom).is_some() {
+            AtomKind::Float
+        } else if atom.starts_with('"') {
+            AtomKind::String
+        } else {
+            AtomKind::Symbol
+        }
+    }
+}
+
+impl std::fmt::Display for AtomKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AtomKind::Bool => f.write_str("boolean"),
+            AtomKind::Int => f.write_str("integer"),
+            AtomKind::Float => f.write_str("float"),
+            AtomKind::String => f.write_str("string"),
+            AtomKind::Symbol => f.write_str("symbol"),
+        }
+    }
+}
+
+fn is_int(atom: &str) -> bool {
+    let digits = if atom.starts_with('-') || atom.starts_with('+') { &atom[1 ..] } else { atom };
+    !digits.is_empty() && digits.bytes().all(|chr| chr.is_ascii_digit())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AtomError {
+    NotAnAtom,
+    UnexpectedKind {
+        expected: AtomKind,
+        found: AtomKind,
+    },
+    IntOutOfRange,
+    InvalidString,
+}
+
+impl std::fmt::Display for AtomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AtomError::NotAnAtom => {
+                write!(f, "Value is not an atom")
+            }
+            AtomError::UnexpectedKind { expected, found } => {
+                write!(f, "Expected {} atom, found {} atom", expected, found)
+            }
+            AtomError::IntOutOfRange => {
+                write!(f, "Integer out of range")
+            }
+            AtomError::InvalidString => {
+                write!(f, "Invalid string")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtomError {
+    fn description(&self) -> &str {
+        match *self {
+            AtomError::NotAnAtom => "Value is not an atom",
+            AtomError::UnexpectedKind { .. } => "Unexpected atom kind",
+            AtomError::IntOutOfRange => "Integer out of range",
+            AtomError::InvalidString => "Invalid string",
+        }
+    }
+}
+
+/// Typed accessors, built on the `yass_aux::parse_*` functions used by
+/// the code generated by `yass_codegen`.
+impl Value {
+    /// Gets the kind of the atom, returning `None` if the value is not
+    /// an atom.
+    #[inline]
+    pub fn atom_kind(&self) -> Option<AtomKind> {
+        match *self {
+            Value::Atom(ref atom) => Some(AtomKind::of(atom)),
+            _ => None,
+        }
+    }
+    
+    pub fn as_bool(&self) -> Result<bool, AtomError> {
+        let atom = self.get_atom()?;
+        yass_aux::parse_bool(atom).ok_or_else(|| unexpected_kind(AtomKind::Bool, atom))
+    }
+    
+    pub fn as_i64(&self) -> Result<i64, AtomError> {
+        let atom = self.get_atom()?;
+        yass_aux::parse_i64(atom).ok_or_else(|| int_error(atom))
+    }
+    
+    pub fn as_u64(&self) -> Result<u64, AtomError> {
+        let atom = self.get_atom()?;
+        yass_aux::parse_u64(atom).ok_or_else(|| int_error(atom))
+    }
+    
+    /// Gets the value of a float or integer atom.
+    pub fn as_f64(&self) -> Result<f64, AtomError> {
+        let atom = self.get_atom()?;
+        yass_aux::parse_f64(atom).ok_or_else(|| unexpected_kind(AtomKind::Float, atom))
+    }
+    
+    /// Gets the value of a string atom, without quotes and with escape
+    /// sequences replaced.
+    pub fn as_str_unescaped(&self) -> Result<String, AtomError> {
+        let atom = self.get_atom()?;
+        match AtomKind::of(atom) {
+            AtomKind::String => yass_aux::parse_utf8_string(atom).ok_or(AtomError::InvalidString),
+            found => Err(AtomError::UnexpectedKind { expected: AtomKind::String, found: found }),
+        }
+    }
+    
+    #[inline]
+    fn get_atom(&self) -> Result<&str, AtomError> {
+        match *self {
+            Value::Atom(ref atom) => Ok(atom),
+            _ => Err(AtomError::NotAnAtom),
+        }
+    }
+}
+
+/// Constructors, built on the `yass_aux::serialize_*` functions, so
+/// the resulting atoms can always be parsed back.
+impl Value {
+    /// Creates a string atom, adding quotes and escaping `string` as
+    /// needed.
+    #[inline]
+    pub fn string(string: &str) -> Self {
+        Value::Atom(yass_aux::serialize_utf8_string_as_string(string))
+    }
+    
+    /// Creates a string atom from arbitrary bytes, escaping non-ASCII
+    /// ones.
+    #[inline]
+    pub fn bytes(bytes: &[u8]) -> Self {
+        Value::Atom(yass_aux::serialize_byte_string_as_string(bytes))
+    }
+    
+    #[inline]
+    pub fn int(value: i64) -> Self {
+        Value::Atom(yass_aux::serialize_i64_as_string(value))
+    }
+    
+    #[inline]
+    pub fn uint(value: u64) -> Self {
+        Value::Atom(yass_aux::serialize_u64_as_string(value))
+    }
+    
+    /// Creates a float atom.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is infinite or NaN, since there are no atoms
+    /// for them. See `try_float` for a non-panicking version.
+    #[inline]
+    pub fn float(value: f64) -> Self {
+        match Value::try_float(value) {
+            Some(atom) => atom,
+            None => panic!("cannot create an atom from non-finite float {}", value),
+        }
+    }
+    
+    /// Creates a float atom, or returns `None` if `value` is infinite
+    /// or NaN, since there are no atoms for them.
+    #[inline]
+    pub fn try_float(value: f64) -> Option<Self> {
+        if value.is_finite() {
+            Some(Value::Atom(yass_aux::serialize_f64_as_string(value)))
+        } else {
+            None
+        }
+    }
+    
+    #[inline]
+    pub fn bool(value: bool) -> Self {
+        Value::Atom(yass_aux::serialize_bool(value).to_string())
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    #[inline]
+    fn from(string: &'a str) -> Self {
+        Value::string(string)
+    }
+}
+
+impl From<String> for Value {
+    #[inline]
+    fn from(string: String) -> Self {
+        Value::string(&string)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    #[inline]
+    fn from(bytes: &'a [u8]) -> Self {
+        Value::bytes(bytes)
+    }
+}
+
+impl From<i32> for Value {
+    #[inline]
+    fn from(value: i32) -> Self {
+        Value::int(value as i64)
+    }
+}
+
+impl From<i64> for Value {
+    #[inline]
+    fn from(value: i64) -> Self {
+        Value::int(value)
+    }
+}
+
+impl From<u32> for Value {
+    #[inline]
+    fn from(value: u32) -> Self {
+        Value::uint(value as u64)
+    }
+}
+
+impl From<u64> for Value {
+    #[inline]
+    fn from(value: u64) -> Self {
+        Value::uint(value)
+    }
+}
+
+impl From<bool> for Value {
+    #[inline]
+    fn from(value: bool) -> Self {
+        Value::bool(value)
+    }
+}
+
+#[inline]
+fn unexpected_kind(expected: AtomKind, atom: &str) -> AtomError {
+    AtomError::UnexpectedKind { expected: expected, found: AtomKind::of(atom) }
+}
+
+fn int_error(atom: &str) -> AtomError {
+    if is_int(atom) {
+        AtomError::IntOutOfRange
+    } else {
+        unexpected_kind(AtomKind::Int, atom)
+    }
+}
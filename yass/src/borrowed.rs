@@ -0,0 +1,102 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::{Document, StructField, Value};
+
+/// A YASS value that borrows its atoms, keys and tags from the text it
+/// was parsed from.
+///
+/// Unlike `Value`, array items and struct field values are not boxed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BorrowedValue<'a> {
+    Atom(&'a str),
+    Array(Vec<BorrowedValue<'a>>),
+    Tagged(&'a str, Box<BorrowedValue<'a>>),
+    Struct(Vec<BorrowedStructField<'a>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BorrowedStructField<'a> {
+    pub key: &'a str,
+    pub value: BorrowedValue<'a>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BorrowedDocument<'a> {
+    pub header: &'a str,
+    pub root_fields: Vec<BorrowedStructField<'a>>,
+}
+
+impl<'a> BorrowedValue<'a> {
+    /// Copies the value into a `Value`.
+    pub fn into_owned(self) -> Value {
+        match self {
+            BorrowedValue::Atom(atom) => Value::Atom(atom.to_string()),
+            BorrowedValue::Array(items) => {
+                Value::Array(items.into_iter().map(|item| Box::new(item.into_owned())).collect())
+            }
+            BorrowedValue::Tagged(tag, value) => Value::Tagged(tag.to_string(), Box::new(value.into_owned())),
+            BorrowedValue::Struct(fields) => {
+                Value::Struct(fields.into_iter().map(BorrowedStructField::into_owned).collect())
+            }
+        }
+    }
+}
+
+impl<'a> BorrowedStructField<'a> {
+    /// Copies the field into a `StructField`.
+    #[inline]
+    pub fn into_owned(self) -> StructField {
+        StructField {
+            key: self.key.to_string(),
+            value: Box::new(self.value.into_owned()),
+        }
+    }
+}
+
+impl<'a> BorrowedDocument<'a> {
+    /// Copies the document into a `Document`.
+    pub fn into_owned(self) -> Document {
+        Document {
+            header: self.header.to_string(),
+            root_fields: self.root_fields.into_iter().map(BorrowedStructField::into_owned).collect(),
+        }
+    }
+}
+
+impl Value {
+    /// Creates a `BorrowedValue` that borrows from this value.
+    pub fn as_borrowed<'a>(&'a self) -> BorrowedValue<'a> {
+        match *self {
+            Value::Atom(ref atom) => BorrowedValue::Atom(atom),
+            Value::Array(ref items) => BorrowedValue::Array(items.iter().map(|item| item.as_borrowed()).collect()),
+            Value::Tagged(ref tag, ref value) => BorrowedValue::Tagged(tag, Box::new(value.as_borrowed())),
+            Value::Struct(ref fields) => BorrowedValue::Struct(fields.iter().map(StructField::as_borrowed).collect()),
+        }
+    }
+}
+
+impl StructField {
+    /// Creates a `BorrowedStructField` that borrows from this field.
+    #[inline]
+    pub fn as_borrowed<'a>(&'a self) -> BorrowedStructField<'a> {
+        BorrowedStructField {
+            key: &self.key,
+            value: self.value.as_borrowed(),
+        }
+    }
+}
+
+impl Document {
+    /// Creates a `BorrowedDocument` that borrows from this document.
+    pub fn as_borrowed<'a>(&'a self) -> BorrowedDocument<'a> {
+        BorrowedDocument {
+            header: &self.header,
+            root_fields: self.root_fields.iter().map(StructField::as_borrowed).collect(),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::{AtomKind, Document, StructField, Value};
+use sha256::sha256;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CanonicalOptions {
+    /// Sort struct fields by key. Fields with the same key keep their
+    /// relative order.
+    pub sort_keys: bool,
+}
+
+/// A SHA-256 digest of the canonical form of a document or value.
+///
+/// It is formatted in hexadecimal by `Display`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest(pub [u8; 32]);
+
+impl Digest {
+    /// Computes the SHA-256 digest of `data`.
+    #[inline]
+    pub fn of_bytes(data: &[u8]) -> Self {
+        Digest(sha256(data))
+    }
+    
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the canonical spelling of an atom:
+///
+/// * Integers without `+` sign and leading zeros, and `0` instead of
+///   `-0`.
+/// * Floats as written by `yass_aux::serialize_f64`.
+/// * Strings as written by `yass_aux::serialize_utf8_string`, or by
+///   `yass_aux::serialize_byte_string` if they are not valid UTF-8.
+///
+/// Other atoms are returned unchanged.
+pub fn canonical_atom(atom: &str) -> String {
+    match AtomKind::of(atom) {
+        AtomKind::Int => {
+            let (negative, digits) = match atom.as_bytes()[0] {
+                b'-' => (true, &atom[1 ..]),
+                b'+' => (false, &atom[1 ..]),
+                _ => (false, atom),
+            };
+            let digits = digits.trim_start_matches('0');
+            if digits.is_empty() {
+                "0".to_string()
+            } else if negative {
+                format!("-{}", digits)
+            } else {
+                digits.to_string()
+            }
+        }
+        AtomKind::Float => match yass_aux::parse_f64(atom) {
+            Some(value) if value.is_finite() => yass_aux::serialize_f64_as_string(value),
+            _ => atom.to_string(),
+        },
+        AtomKind::String => {
+            if let Some(string) = yass_aux::parse_utf8_string(atom) {
+                yass_aux::serialize_utf8_string_as_string(&string)
+            } else if let Some(bytes) = yass_aux::parse_byte_string(atom) {
+                yass_aux::serialize_byte_string_as_string(&bytes)
+            } else {
+                atom.to_string()
+            }
+        }
+        AtomKind::Bool | AtomKind::Symbol => atom.to_string(),
+    }
+}
+
+fn canonicalize_fields(fields: &mut [StructField], options: &CanonicalOptions) {
+    for field in fields.iter_mut() {
+        field.key = canonical_atom(&field.key);
+        field.value.canonicalize(options);
+    }
+    if options.sort_keys {
+        fields.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+}
+
+/// Writes a value in a single line, as the compact style of
+/// `yass_serializer`.
+pub(crate) fn write_compact(value: &Value, output: &mut String) {
+    match *value {
+        Value::Atom(ref atom) => output.push_str(atom),
+        Value::Array(ref items) => {
+            output.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    output.push(' ');
+                }
+                write_compact(item, output);
+            }
+            output.push(']');
+        }
+        Value::Tagged(ref tag, ref sub_value) => {
+            output.push('(');
+            output.push_str(tag);
+            output.push(')');
+            write_compact(sub_value, output);
+        }
+        Value::Struct(ref fields) => {
+            output.push('{');
+            for (i, field) in fields.iter().enumerate() {
+                if i != 0 {
+                    output.push(' ');
+                }
+                output.push_str(&field.key);
+                output.push(' ');
+                write_compact(&field.value, output);
+            }
+            output.push('}');
+        }
+    }
+}
+
+/// Canonical form.
+///
+/// Two documents that only differ in the spelling of their atoms (such
+/// as `1.0` and `1.00`, or `"\x41"` and `"A"`), and optionally in the
+/// order of their struct fields, have the same canonical form. Since
+/// values do not store whitespace or comments, those never matter.
+impl Value {
+    /// Converts the value to its canonical form.
+    pub fn canonicalize(&mut self, options: &CanonicalOptions) {
+        match *self {
+            Value::Atom(ref mut atom) => *atom = canonical_atom(atom),
+            Value::Array(ref mut items) => {
+                for item in items.iter_mut() {
+                    item.canonicalize(options);
+                }
+            }
+            Value::Tagged(ref mut tag, ref mut sub_value) => {
+                *tag = canonical_atom(tag);
+                sub_value.canonicalize(options);
+            }
+            Value::Struct(ref mut fields) => canonicalize_fields(fields, options),
+        }
+    }
+    
+    #[inline]
+    pub fn to_canonical(&self, options: &CanonicalOptions) -> Value {
+        let mut value = self.clone();
+        value.canonicalize(options);
+        value
+    }
+    
+    /// Serializes the canonical form in a single line.
+    pub fn to_canonical_string(&self, options: &CanonicalOptions) -> String {
+        let mut output = String::new();
+        write_compact(&self.to_canonical(options), &mut output);
+        output
+    }
+    
+    /// Computes the SHA-256 digest of `to_canonical_string`.
+    #[inline]
+    pub fn digest(&self, options: &CanonicalOptions) -> Digest {
+        Digest::of_bytes(self.to_canonical_string(options).as_bytes())
+    }
+}
+
+impl Document {
+    /// Converts the document to its canonical form.
+    pub fn canonicalize(&mut self, options: &CanonicalOptions) {
+        self.header = canonical_atom(&self.header);
+        canonicalize_fields(&mut self.root_fields, options);
+    }
+    
+    #[inline]
+    pub fn to_canonical(&self, options: &CanonicalOptions) -> Document {
+        let mut document = self.clone();
+        document.canonicalize(options);
+        document
+    }
+    
+    /// Serializes the canonical form in a single line, as the compact
+    /// style of `yass_serializer`.
+    pub fn to_canonical_string(&self, options: &CanonicalOptions) -> String {
+        let document = self.to_canonical(options);
+        let mut output = String::new();
+        output.push('(');
+        output.push_str(&document.header);
+        output.push(')');
+        for field in document.root_fields.iter() {
+            output.push(' ');
+            output.push_str(&field.key);
+            output.push(' ');
+            write_compact(&field.value, &mut output);
+        }
+        output
+    }
+    
+    /// Computes the SHA-256 digest of `to_canonical_string`.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate yass;
+    ///
+    /// fn main() {
+    ///     let options = yass::CanonicalOptions { sort_keys: true };
+    ///     let a = yass_document!(("config") "x": "1.00", "y": r#""\x41""#);
+    ///     let b = yass_document!(("config") "y": r#""A""#, "x": "1.0");
+    ///     assert_eq!(a.digest(&options), b.digest(&options));
+    /// }
+    /// ```
+    #[inline]
+    pub fn digest(&self, options: &CanonicalOptions) -> Digest {
+        Digest::of_bytes(self.to_canonical_string(options).as_bytes())
+    }
+}
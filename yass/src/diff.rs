@@ -0,0 +1,359 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+
+use super::{Document, Path, StructField, Value};
+use canonical::write_compact;
+
+/// A difference between two documents or values.
+///
+/// Paths of removed nodes refer to the old document, all others refer
+/// to the new one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    HeaderChanged {
+        old: String,
+        new: String,
+    },
+    Added {
+        path: Path,
+        value: Value,
+    },
+    Removed {
+        path: Path,
+        value: Value,
+    },
+    /// An atom changed, or a value was replaced by one of another type.
+    Changed {
+        path: Path,
+        old: Value,
+        new: Value,
+    },
+    TagChanged {
+        path: Path,
+        old: String,
+        new: String,
+    },
+}
+
+/// Result of `diff`.
+///
+/// It can be rendered as text with `Display`, one change per line, or
+/// as a YASS document with `to_document`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+/// Compares two documents.
+///
+/// Struct fields are matched by key, so reordering fields is not a
+/// change. Fields with a repeated key are matched first with an equal
+/// field, and then in order. Array items are matched in order, so an
+/// inserted item is reported as added instead of changing all the
+/// following ones.
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+///
+/// fn main() {
+///     let old = yass_document!(("example") "a": "1", "b": ["x", "y"]);
+///     let new = yass_document!(("example") "b": ["x", "z", "y"], "a": "2");
+///     let diff = yass::diff(&old, &new);
+///     assert_eq!(diff.to_string(), "+ b/[1]: z\n~ a: 1 -> 2\n");
+/// }
+/// ```
+pub fn diff(old: &Document, new: &Document) -> Diff {
+    let mut changes = Vec::new();
+    if old.header != new.header {
+        changes.push(Change::HeaderChanged { old: old.header.clone(), new: new.header.clone() });
+    }
+    diff_fields(&old.root_fields, &new.root_fields, &Path::new(), &mut changes);
+    Diff { changes: changes }
+}
+
+/// Like `diff`, but for values. Paths are relative to them.
+pub fn diff_values(old: &Value, new: &Value) -> Diff {
+    let mut changes = Vec::new();
+    diff_value(old, new, &Path::new(), &mut changes);
+    Diff { changes: changes }
+}
+
+impl Diff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    
+    /// Converts the diff to a document with header `diff` and one field
+    /// per change:
+    ///
+    /// ```text
+    /// (diff)
+    /// header-changed {old example new example-2}
+    /// added {path "a/b" value 1}
+    /// removed {path "c" value [1 2]}
+    /// changed {path "d" old 1 new 2}
+    /// tag-changed {path "e" old t1 new t2}
+    /// ```
+    pub fn to_document(&self) -> Document {
+        fn field(key: &str, value: Value) -> StructField {
+            StructField { key: key.to_string(), value: Box::new(value) }
+        }
+        
+        let root_fields = self.changes.iter().map(|change| {
+            match *change {
+                Change::HeaderChanged { ref old, ref new } => {
+                    field("header-changed", Value::Struct(vec![
+                        field("old", Value::Atom(old.clone())),
+                        field("new", Value::Atom(new.clone())),
+                    ]))
+                }
+                Change::Added { ref path, ref value } => {
+                    field("added", Value::Struct(vec![
+                        field("path", Value::string(&path.to_string())),
+                        field("value", value.clone()),
+                    ]))
+                }
+                Change::Removed { ref path, ref value } => {
+                    field("removed", Value::Struct(vec![
+                        field("path", Value::string(&path.to_string())),
+                        field("value", value.clone()),
+                    ]))
+                }
+                Change::Changed { ref path, ref old, ref new } => {
+                    field("changed", Value::Struct(vec![
+                        field("path", Value::string(&path.to_string())),
+                        field("old", old.clone()),
+                        field("new", new.clone()),
+                    ]))
+                }
+                Change::TagChanged { ref path, ref old, ref new } => {
+                    field("tag-changed", Value::Struct(vec![
+                        field("path", Value::string(&path.to_string())),
+                        field("old", Value::Atom(old.clone())),
+                        field("new", Value::Atom(new.clone())),
+                    ]))
+                }
+            }
+        }).collect();
+        
+        Document { header: "diff".to_string(), root_fields: root_fields }
+    }
+}
+
+impl std::fmt::Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for change in self.changes.iter() {
+            match *change {
+                Change::HeaderChanged { ref old, ref new } => {
+                    writeln!(f, "~ header: ({}) -> ({})", old, new)?;
+                }
+                Change::Added { ref path, ref value } => {
+                    writeln!(f, "+ {}: {}", path, compact(value))?;
+                }
+                Change::Removed { ref path, ref value } => {
+                    writeln!(f, "- {}: {}", path, compact(value))?;
+                }
+                Change::Changed { ref path, ref old, ref new } => {
+                    writeln!(f, "~ {}: {} -> {}", path, compact(old), compact(new))?;
+                }
+                Change::TagChanged { ref path, ref old, ref new } => {
+                    writeln!(f, "~ {}: ({}) -> ({})", path, old, new)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn diff_value(old: &Value, new: &Value, path: &Path, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (&Value::Atom(ref old_atom), &Value::Atom(ref new_atom)) => {
+            if old_atom != new_atom {
+                changes.push(Change::Changed { path: path.clone(), old: old.clone(), new: new.clone() });
+            }
+        }
+        (&Value::Array(ref old_items), &Value::Array(ref new_items)) => {
+            diff_items(old_items, new_items, path, changes);
+        }
+        (&Value::Tagged(ref old_tag, ref old_value), &Value::Tagged(ref new_tag, ref new_value)) => {
+            if old_tag != new_tag {
+                changes.push(Change::TagChanged { path: path.clone(), old: old_tag.clone(), new: new_tag.clone() });
+            }
+            diff_value(old_value, new_value, path, changes);
+        }
+        (&Value::Struct(ref old_fields), &Value::Struct(ref new_fields)) => {
+            diff_fields(old_fields, new_fields, path, changes);
+        }
+        _ => {
+            changes.push(Change::Changed { path: path.clone(), old: old.clone(), new: new.clone() });
+        }
+    }
+}
+
+fn diff_fields(old: &[StructField], new: &[StructField], path: &Path, changes: &mut Vec<Change>) {
+    // Indices of the fields with each key, with the keys in order of
+    // appearance, first in the new struct.
+    let mut keys: Vec<&str> = Vec::new();
+    let mut groups: HashMap<&str, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for (j, field) in new.iter().enumerate() {
+        groups.entry(&field.key).or_insert_with(|| {
+            keys.push(&field.key);
+            (Vec::new(), Vec::new())
+        }).1.push(j);
+    }
+    for (i, field) in old.iter().enumerate() {
+        groups.entry(&field.key).or_insert_with(|| {
+            keys.push(&field.key);
+            (Vec::new(), Vec::new())
+        }).0.push(i);
+    }
+    
+    for key in keys {
+        let (ref old_indices, ref new_indices) = groups[key];
+        // Index among the fields with the same key, if it is repeated.
+        let old_occurrence = |o: usize| if old_indices.len() > 1 { Some(o) } else { None };
+        let new_occurrence = |n: usize| if new_indices.len() > 1 { Some(n) } else { None };
+        
+        // Match equal fields first, then the remaining ones in order.
+        let mut old_matched = vec![false; old_indices.len()];
+        let mut new_matched = vec![false; new_indices.len()];
+        for (n, &j) in new_indices.iter().enumerate() {
+            let equal = (0 .. old_indices.len()).find(|&o| !old_matched[o] && old[old_indices[o]].value == new[j].value);
+            if let Some(o) = equal {
+                old_matched[o] = true;
+                new_matched[n] = true;
+            }
+        }
+        let old_unmatched = (0 .. old_indices.len()).filter(|&o| !old_matched[o]).collect::<Vec<_>>();
+        let new_unmatched = (0 .. new_indices.len()).filter(|&n| !new_matched[n]).collect::<Vec<_>>();
+        
+        for (&o, &n) in old_unmatched.iter().zip(new_unmatched.iter()) {
+            let mut field_path = path.clone();
+            field_path.push_key(key, new_occurrence(n));
+            diff_value(&old[old_indices[o]].value, &new[new_indices[n]].value, &field_path, changes);
+        }
+        for &o in old_unmatched.iter().skip(new_unmatched.len()) {
+            let mut field_path = path.clone();
+            field_path.push_key(key, old_occurrence(o));
+            changes.push(Change::Removed { path: field_path, value: (*old[old_indices[o]].value).clone() });
+        }
+        for &n in new_unmatched.iter().skip(old_unmatched.len()) {
+            let mut field_path = path.clone();
+            field_path.push_key(key, new_occurrence(n));
+            changes.push(Change::Added { path: field_path, value: (*new[new_indices[n]].value).clone() });
+        }
+    }
+}
+
+/// Maximum product of the lengths of two arrays (without their common
+/// prefix and suffix) to search for equal items in them.
+const MAX_LCS_COMPARISONS: usize = 1 << 22;
+
+fn diff_items(old: &[Box<Value>], new: &[Box<Value>], path: &Path, changes: &mut Vec<Change>) {
+    // Equal items that are kept (a longest common subsequence), found
+    // after skipping the common prefix and suffix.
+    let prefix = old.iter().zip(new.iter()).take_while(|&(old_item, new_item)| old_item == new_item).count();
+    let suffix = old[prefix ..].iter().rev().zip(new[prefix ..].iter().rev())
+        .take_while(|&(old_item, new_item)| old_item == new_item)
+        .count();
+    let old_middle = &old[prefix .. old.len() - suffix];
+    let new_middle = &new[prefix .. new.len() - suffix];
+    let mut anchors: Vec<(usize, usize)> = (0 .. prefix).map(|i| (i, i)).collect();
+    // Too long arrays are compared item by item.
+    if old_middle.len().saturating_mul(new_middle.len()) <= MAX_LCS_COMPARISONS {
+        lcs_anchors(old_middle, new_middle, prefix, prefix, &mut anchors);
+    }
+    anchors.extend((0 .. suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    anchors.push((old.len(), new.len()));
+    
+    // Items between two anchors are compared in order.
+    let (mut old_begin, mut new_begin) = (0, 0);
+    for (old_end, new_end) in anchors {
+        let paired = std::cmp::min(old_end - old_begin, new_end - new_begin);
+        for k in 0 .. paired {
+            let mut item_path = path.clone();
+            item_path.push_item(new_begin + k);
+            diff_value(&old[old_begin + k], &new[new_begin + k], &item_path, changes);
+        }
+        for (k, item) in old[old_begin + paired .. old_end].iter().enumerate() {
+            let mut item_path = path.clone();
+            item_path.push_item(old_begin + paired + k);
+            changes.push(Change::Removed { path: item_path, value: (**item).clone() });
+        }
+        for (k, item) in new[new_begin + paired .. new_end].iter().enumerate() {
+            let mut item_path = path.clone();
+            item_path.push_item(new_begin + paired + k);
+            changes.push(Change::Added { path: item_path, value: (**item).clone() });
+        }
+        old_begin = old_end + 1;
+        new_begin = new_end + 1;
+    }
+}
+
+/// Appends the indices of the items of a longest common subsequence of
+/// `old` and `new`, offset by `old_offset` and `new_offset`.
+///
+/// Uses Hirschberg's algorithm, which needs memory proportional to
+/// the length of the arrays.
+fn lcs_anchors(old: &[Box<Value>], new: &[Box<Value>], old_offset: usize, new_offset: usize, anchors: &mut Vec<(usize, usize)>) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    if old.len() == 1 {
+        if let Some(j) = new.iter().position(|item| *item == old[0]) {
+            anchors.push((old_offset, new_offset + j));
+        }
+        return;
+    }
+    
+    let middle = old.len() / 2;
+    let upper = lcs_lengths(old[.. middle].iter(), new.iter());
+    let lower = lcs_lengths(old[middle ..].iter().rev(), new.iter().rev());
+    
+    // Split `new` where the sum of both halves is maximum.
+    let mut split = 0;
+    for j in 1 .. new.len() + 1 {
+        if upper[j] + lower[new.len() - j] > upper[split] + lower[new.len() - split] {
+            split = j;
+        }
+    }
+    
+    lcs_anchors(&old[.. middle], &new[.. split], old_offset, new_offset, anchors);
+    lcs_anchors(&old[middle ..], &new[split ..], old_offset + middle, new_offset + split, anchors);
+}
+
+/// Returns the length of the longest common subsequence of `old` and
+/// each prefix of `new`.
+fn lcs_lengths<'a, I, J>(old: I, new: J) -> Vec<usize>
+    where I: Iterator<Item=&'a Box<Value>>, J: Iterator<Item=&'a Box<Value>> + Clone
+{
+    let mut row = vec![0; new.clone().count() + 1];
+    for old_item in old {
+        let mut diagonal = 0;
+        for (j, new_item) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if old_item == new_item {
+                diagonal + 1
+            } else {
+                std::cmp::max(above, row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+#[inline]
+fn compact(value: &Value) -> String {
+    let mut output = String::new();
+    write_compact(value, &mut output);
+    output
+}
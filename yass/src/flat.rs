@@ -0,0 +1,418 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Flat representation of documents.
+//!
+//! A `FlatDocument` stores all its nodes in a single vector and all its
+//! atoms, keys and tags in a single string, so it only needs a few
+//! allocations regardless of the number of nodes. Nodes are accessed
+//! through `NodeRef` handles.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! #[macro_use]
+//! extern crate yass;
+//!
+//! use yass::flat::{FlatDocument, FlatValue};
+//!
+//! fn main() {
+//!     let document = yass_document!(("example") "a": "1", "b": ["2", "3"]);
+//!     let flat = FlatDocument::from_document(&document);
+//!     
+//!     let field = flat.root_fields().get(1).unwrap();
+//!     assert_eq!(field.key(), Some("b"));
+//!     match field.value() {
+//!         FlatValue::Array(items) => {
+//!             let items = items.map(|item| item.as_atom().unwrap()).collect::<Vec<_>>();
+//!             assert_eq!(items, ["2", "3"]);
+//!         }
+//!         _ => unreachable!(),
+//!     }
+//!     
+//!     assert_eq!(flat.to_document(), document);
+//! }
+//! ```
+
+use super::{Document, StructField, Value};
+
+/// Identifies a node of a `FlatDocument`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct StrRange {
+    start: u32,
+    end: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum NodeData {
+    Atom(StrRange),
+    /// Range in `children`.
+    Array(u32, u32),
+    Tagged(StrRange, NodeId),
+    /// Range in `children`.
+    Struct(u32, u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Node {
+    /// Key of the field, if the node is the value of a struct field.
+    key: Option<StrRange>,
+    data: NodeData,
+}
+
+/// A document whose nodes are stored contiguously.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlatDocument {
+    text: String,
+    nodes: Vec<Node>,
+    children: Vec<NodeId>,
+    header: StrRange,
+    root_fields: (u32, u32),
+}
+
+#[inline]
+fn to_u32(value: usize) -> u32 {
+    assert!(value <= u32::max_value() as usize, "flat document too big");
+    value as u32
+}
+
+impl FlatDocument {
+    /// Converts a `Document`.
+    pub fn from_document(document: &Document) -> Self {
+        fn add_value(builder: &mut FlatDocumentBuilder, value: &Value) -> NodeId {
+            match *value {
+                Value::Atom(ref atom) => builder.atom(atom),
+                Value::Array(ref items) => {
+                    let mark = builder.mark();
+                    for item in items.iter() {
+                        let item = add_value(builder, item);
+                        builder.push_item(item);
+                    }
+                    builder.array(mark)
+                }
+                Value::Tagged(ref tag, ref sub_value) => {
+                    let sub_value = add_value(builder, sub_value);
+                    builder.tagged(tag, sub_value)
+                }
+                Value::Struct(ref fields) => {
+                    let mark = builder.mark();
+                    add_fields(builder, fields);
+                    builder.struct_value(mark)
+                }
+            }
+        }
+        
+        fn add_fields(builder: &mut FlatDocumentBuilder, fields: &[StructField]) {
+            for field in fields.iter() {
+                let value = add_value(builder, &field.value);
+                builder.push_field(&field.key, value);
+            }
+        }
+        
+        let mut builder = FlatDocumentBuilder::new();
+        let mark = builder.mark();
+        add_fields(&mut builder, &document.root_fields);
+        builder.finish(&document.header, mark)
+    }
+    
+    /// Converts to a `Document`.
+    pub fn to_document(&self) -> Document {
+        Document {
+            header: self.header().to_string(),
+            root_fields: self.root_fields().map(|field| field.to_struct_field()).collect(),
+        }
+    }
+    
+    #[inline]
+    pub fn header(&self) -> &str {
+        self.str(self.header)
+    }
+    
+    #[inline]
+    pub fn root_fields<'a>(&'a self) -> Children<'a> {
+        Children {
+            document: self,
+            ids: &self.children[self.root_fields.0 as usize .. self.root_fields.1 as usize],
+        }
+    }
+    
+    /// Gets a node by its id.
+    ///
+    /// Panics if the id does not belong to this document.
+    #[inline]
+    pub fn node<'a>(&'a self, id: NodeId) -> NodeRef<'a> {
+        assert!(id.index() < self.nodes.len());
+        NodeRef { document: self, id: id }
+    }
+    
+    /// Number of nodes, excluding the document itself.
+    #[inline]
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+    
+    #[inline]
+    fn str(&self, range: StrRange) -> &str {
+        &self.text[range.start as usize .. range.end as usize]
+    }
+}
+
+impl<'a> From<&'a Document> for FlatDocument {
+    #[inline]
+    fn from(document: &'a Document) -> Self {
+        Self::from_document(document)
+    }
+}
+
+impl<'a> From<&'a FlatDocument> for Document {
+    #[inline]
+    fn from(document: &'a FlatDocument) -> Self {
+        document.to_document()
+    }
+}
+
+/// The contents of a node.
+#[derive(Clone, Debug)]
+pub enum FlatValue<'a> {
+    Atom(&'a str),
+    Array(Children<'a>),
+    Tagged(&'a str, NodeRef<'a>),
+    Struct(Children<'a>),
+}
+
+/// A handle to a node of a `FlatDocument`.
+#[derive(Copy, Clone, Debug)]
+pub struct NodeRef<'a> {
+    document: &'a FlatDocument,
+    id: NodeId,
+}
+
+impl<'a> NodeRef<'a> {
+    #[inline]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+    
+    /// Gets the key of the struct field whose value is this node.
+    #[inline]
+    pub fn key(&self) -> Option<&'a str> {
+        let document = self.document;
+        self.node().key.map(|key| document.str(key))
+    }
+    
+    pub fn value(&self) -> FlatValue<'a> {
+        let document = self.document;
+        match self.node().data {
+            NodeData::Atom(atom) => FlatValue::Atom(document.str(atom)),
+            NodeData::Array(start, end) => FlatValue::Array(self.children(start, end)),
+            NodeData::Tagged(tag, sub_value) => FlatValue::Tagged(document.str(tag), document.node(sub_value)),
+            NodeData::Struct(start, end) => FlatValue::Struct(self.children(start, end)),
+        }
+    }
+    
+    #[inline]
+    pub fn as_atom(&self) -> Option<&'a str> {
+        match self.value() {
+            FlatValue::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+    
+    /// Gets a child with the same numbering as `PosMap`: array items,
+    /// struct fields, or the inner value of a tagged value.
+    pub fn child(&self, index: usize) -> Option<NodeRef<'a>> {
+        match self.value() {
+            FlatValue::Atom(_) => None,
+            FlatValue::Array(children) | FlatValue::Struct(children) => children.get(index),
+            FlatValue::Tagged(_, sub_value) => if index == 0 { Some(sub_value) } else { None },
+        }
+    }
+    
+    /// Converts the node to a `Value`.
+    pub fn to_value(&self) -> Value {
+        match self.value() {
+            FlatValue::Atom(atom) => Value::Atom(atom.to_string()),
+            FlatValue::Array(items) => Value::Array(items.map(|item| Box::new(item.to_value())).collect()),
+            FlatValue::Tagged(tag, sub_value) => Value::Tagged(tag.to_string(), Box::new(sub_value.to_value())),
+            FlatValue::Struct(fields) => Value::Struct(fields.map(|field| field.to_struct_field()).collect()),
+        }
+    }
+    
+    /// Converts the node to a `StructField`, with an empty key if the
+    /// node is not the value of a field.
+    pub fn to_struct_field(&self) -> StructField {
+        StructField {
+            key: self.key().unwrap_or("").to_string(),
+            value: Box::new(self.to_value()),
+        }
+    }
+    
+    #[inline]
+    fn node(&self) -> &'a Node {
+        &self.document.nodes[self.id.index()]
+    }
+    
+    #[inline]
+    fn children(&self, start: u32, end: u32) -> Children<'a> {
+        Children {
+            document: self.document,
+            ids: &self.document.children[start as usize .. end as usize],
+        }
+    }
+}
+
+/// Iterator over array items or struct fields.
+#[derive(Clone, Debug)]
+pub struct Children<'a> {
+    document: &'a FlatDocument,
+    ids: &'a [NodeId],
+}
+
+impl<'a> Children<'a> {
+    /// Gets the remaining child with the given index.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<NodeRef<'a>> {
+        let document = self.document;
+        self.ids.get(index).map(|&id| NodeRef { document: document, id: id })
+    }
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = NodeRef<'a>;
+    
+    #[inline]
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        let (&id, rest) = self.ids.split_first()?;
+        self.ids = rest;
+        Some(NodeRef { document: self.document, id: id })
+    }
+    
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.ids.len(), Some(self.ids.len()))
+    }
+}
+
+impl<'a> ExactSizeIterator for Children<'a> {}
+
+/// Position in the list of pending children of a
+/// `FlatDocumentBuilder`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Mark(usize);
+
+/// Builds a `FlatDocument` bottom-up.
+///
+/// Children are created before their parent. To create an array or a
+/// struct, take a `mark`, create and push its children, and then call
+/// `array` or `struct_value` with the mark.
+#[derive(Clone, Debug)]
+pub struct FlatDocumentBuilder {
+    document: FlatDocument,
+    pending: Vec<NodeId>,
+}
+
+impl FlatDocumentBuilder {
+    pub fn new() -> Self {
+        Self {
+            document: FlatDocument {
+                text: String::new(),
+                nodes: Vec::new(),
+                children: Vec::new(),
+                header: StrRange { start: 0, end: 0 },
+                root_fields: (0, 0),
+            },
+            pending: Vec::new(),
+        }
+    }
+    
+    #[inline]
+    pub fn atom(&mut self, atom: &str) -> NodeId {
+        let atom = self.push_str(atom);
+        self.push_node(NodeData::Atom(atom))
+    }
+    
+    #[inline]
+    pub fn tagged(&mut self, tag: &str, value: NodeId) -> NodeId {
+        let tag = self.push_str(tag);
+        self.push_node(NodeData::Tagged(tag, value))
+    }
+    
+    #[inline]
+    pub fn mark(&self) -> Mark {
+        Mark(self.pending.len())
+    }
+    
+    #[inline]
+    pub fn push_item(&mut self, value: NodeId) {
+        self.pending.push(value);
+    }
+    
+    #[inline]
+    pub fn push_field(&mut self, key: &str, value: NodeId) {
+        let key = self.push_str(key);
+        self.document.nodes[value.index()].key = Some(key);
+        self.pending.push(value);
+    }
+    
+    /// Creates an array with the items pushed since `mark`.
+    #[inline]
+    pub fn array(&mut self, mark: Mark) -> NodeId {
+        let (start, end) = self.take_pending(mark);
+        self.push_node(NodeData::Array(start, end))
+    }
+    
+    /// Creates a struct with the fields pushed since `mark`.
+    #[inline]
+    pub fn struct_value(&mut self, mark: Mark) -> NodeId {
+        let (start, end) = self.take_pending(mark);
+        self.push_node(NodeData::Struct(start, end))
+    }
+    
+    /// Finishes the document, with the fields pushed since `mark` as
+    /// root fields.
+    pub fn finish(mut self, header: &str, mark: Mark) -> FlatDocument {
+        self.document.header = self.push_str(header);
+        self.document.root_fields = self.take_pending(mark);
+        self.document
+    }
+    
+    fn push_str(&mut self, s: &str) -> StrRange {
+        let start = to_u32(self.document.text.len());
+        self.document.text.push_str(s);
+        StrRange { start: start, end: to_u32(self.document.text.len()) }
+    }
+    
+    fn push_node(&mut self, data: NodeData) -> NodeId {
+        let id = NodeId(to_u32(self.document.nodes.len()));
+        self.document.nodes.push(Node { key: None, data: data });
+        id
+    }
+    
+    fn take_pending(&mut self, mark: Mark) -> (u32, u32) {
+        let start = to_u32(self.document.children.len());
+        self.document.children.extend(self.pending.drain(mark.0 ..));
+        (start, to_u32(self.document.children.len()))
+    }
+}
+
+impl Default for FlatDocumentBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
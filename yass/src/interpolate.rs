@@ -0,0 +1,266 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Document, Pos, PosMap, SourceMap, Value};
+
+/// Source of the variables used by `interpolate`.
+pub trait Variables {
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+/// Takes variables from the environment of the process. Variables
+/// whose value is not valid Unicode are undefined.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EnvVariables;
+
+impl Variables for EnvVariables {
+    #[inline]
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+impl<S: std::hash::BuildHasher> Variables for HashMap<String, String, S> {
+    #[inline]
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl Variables for BTreeMap<String, String> {
+    #[inline]
+    fn get(&self, name: &str) -> Option<String> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
+
+/// Interpolation error. Positions point to the `$` of the reference
+/// when the position of the atom is known.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InterpolationError {
+    /// A `${` without its closing `}`.
+    UnclosedReference {
+        pos: Option<Pos>,
+    },
+    InvalidVariableName {
+        name: String,
+        pos: Option<Pos>,
+    },
+    /// A variable without default value is not defined.
+    UndefinedVariable {
+        name: String,
+        pos: Option<Pos>,
+    },
+}
+
+impl InterpolationError {
+    #[inline]
+    pub fn pos(&self) -> Option<Pos> {
+        match *self {
+            InterpolationError::UnclosedReference { pos } |
+            InterpolationError::InvalidVariableName { pos, .. } |
+            InterpolationError::UndefinedVariable { pos, .. } => pos,
+        }
+    }
+}
+
+impl std::fmt::Display for InterpolationError {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        super::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl super::DisplayWithSourceMap for InterpolationError {
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&SourceMap>) -> std::fmt::Result {
+        match *self {
+            InterpolationError::UnclosedReference { .. } => write!(f, "Unclosed variable reference")?,
+            InterpolationError::InvalidVariableName { ref name, .. } => write!(f, "Invalid variable name {:?}", name)?,
+            InterpolationError::UndefinedVariable { ref name, .. } => write!(f, "Undefined variable {:?}", name)?,
+        }
+        if let Some(pos) = self.pos() {
+            write!(f, " at {}", pos.display(source_map))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InterpolationError {
+    fn description(&self) -> &str {
+        match *self {
+            InterpolationError::UnclosedReference { .. } => "Unclosed variable reference",
+            InterpolationError::InvalidVariableName { .. } => "Invalid variable name",
+            InterpolationError::UndefinedVariable { .. } => "Undefined variable",
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(chr) if chr == '_' || chr.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|chr| chr == '_' || chr.is_ascii_alphanumeric())
+}
+
+/// Position of the byte `offset` of an atom that begins at `pos`. String
+/// atoms cannot contain line breaks.
+#[inline]
+fn offset_pos(pos: Option<Pos>, offset: usize) -> Option<Pos> {
+    pos.map(|pos| Pos::with_source(pos.line, pos.column + offset as u32, pos.source))
+}
+
+/// Appends `value` escaped as the contents of a string atom.
+#[inline]
+fn push_escaped(value: &str, output: &mut String) {
+    let escaped = yass_aux::serialize_utf8_string_as_string(value);
+    output.push_str(&escaped[1 .. escaped.len() - 1]);
+}
+
+/// Interpolates the variables of a string atom. `pos` is the position of
+/// the atom. Atoms that are not strings are returned unchanged.
+///
+/// The text of the atom is processed before unescaping it:
+///
+/// * `${NAME}` is replaced by the value of `NAME`, escaped as a string.
+///   It is an error if `NAME` is not defined.
+/// * `${NAME:-default}` is replaced by the value of `NAME`, or by
+///   `default` if `NAME` is undefined or empty. `default` is copied
+///   as written, so it can contain escape sequences, but not references.
+/// * `$${` is replaced by `${`.
+///
+/// Names are made of ASCII letters, digits and `_`, and do not begin
+/// with a digit. Any other `$` is kept.
+pub fn interpolate_atom<V: Variables + ?Sized>(atom: &str, pos: Option<Pos>, variables: &V)
+    -> Result<String, InterpolationError>
+{
+    if !atom.starts_with('"') {
+        return Ok(atom.to_string());
+    }
+    
+    let bytes = atom.as_bytes();
+    let mut output = String::with_capacity(atom.len());
+    let mut i = 0;
+    let mut copied = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' if atom[i ..].starts_with("$${") => {
+                output.push_str(&atom[copied .. i]);
+                output.push_str("${");
+                i += 3;
+                copied = i;
+            }
+            b'$' if atom[i ..].starts_with("${") => {
+                output.push_str(&atom[copied .. i]);
+                let ref_pos = offset_pos(pos, i);
+                
+                // Find the closing brace, skipping escape sequences.
+                let begin = i + 2;
+                let mut end = begin;
+                loop {
+                    match bytes.get(end) {
+                        Some(&b'}') => break,
+                        Some(&b'\\') if atom[end ..].starts_with("\\u{") => {
+                            end += atom[end ..].find('}').map_or(bytes.len() - end, |j| j + 1);
+                        }
+                        Some(&b'\\') => end += 2,
+                        Some(&b'"') | None => return Err(InterpolationError::UnclosedReference { pos: ref_pos }),
+                        Some(_) => end += 1,
+                    }
+                }
+                
+                let reference = &atom[begin .. end];
+                let (name, default) = match reference.find(":-") {
+                    Some(j) => (&reference[.. j], Some(&reference[j + 2 ..])),
+                    None => (reference, None),
+                };
+                if !is_valid_name(name) {
+                    return Err(InterpolationError::InvalidVariableName { name: name.to_string(), pos: ref_pos });
+                }
+                match (variables.get(name), default) {
+                    (Some(ref value), None) => push_escaped(value, &mut output),
+                    (Some(ref value), Some(_)) if !value.is_empty() => push_escaped(value, &mut output),
+                    (_, Some(default)) => output.push_str(default),
+                    (None, None) => {
+                        return Err(InterpolationError::UndefinedVariable { name: name.to_string(), pos: ref_pos });
+                    }
+                }
+                
+                i = end + 1;
+                copied = i;
+            }
+            _ => i += 1,
+        }
+    }
+    output.push_str(&atom[copied ..]);
+    Ok(output)
+}
+
+/// Interpolates the variables of the string atoms in `value`, as
+/// `interpolate_atom`. Keys and tags are not modified.
+pub fn interpolate_value<V: Variables + ?Sized>(value: &mut Value, pos_map: &PosMap, variables: &V)
+    -> Result<(), InterpolationError>
+{
+    match *value {
+        Value::Atom(ref mut atom) => {
+            *atom = interpolate_atom(atom, pos_map.get_value_pos(), variables)?;
+        }
+        Value::Array(ref mut items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_value(item, pos_map.child(i), variables)?;
+            }
+        }
+        Value::Tagged(_, ref mut sub_value) => interpolate_value(sub_value, pos_map.child(0), variables)?,
+        Value::Struct(ref mut fields) => {
+            for (i, field) in fields.iter_mut().enumerate() {
+                interpolate_value(&mut field.value, pos_map.child(i), variables)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Interpolates the variables of the string atoms in the root fields of
+/// `document`, as `interpolate_atom`. This is an explicit pass, to run
+/// after parsing and before decoding the document (for example, with a
+/// `from_yass_document` generated by `yass_codegen`).
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+///
+/// use std::collections::HashMap;
+///
+/// fn main() {
+///     let mut document = yass_document!(
+///         ("config")
+///         "path": r#""${HOME}/data""#,
+///         "port": r#""${PORT:-8080}""#
+///     );
+///     let mut variables = HashMap::new();
+///     variables.insert("HOME".to_string(), "/home/user".to_string());
+///     
+///     yass::interpolate(&mut document, &yass::PosMap::new(), &variables).unwrap();
+///     assert_eq!(document, yass_document!(
+///         ("config")
+///         "path": r#""/home/user/data""#,
+///         "port": r#""8080""#
+///     ));
+/// }
+/// ```
+pub fn interpolate<V: Variables + ?Sized>(document: &mut Document, pos_map: &PosMap, variables: &V)
+    -> Result<(), InterpolationError>
+{
+    for (i, field) in document.root_fields.iter_mut().enumerate() {
+        interpolate_value(&mut field.value, pos_map.child(i), variables)?;
+    }
+    Ok(())
+}
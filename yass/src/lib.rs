@@ -39,64 +39,249 @@
 //! }
 //! ```
 
-use std::collections::HashMap;
+extern crate yass_aux;
+
+mod atom;
+mod borrowed;
+mod canonical;
+mod diff;
+pub mod flat;
+mod interpolate;
+mod merge;
+mod path;
+mod sha256;
+mod source;
+mod validate;
+pub mod visit;
+
+pub use atom::{AtomKind, AtomError};
+pub use borrowed::{BorrowedValue, BorrowedStructField, BorrowedDocument};
+pub use canonical::{canonical_atom, CanonicalOptions, Digest};
+pub use diff::{diff, diff_values, Change, Diff};
+pub use flat::FlatDocument;
+pub use interpolate::{interpolate, interpolate_atom, interpolate_value, EnvVariables, InterpolationError, Variables};
+pub use merge::{merge, merge_values, Arrays, LayerMap, MergeError, MergeOptions, Merged, RepeatedKeys};
+pub use path::{Path, PathNode, PathStep, PathParseError};
+pub use source::{SourceId, SourceMap, DisplayWithSourceMap, WithSourceMap, PosDisplay};
+pub use validate::{is_atom_chr, is_string_chr, check_atom, AtomSyntaxError, AtomPart, ValidationError};
+pub use visit::{Visit, VisitMut, Fold};
 
 /// Represents a position in a text file.
 ///
-/// `line` and `column` begin to count with zero.
+/// `line` and `column` begin to count with zero. `source` identifies
+/// the file within a `SourceMap`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Pos {
     pub line: u32,
     pub column: u32,
+    pub source: SourceId,
 }
 
 impl Pos {
+    /// Creates a position with an unknown source.
     #[inline]
     pub fn new(line: u32, column: u32) -> Self {
-        Self { line: line, column: column }
+        Self::with_source(line, column, SourceId::UNKNOWN)
+    }
+    
+    #[inline]
+    pub fn with_source(line: u32, column: u32, source: SourceId) -> Self {
+        Self { line: line, column: column, source: source }
     }
 }
 
-/// Maps YASS values with their positions in the original
-/// text file.
-#[derive(Debug)]
+/// Represents a range in a text file.
+///
+/// `start` and `start_offset` point to the first character of the range,
+/// `end` and `end_offset` point just after the last one. Offsets are
+/// counted in bytes from the beginning of the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn new(start: Pos, end: Pos, start_offset: usize, end_offset: usize) -> Self {
+        Self {
+            start: start,
+            end: end,
+            start_offset: start_offset,
+            end_offset: end_offset,
+        }
+    }
+    
+    /// Creates a span that begins at the beginning of `self` and
+    /// finishes at the end of `other`.
+    #[inline]
+    pub fn to(&self, other: &Span) -> Self {
+        Self::new(self.start, other.end, self.start_offset, other.end_offset)
+    }
+    
+    /// Length of the span in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end_offset - self.start_offset
+    }
+}
+
+/// Maps the nodes of a YASS document or value with their positions
+/// in the original text file.
+///
+/// A `PosMap` has the same shape as the value it describes: it has
+/// one child for each array item, one child for each struct field and
+/// one child for the inner value of a tagged value. The map of a
+/// document has one child for each root field. The span of a struct
+/// field and its key are stored in the map of the field value.
+///
+/// Since it does not refer to the values themselves, a `PosMap` is
+/// still valid after the described values are cloned or moved, as
+/// long as they are not modified.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct PosMap {
-    values: HashMap<*const Value, Pos>,
-    struct_fields: HashMap<*const Value, Pos>,
+    span: Option<Span>,
+    field_span: Option<Span>,
+    key_span: Option<Span>,
+    tag_span: Option<Span>,
+    children: Vec<PosMap>,
 }
 
+static EMPTY_POS_MAP: PosMap = PosMap {
+    span: None,
+    field_span: None,
+    key_span: None,
+    tag_span: None,
+    children: Vec::new(),
+};
+
 impl PosMap {
     /// Creates an empty map.
     #[inline]
     pub fn new() -> Self {
         Self {
-            values: HashMap::new(),
-            struct_fields: HashMap::new(),
+            span: None,
+            field_span: None,
+            key_span: None,
+            tag_span: None,
+            children: Vec::new(),
         }
     }
     
-    /// Gets the position of a value, returning `None` if unknown.
+    /// Gets the position of the value, returning `None` if unknown.
+    #[inline]
+    pub fn get_value_pos(&self) -> Option<Pos> {
+        self.span.map(|span| span.start)
+    }
+    
+    /// Gets the position of the struct field whose value is described
+    /// by this map, returning `None` if unknown.
+    #[inline]
+    pub fn get_struct_field_pos(&self) -> Option<Pos> {
+        self.field_span.map(|span| span.start)
+    }
+    
+    /// Gets the span of the value, returning `None` if unknown.
+    ///
+    /// For tagged values, the span includes the tag.
     #[inline]
-    pub fn get_value_pos(&self, value: &Value) -> Option<Pos> {
-        self.values.get(&(value as *const Value)).map(|&v| v)
+    pub fn get_value_span(&self) -> Option<Span> {
+        self.span
     }
     
-    /// Gets the position of a struct field, returning `None` if unknown.
+    /// Gets the span of the struct field (from the beginning of the
+    /// key to the end of the value), returning `None` if unknown.
     #[inline]
-    pub fn get_struct_field_pos(&self, value: &Value) -> Option<Pos> {
-        self.struct_fields.get(&(value as *const Value)).map(|&v| v)
+    pub fn get_struct_field_span(&self) -> Option<Span> {
+        self.field_span
     }
     
-    /// Adds or overrides the position of a value.
+    /// Gets the span of the key of the struct field, returning `None`
+    /// if unknown.
     #[inline]
-    pub fn set_value_pos(&mut self, value: &Value, pos: Pos) {
-        self.values.insert(value as *const Value, pos);
+    pub fn get_key_span(&self) -> Option<Span> {
+        self.key_span
     }
     
-    /// Adds or overrides the position of a struct field.
+    /// Gets the span of the tag (without parentheses) of a tagged
+    /// value, returning `None` if unknown.
     #[inline]
-    pub fn set_struct_field_pos(&mut self, value: &Value, pos: Pos) {
-        self.struct_fields.insert(value as *const Value, pos);
+    pub fn get_tag_span(&self) -> Option<Span> {
+        self.tag_span
+    }
+    
+    /// Gets the span of the header (without parentheses) of a
+    /// document, returning `None` if unknown.
+    #[inline]
+    pub fn get_header_span(&self) -> Option<Span> {
+        self.tag_span
+    }
+    
+    /// Sets the span of the value.
+    #[inline]
+    pub fn set_value_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
+    
+    /// Sets the span of the struct field.
+    #[inline]
+    pub fn set_struct_field_span(&mut self, span: Span) {
+        self.field_span = Some(span);
+    }
+    
+    /// Sets the span of the key of the struct field.
+    #[inline]
+    pub fn set_key_span(&mut self, span: Span) {
+        self.key_span = Some(span);
+    }
+    
+    /// Sets the span of the tag of a tagged value.
+    #[inline]
+    pub fn set_tag_span(&mut self, span: Span) {
+        self.tag_span = Some(span);
+    }
+    
+    /// Sets the span of the header of a document.
+    #[inline]
+    pub fn set_header_span(&mut self, span: Span) {
+        self.tag_span = Some(span);
+    }
+    
+    /// Gets the map of the child with the given index, returning an
+    /// empty map if unknown.
+    #[inline]
+    pub fn child(&self, index: usize) -> &PosMap {
+        self.children.get(index).unwrap_or(&EMPTY_POS_MAP)
+    }
+    
+    /// Gets the map of a descendant, following `index_path` from this
+    /// map. Returns an empty map if unknown.
+    pub fn get(&self, index_path: &[usize]) -> &PosMap {
+        let mut current = self;
+        for &index in index_path {
+            current = current.child(index);
+        }
+        current
+    }
+    
+    /// Gets the maps of all known children.
+    #[inline]
+    pub fn children(&self) -> &[PosMap] {
+        &self.children
+    }
+    
+    /// Gets a mutable reference to the maps of all known children.
+    #[inline]
+    pub fn children_mut(&mut self) -> &mut Vec<PosMap> {
+        &mut self.children
+    }
+    
+    /// Appends the map of a child.
+    #[inline]
+    pub fn push_child(&mut self, child: PosMap) {
+        self.children.push(child);
     }
 }
 
@@ -344,3 +529,6 @@ macro_rules! yass_document {
         }
     };
 }
+
+#[cfg(test)]
+mod tests;
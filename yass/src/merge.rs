@@ -0,0 +1,343 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+
+use super::{Document, PosMap, StructField, Value};
+
+/// How `merge` handles keys that appear more than once in a struct.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RepeatedKeys {
+    /// The fields of the upper layer are appended after the existing ones.
+    Append,
+    /// The fields of the upper layer replace all the existing fields with
+    /// the same key.
+    Replace,
+}
+
+/// How `merge` handles arrays present in both layers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Arrays {
+    /// The array of the upper layer replaces the existing one.
+    Replace,
+    /// The items of the upper layer are appended after the existing ones.
+    Concat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MergeOptions {
+    pub repeated_keys: RepeatedKeys,
+    pub arrays: Arrays,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            repeated_keys: RepeatedKeys::Append,
+            arrays: Arrays::Replace,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MergeError {
+    NoLayers,
+    HeaderMismatch {
+        layer: usize,
+        expected: String,
+        found: String,
+    },
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            MergeError::NoLayers => write!(f, "No layers to merge"),
+            MergeError::HeaderMismatch { layer, ref expected, ref found } => {
+                write!(f, "Layer {} has header \"{}\", expected \"{}\"", layer, found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {
+    fn description(&self) -> &str {
+        match *self {
+            MergeError::NoLayers => "no layers to merge",
+            MergeError::HeaderMismatch { .. } => "header mismatch",
+        }
+    }
+}
+
+/// Maps the nodes of a merged document or value with the layer they
+/// came from.
+///
+/// It has the same shape as a `PosMap`. Each node stores the index of
+/// the layer and the index path of the node within that layer. A
+/// merged struct, a tagged value whose inner value was merged or a
+/// concatenated array comes from the layer that introduced it, while
+/// each of its children has its own origin.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LayerMap {
+    layer: usize,
+    index_path: Vec<usize>,
+    children: Vec<LayerMap>,
+}
+
+impl LayerMap {
+    /// Index of the layer the node came from.
+    #[inline]
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+    
+    /// Index path of the node within its layer.
+    #[inline]
+    pub fn index_path(&self) -> &[usize] {
+        &self.index_path
+    }
+    
+    /// Gets the map of the child with the given index.
+    #[inline]
+    pub fn child(&self, index: usize) -> Option<&LayerMap> {
+        self.children.get(index)
+    }
+    
+    /// Gets the map of a descendant, following `index_path` from this
+    /// map.
+    pub fn get(&self, index_path: &[usize]) -> Option<&LayerMap> {
+        let mut current = self;
+        for &index in index_path {
+            current = current.child(index)?;
+        }
+        Some(current)
+    }
+    
+    #[inline]
+    pub fn children(&self) -> &[LayerMap] {
+        &self.children
+    }
+    
+    /// Builds the `PosMap` of the merged document from the maps of the
+    /// layers, taking each node from the layer it came from.
+    pub fn to_pos_map(&self, layer_pos_maps: &[PosMap]) -> PosMap {
+        let mut pos_map = match layer_pos_maps.get(self.layer) {
+            Some(layer_pos_map) => {
+                let origin = layer_pos_map.get(&self.index_path);
+                PosMap {
+                    span: origin.span,
+                    field_span: origin.field_span,
+                    key_span: origin.key_span,
+                    tag_span: origin.tag_span,
+                    children: Vec::new(),
+                }
+            }
+            None => PosMap::new(),
+        };
+        for child in self.children.iter() {
+            pos_map.push_child(child.to_pos_map(layer_pos_maps));
+        }
+        pos_map
+    }
+    
+    fn from_value(value: &Value, layer: usize, index_path: &mut Vec<usize>) -> Self {
+        let children = match *value {
+            Value::Atom(_) => Vec::new(),
+            Value::Array(ref items) => items.iter().enumerate().map(|(i, item)| {
+                Self::from_child(item, layer, index_path, i)
+            }).collect(),
+            Value::Tagged(_, ref sub_value) => vec![Self::from_child(sub_value, layer, index_path, 0)],
+            Value::Struct(ref fields) => fields.iter().enumerate().map(|(i, field)| {
+                Self::from_child(&field.value, layer, index_path, i)
+            }).collect(),
+        };
+        LayerMap {
+            layer: layer,
+            index_path: index_path.clone(),
+            children: children,
+        }
+    }
+    
+    fn from_child(value: &Value, layer: usize, index_path: &mut Vec<usize>, index: usize) -> Self {
+        index_path.push(index);
+        let map = Self::from_value(value, layer, index_path);
+        index_path.pop();
+        map
+    }
+}
+
+/// Result of `merge`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Merged {
+    pub document: Document,
+    pub layer_map: LayerMap,
+}
+
+/// Merges several documents with the same header, each layer
+/// overriding the previous ones.
+///
+/// * Fields whose key appears once in both layers are merged: structs
+///   are merged recursively, and any other value is replaced.
+/// * Fields whose key is repeated in any of the layers are handled
+///   according to `options.repeated_keys`.
+/// * Arrays are handled according to `options.arrays`.
+/// * Tagged values with the same tag merge their inner values. If the
+///   tag differs, the value is replaced.
+///
+/// ```
+/// #[macro_use]
+/// extern crate yass;
+///
+/// fn main() {
+///     let defaults = yass_document!(("config") "server": {"host": "localhost", "port": "80"});
+///     let user = yass_document!(("config") "server": {"port": "8080"});
+///     let merged = yass::merge(&[defaults, user], &yass::MergeOptions::default()).unwrap();
+///     assert_eq!(merged.document, yass_document!(("config") "server": {"host": "localhost", "port": "8080"}));
+///     assert_eq!(merged.layer_map.get(&[0, 0]).unwrap().layer(), 0);
+///     assert_eq!(merged.layer_map.get(&[0, 1]).unwrap().layer(), 1);
+/// }
+/// ```
+pub fn merge(layers: &[Document], options: &MergeOptions) -> Result<Merged, MergeError> {
+    let first = layers.first().ok_or(MergeError::NoLayers)?;
+    let mut root_fields = first.root_fields.clone();
+    let mut layer_map = LayerMap {
+        layer: 0,
+        index_path: Vec::new(),
+        children: root_fields.iter().enumerate().map(|(i, field)| {
+            LayerMap::from_value(&field.value, 0, &mut vec![i])
+        }).collect(),
+    };
+    
+    for (layer, document) in layers.iter().enumerate().skip(1) {
+        if document.header != first.header {
+            return Err(MergeError::HeaderMismatch {
+                layer: layer,
+                expected: first.header.clone(),
+                found: document.header.clone(),
+            });
+        }
+        merge_fields(&mut root_fields, &mut layer_map, &document.root_fields, layer, &mut Vec::new(), options);
+    }
+    
+    Ok(Merged {
+        document: Document {
+            header: first.header.clone(),
+            root_fields: root_fields,
+        },
+        layer_map: layer_map,
+    })
+}
+
+/// Like `merge`, but for values.
+pub fn merge_values(layers: &[Value], options: &MergeOptions) -> Result<(Value, LayerMap), MergeError> {
+    let first = layers.first().ok_or(MergeError::NoLayers)?;
+    let mut value = first.clone();
+    let mut layer_map = LayerMap::from_value(first, 0, &mut Vec::new());
+    for (layer, upper) in layers.iter().enumerate().skip(1) {
+        merge_value(&mut value, &mut layer_map, upper, layer, &mut Vec::new(), options);
+    }
+    Ok((value, layer_map))
+}
+
+fn merge_value(
+    value: &mut Value,
+    map: &mut LayerMap,
+    upper: &Value,
+    layer: usize,
+    index_path: &mut Vec<usize>,
+    options: &MergeOptions,
+) {
+    match (value, upper) {
+        (&mut Value::Struct(ref mut fields), &Value::Struct(ref upper_fields)) => {
+            merge_fields(fields, map, upper_fields, layer, index_path, options);
+        }
+        (&mut Value::Array(ref mut items), &Value::Array(ref upper_items)) if options.arrays == Arrays::Concat => {
+            for (i, item) in upper_items.iter().enumerate() {
+                items.push(item.clone());
+                map.children.push(LayerMap::from_child(item, layer, index_path, i));
+            }
+        }
+        (&mut Value::Tagged(ref tag, ref mut sub_value), &Value::Tagged(ref upper_tag, ref upper_sub_value)) if tag == upper_tag => {
+            index_path.push(0);
+            merge_value(sub_value, &mut map.children[0], upper_sub_value, layer, index_path, options);
+            index_path.pop();
+        }
+        (value, upper) => {
+            *value = upper.clone();
+            *map = LayerMap::from_value(upper, layer, index_path);
+        }
+    }
+}
+
+fn merge_fields(
+    fields: &mut Vec<StructField>,
+    map: &mut LayerMap,
+    upper_fields: &[StructField],
+    layer: usize,
+    index_path: &mut Vec<usize>,
+    options: &MergeOptions,
+) {
+    // Indices of the fields with each key of the upper layer, in the
+    // upper layer and in `fields`, with the keys in order of appearance.
+    let mut keys: Vec<&str> = Vec::new();
+    let mut groups: HashMap<&str, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for (j, upper_field) in upper_fields.iter().enumerate() {
+        groups.entry(&upper_field.key).or_insert_with(|| {
+            keys.push(&upper_field.key);
+            (Vec::new(), Vec::new())
+        }).0.push(j);
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if let Some(group) = groups.get_mut(field.key.as_str()) {
+            group.1.push(i);
+        }
+    }
+    
+    // Fields with a single occurrence in both layers are merged. Otherwise,
+    // the fields of the upper layer replace the ones of `fields` (at the
+    // first of them) or are appended.
+    let mut replace = false;
+    let mut appended = Vec::new();
+    for key in keys.iter() {
+        let (ref upper_indices, ref indices) = groups[key];
+        if indices.len() == 1 && upper_indices.len() == 1 {
+            let (i, j) = (indices[0], upper_indices[0]);
+            index_path.push(j);
+            merge_value(&mut fields[i].value, &mut map.children[i], &upper_fields[j].value, layer, index_path, options);
+            index_path.pop();
+        } else if options.repeated_keys == RepeatedKeys::Replace && !indices.is_empty() {
+            replace = true;
+        } else {
+            appended.extend_from_slice(upper_indices);
+        }
+    }
+    
+    if replace {
+        let old_fields = std::mem::replace(fields, Vec::new());
+        let old_children = std::mem::replace(&mut map.children, Vec::new());
+        for (i, (field, child)) in old_fields.into_iter().zip(old_children).enumerate() {
+            match groups.get(field.key.as_str()) {
+                Some(&(ref upper_indices, ref indices)) if indices.len() != 1 || upper_indices.len() != 1 => {
+                    if indices[0] == i {
+                        for &j in upper_indices.iter() {
+                            fields.push(upper_fields[j].clone());
+                            map.children.push(LayerMap::from_child(&upper_fields[j].value, layer, index_path, j));
+                        }
+                    }
+                }
+                _ => {
+                    fields.push(field);
+                    map.children.push(child);
+                }
+            }
+        }
+    }
+    for &j in appended.iter() {
+        fields.push(upper_fields[j].clone());
+        map.children.push(LayerMap::from_child(&upper_fields[j].value, layer, index_path, j));
+    }
+}
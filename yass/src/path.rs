@@ -0,0 +1,597 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+
+use super::{Document, StructField, Value};
+
+/// A step of a `Path`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathStep {
+    /// Selects the struct fields with the given key.
+    Key(String),
+    /// Selects all children (struct fields or array items).
+    Any,
+    /// Keeps only the n-th (starting with zero) node selected by the
+    /// previous steps of the same segment.
+    Index(usize),
+    /// Keeps only the tagged values with the given tag.
+    Tag(String),
+}
+
+/// A path to zero or more nodes of a document or value.
+///
+/// The text form is a list of segments separated by `/`. Each segment
+/// is a key (or `*` to match any child) followed by any number of
+/// filters: `[n]` keeps the n-th match and `(tag)` keeps values tagged
+/// with `tag`. A segment made only of filters matches any child, so
+/// `list/[2]` is the third item of the array `list`.
+///
+/// * `window/title`: the field `title` of the field `window`.
+/// * `item[1]/name`: the field `name` of the second `item` field.
+/// * `item(text)/data`: the field `data` of the `item` fields whose
+///   value is tagged with `text`.
+///
+/// Keys and tags are matched through tagged values, so `item/name` also
+/// finds `name` in `item (image){ name "sun1" }`. The characters
+/// `/ * [ ] ( ) \` can be escaped with `\`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    steps: Vec<PathStep>,
+}
+
+impl Path {
+    /// Creates an empty path, which selects the root.
+    #[inline]
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+    
+    #[inline]
+    pub fn from_steps(steps: Vec<PathStep>) -> Self {
+        Self { steps: steps }
+    }
+    
+    #[inline]
+    pub fn steps(&self) -> &[PathStep] {
+        &self.steps
+    }
+    
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+    
+    #[inline]
+    pub fn push(&mut self, step: PathStep) {
+        self.steps.push(step);
+    }
+    
+    #[inline]
+    pub fn pop(&mut self) -> Option<PathStep> {
+        self.steps.pop()
+    }
+    
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.steps.truncate(len);
+    }
+    
+    /// Appends the steps that lead to the field at `index` of `fields`:
+    /// its key, followed by its index among the fields with the same key
+    /// if the key is repeated.
+    ///
+    /// This scans `fields`, so use `push_key` with the result of
+    /// `field_occurrences` when visiting every field of a struct.
+    pub fn push_field(&mut self, fields: &[StructField], index: usize) {
+        let key = &fields[index].key;
+        let occurrence = fields[.. index].iter().filter(|field| field.key == *key).count();
+        let is_repeated = occurrence != 0 || fields[index + 1 ..].iter().any(|field| field.key == *key);
+        self.push_key(key, if is_repeated { Some(occurrence) } else { None });
+    }
+    
+    /// Appends the steps that lead to a field with key `key`, followed by
+    /// `occurrence` if it is not `None`.
+    #[inline]
+    pub fn push_key(&mut self, key: &str, occurrence: Option<usize>) {
+        self.steps.push(PathStep::Key(key.to_string()));
+        if let Some(occurrence) = occurrence {
+            self.steps.push(PathStep::Index(occurrence));
+        }
+    }
+    
+    /// Appends the steps that lead to the array item at `index`.
+    #[inline]
+    pub fn push_item(&mut self, index: usize) {
+        self.steps.push(PathStep::Any);
+        self.steps.push(PathStep::Index(index));
+    }
+    
+    pub fn parse(text: &str) -> Result<Self, PathParseError> {
+        PathParser::new(text).parse()
+    }
+    
+    /// Finds the nodes of `document` selected by this path.
+    ///
+    /// Each node is returned as a list of child indices from the root,
+    /// with the same shape as a `PosMap`: the inner value of a tagged
+    /// value is its child 0.
+    pub fn resolve(&self, document: &Document) -> Vec<Vec<usize>> {
+        self.resolve_node(Node::Document(document))
+    }
+    
+    /// Like `resolve`, but starting at a value.
+    pub fn resolve_value(&self, value: &Value) -> Vec<Vec<usize>> {
+        self.resolve_node(Node::Value(value))
+    }
+    
+    /// Like `resolve`, but starting at any node with the shape of a
+    /// document or value.
+    pub fn resolve_node<'a, N: PathNode<'a>>(&self, root: N) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<(Vec<usize>, N)>> = vec![vec![(Vec::new(), root)]];
+        for step in self.steps.iter() {
+            match *step {
+                PathStep::Key(_) | PathStep::Any => {
+                    let key = match *step {
+                        PathStep::Key(ref key) => Some(key.as_str()),
+                        _ => None,
+                    };
+                    let mut new_groups = Vec::new();
+                    for (index_path, node) in groups.into_iter().flat_map(|group| group.into_iter()) {
+                        let mut group = Vec::new();
+                        let (index_path, node) = skip_tags(index_path, node);
+                        for (i, (child_key, child)) in node.children().into_iter().enumerate() {
+                            if key.is_none() || key == child_key {
+                                let mut child_index_path = index_path.clone();
+                                child_index_path.push(i);
+                                group.push((child_index_path, child));
+                            }
+                        }
+                        new_groups.push(group);
+                    }
+                    groups = new_groups;
+                }
+                PathStep::Index(index) => {
+                    for group in groups.iter_mut() {
+                        if index < group.len() {
+                            let item = group.swap_remove(index);
+                            group.clear();
+                            group.push(item);
+                        } else {
+                            group.clear();
+                        }
+                    }
+                }
+                PathStep::Tag(ref tag) => {
+                    for group in groups.iter_mut() {
+                        group.retain(|item| item.1.tagged().map(|(item_tag, _)| item_tag) == Some(tag.as_str()));
+                    }
+                }
+            }
+        }
+        groups.into_iter().flat_map(|group| group.into_iter().map(|(index_path, _)| index_path)).collect()
+    }
+}
+
+/// A node of a tree with the shape of a document or value, in which a
+/// `Path` can be resolved with `Path::resolve_node`. It allows resolving
+/// paths in other representations of a document without converting them
+/// to a `Document`.
+pub trait PathNode<'a>: Copy {
+    /// Gets the tag and the inner value, if the node is a tagged value.
+    fn tagged(self) -> Option<(&'a str, Self)>;
+    
+    /// Gets the struct fields (with their keys) or array items of the
+    /// node, or the root fields if it is a document.
+    fn children(self) -> Vec<(Option<&'a str>, Self)>;
+}
+
+/// Moves from `node` to the value inside its tags, following the
+/// numbering of `PosMap`.
+fn skip_tags<'a, N: PathNode<'a>>(mut index_path: Vec<usize>, mut node: N) -> (Vec<usize>, N) {
+    while let Some((_, sub_node)) = node.tagged() {
+        index_path.push(0);
+        node = sub_node;
+    }
+    (index_path, node)
+}
+
+/// Returns, for each field of `fields`, its index among the fields with
+/// the same key, or `None` if its key is not repeated, as used by
+/// `Path::push_field`.
+pub(crate) fn field_occurrences(fields: &[StructField]) -> Vec<Option<usize>> {
+    let mut counts = HashMap::new();
+    for field in fields.iter() {
+        *counts.entry(field.key.as_str()).or_insert(0) += 1;
+    }
+    
+    let mut seen = HashMap::new();
+    fields.iter().map(|field| {
+        if counts[field.key.as_str()] == 1 {
+            return None;
+        }
+        let occurrence = seen.entry(field.key.as_str()).or_insert(0);
+        *occurrence += 1;
+        Some(*occurrence - 1)
+    }).collect()
+}
+
+impl Document {
+    /// Parses `path` and returns the values it selects.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate yass;
+    ///
+    /// fn main() {
+    ///     let document = yass_document!(
+    ///         ("example")
+    ///         "item": (as "image": {"name": "sun1"}),
+    ///         "item": (as "text": {"name": "text1"})
+    ///     );
+    ///     let names = document.select("item(text)/name").unwrap();
+    ///     assert_eq!(names, vec![&yass::Value::Atom("text1".to_string())]);
+    /// }
+    /// ```
+    #[inline]
+    pub fn select(&self, path: &str) -> Result<Vec<&Value>, PathParseError> {
+        Ok(self.select_path(&Path::parse(path)?))
+    }
+    
+    #[inline]
+    pub fn select_mut(&mut self, path: &str) -> Result<Vec<&mut Value>, PathParseError> {
+        Ok(self.select_path_mut(&Path::parse(path)?))
+    }
+    
+    /// Returns the values selected by `path`. The root is not a value,
+    /// so an empty path selects nothing.
+    pub fn select_path(&self, path: &Path) -> Vec<&Value> {
+        path.resolve(self).iter().filter_map(|index_path| self.get(index_path)).collect()
+    }
+    
+    pub fn select_path_mut(&mut self, path: &Path) -> Vec<&mut Value> {
+        let mut index_paths = path.resolve(self);
+        index_paths.retain(|index_path| !index_path.is_empty());
+        index_paths.sort();
+        let index_paths = index_paths.iter().map(|index_path| index_path.as_slice()).collect::<Vec<_>>();
+        
+        let mut values = Vec::new();
+        collect_children_mut(self.root_fields.iter_mut().map(|field| &mut *field.value), &index_paths, &mut values);
+        values
+    }
+    
+    /// Gets the value at `index_path`, as returned by `Path::resolve`.
+    pub fn get(&self, index_path: &[usize]) -> Option<&Value> {
+        let (&index, rest) = index_path.split_first()?;
+        self.root_fields.get(index)?.value.get(rest)
+    }
+}
+
+impl Value {
+    /// Parses `path` and returns the values it selects. An empty path
+    /// selects `self`.
+    #[inline]
+    pub fn select(&self, path: &str) -> Result<Vec<&Value>, PathParseError> {
+        Ok(self.select_path(&Path::parse(path)?))
+    }
+    
+    #[inline]
+    pub fn select_mut(&mut self, path: &str) -> Result<Vec<&mut Value>, PathParseError> {
+        Ok(self.select_path_mut(&Path::parse(path)?))
+    }
+    
+    pub fn select_path(&self, path: &Path) -> Vec<&Value> {
+        path.resolve_value(self).iter().filter_map(|index_path| self.get(index_path)).collect()
+    }
+    
+    pub fn select_path_mut(&mut self, path: &Path) -> Vec<&mut Value> {
+        let mut index_paths = path.resolve_value(self);
+        index_paths.sort();
+        let index_paths = index_paths.iter().map(|index_path| index_path.as_slice()).collect::<Vec<_>>();
+        
+        let mut values = Vec::new();
+        collect_mut(self, &index_paths, &mut values);
+        values
+    }
+    
+    /// Gets the descendant at `index_path`, as returned by
+    /// `Path::resolve_value`.
+    pub fn get(&self, index_path: &[usize]) -> Option<&Value> {
+        let mut current = self;
+        for &index in index_path {
+            current = match *current {
+                Value::Atom(_) => return None,
+                Value::Array(ref items) => items.get(index)?,
+                Value::Tagged(_, ref sub_value) if index == 0 => sub_value,
+                Value::Tagged(..) => return None,
+                Value::Struct(ref fields) => &fields.get(index)?.value,
+            };
+        }
+        Some(current)
+    }
+}
+
+/// Collects the values at `index_paths`, which must be sorted. Since
+/// the paths come from a `Path`, none of them is a prefix of another.
+fn collect_mut<'a>(value: &'a mut Value, index_paths: &[&[usize]], values: &mut Vec<&'a mut Value>) {
+    if index_paths.iter().any(|index_path| index_path.is_empty()) {
+        values.push(value);
+        return;
+    }
+    
+    match *value {
+        Value::Atom(_) => {}
+        Value::Array(ref mut items) => {
+            collect_children_mut(items.iter_mut().map(|item| &mut **item), index_paths, values);
+        }
+        Value::Tagged(_, ref mut sub_value) => {
+            collect_children_mut(std::iter::once(&mut **sub_value), index_paths, values);
+        }
+        Value::Struct(ref mut fields) => {
+            collect_children_mut(fields.iter_mut().map(|field| &mut *field.value), index_paths, values);
+        }
+    }
+}
+
+fn collect_children_mut<'a, I>(children: I, mut index_paths: &[&[usize]], values: &mut Vec<&'a mut Value>)
+    where I: Iterator<Item = &'a mut Value>
+{
+    for (i, child) in children.enumerate() {
+        let count = index_paths.iter().take_while(|index_path| index_path[0] == i).count();
+        if count != 0 {
+            let child_index_paths = index_paths[.. count].iter().map(|index_path| &index_path[1 ..]).collect::<Vec<_>>();
+            collect_mut(child, &child_index_paths, values);
+        }
+        index_paths = &index_paths[count ..];
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Node<'a> {
+    Document(&'a Document),
+    Value(&'a Value),
+}
+
+impl<'a> PathNode<'a> for Node<'a> {
+    fn tagged(self) -> Option<(&'a str, Self)> {
+        match self {
+            Node::Value(&Value::Tagged(ref tag, ref sub_value)) => Some((tag.as_str(), Node::Value(sub_value))),
+            _ => None,
+        }
+    }
+    
+    fn children(self) -> Vec<(Option<&'a str>, Self)> {
+        match self {
+            Node::Document(document) => {
+                document.root_fields.iter().map(|field| (Some(field.key.as_str()), Node::Value(&*field.value))).collect()
+            }
+            Node::Value(value) => {
+                match *value {
+                    Value::Struct(ref fields) => {
+                        fields.iter().map(|field| (Some(field.key.as_str()), Node::Value(&*field.value))).collect()
+                    }
+                    Value::Array(ref items) => {
+                        items.iter().map(|item| (None, Node::Value(&**item))).collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Path {
+    type Err = PathParseError;
+    
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, PathParseError> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            match *step {
+                PathStep::Key(ref key) => {
+                    if i != 0 {
+                        f.write_str("/")?;
+                    }
+                    write_escaped(key, f)?;
+                }
+                PathStep::Any => {
+                    if i != 0 {
+                        f.write_str("/")?;
+                    }
+                    match self.steps.get(i + 1) {
+                        Some(&PathStep::Index(_)) | Some(&PathStep::Tag(_)) => {}
+                        _ => f.write_str("*")?,
+                    }
+                }
+                PathStep::Index(index) => write!(f, "[{}]", index)?,
+                PathStep::Tag(ref tag) => {
+                    f.write_str("(")?;
+                    write_escaped(tag, f)?;
+                    f.write_str(")")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_escaped(s: &str, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for chr in s.chars() {
+        if is_special_chr(chr) {
+            write!(f, "\\{}", chr)?;
+        } else {
+            write!(f, "{}", chr)?;
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn is_special_chr(chr: char) -> bool {
+    match chr {
+        '/' | '*' | '[' | ']' | '(' | ')' | '\\' => true,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathParseError {
+    UnexpectedChr {
+        offset: usize,
+        chr: char,
+    },
+    UnexpectedEnd,
+    EmptySegment {
+        offset: usize,
+    },
+    InvalidIndex {
+        offset: usize,
+    },
+}
+
+impl std::fmt::Display for PathParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            PathParseError::UnexpectedChr { offset, chr } => {
+                write!(f, "Unexpected character {:?} in path at offset {}", chr, offset)
+            }
+            PathParseError::UnexpectedEnd => {
+                write!(f, "Unexpected end of path")
+            }
+            PathParseError::EmptySegment { offset } => {
+                write!(f, "Empty path segment at offset {}", offset)
+            }
+            PathParseError::InvalidIndex { offset } => {
+                write!(f, "Invalid index in path at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathParseError {
+    fn description(&self) -> &str {
+        match *self {
+            PathParseError::UnexpectedChr { .. } => "Unexpected character in path",
+            PathParseError::UnexpectedEnd => "Unexpected end of path",
+            PathParseError::EmptySegment { .. } => "Empty path segment",
+            PathParseError::InvalidIndex { .. } => "Invalid index in path",
+        }
+    }
+}
+
+struct PathParser<'a> {
+    text: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> PathParser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text: text,
+            chars: text.char_indices().peekable(),
+        }
+    }
+    
+    fn parse(mut self) -> Result<Path, PathParseError> {
+        let mut path = Path::new();
+        if self.text.is_empty() {
+            return Ok(path);
+        }
+        
+        loop {
+            self.parse_segment(&mut path)?;
+            match self.chars.next() {
+                None => return Ok(path),
+                Some((_, '/')) => {}
+                Some((offset, chr)) => return Err(PathParseError::UnexpectedChr { offset: offset, chr: chr }),
+            }
+        }
+    }
+    
+    fn parse_segment(&mut self, path: &mut Path) -> Result<(), PathParseError> {
+        let offset = self.offset();
+        match self.chars.peek().cloned() {
+            None | Some((_, '/')) => return Err(PathParseError::EmptySegment { offset: offset }),
+            Some((_, '*')) => {
+                self.chars.next();
+                path.push(PathStep::Any);
+            }
+            Some((_, '[')) | Some((_, '(')) => path.push(PathStep::Any),
+            Some(_) => {
+                let key = self.parse_name(&['/', '[', '('])?;
+                path.push(PathStep::Key(key));
+            }
+        }
+        
+        loop {
+            match self.chars.peek().cloned() {
+                Some((_, '[')) => {
+                    self.chars.next();
+                    let offset = self.offset();
+                    let mut digits = String::new();
+                    while let Some((_, chr)) = self.chars.peek().cloned() {
+                        if chr == ']' {
+                            break;
+                        }
+                        digits.push(chr);
+                        self.chars.next();
+                    }
+                    self.expect(']')?;
+                    let index = digits.parse().map_err(|_| PathParseError::InvalidIndex { offset: offset })?;
+                    path.push(PathStep::Index(index));
+                }
+                Some((_, '(')) => {
+                    self.chars.next();
+                    let tag = self.parse_name(&[')'])?;
+                    self.expect(')')?;
+                    path.push(PathStep::Tag(tag));
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+    
+    fn parse_name(&mut self, terminators: &[char]) -> Result<String, PathParseError> {
+        let mut name = String::new();
+        while let Some((offset, chr)) = self.chars.peek().cloned() {
+            if terminators.contains(&chr) {
+                break;
+            }
+            self.chars.next();
+            if chr == '\\' {
+                match self.chars.next() {
+                    Some((_, chr)) if is_special_chr(chr) => name.push(chr),
+                    Some((offset, chr)) => return Err(PathParseError::UnexpectedChr { offset: offset, chr: chr }),
+                    None => return Err(PathParseError::UnexpectedEnd),
+                }
+            } else if is_special_chr(chr) {
+                return Err(PathParseError::UnexpectedChr { offset: offset, chr: chr });
+            } else {
+                name.push(chr);
+            }
+        }
+        Ok(name)
+    }
+    
+    fn expect(&mut self, expected: char) -> Result<(), PathParseError> {
+        match self.chars.next() {
+            Some((_, chr)) if chr == expected => Ok(()),
+            Some((offset, chr)) => Err(PathParseError::UnexpectedChr { offset: offset, chr: chr }),
+            None => Err(PathParseError::UnexpectedEnd),
+        }
+    }
+    
+    #[inline]
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map(|&(offset, _)| offset).unwrap_or(self.text.len())
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::Pos;
+
+/// Identifies the source (such as a file) of a position.
+///
+/// Identifiers are given by a `SourceMap`. `SourceId::UNKNOWN` is used
+/// by positions whose source is not registered.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(pub u32);
+
+impl SourceId {
+    pub const UNKNOWN: SourceId = SourceId(0);
+}
+
+/// Registry of the names of sources.
+///
+/// ```
+/// extern crate yass;
+///
+/// fn main() {
+///     let mut source_map = yass::SourceMap::new();
+///     let source = source_map.add("config.yass");
+///     let pos = yass::Pos::with_source(2, 4, source);
+///     assert_eq!(pos.display(Some(&source_map)).to_string(), "config.yass:3:5");
+///     assert_eq!(pos.display(None).to_string(), "3:5");
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SourceMap {
+    names: Vec<String>,
+}
+
+impl SourceMap {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Registers a source, returning its identifier.
+    pub fn add<S: Into<String>>(&mut self, name: S) -> SourceId {
+        self.names.push(name.into());
+        SourceId(self.names.len() as u32)
+    }
+    
+    /// Gets the name of a source, or `None` if it is not registered.
+    pub fn name(&self, source: SourceId) -> Option<&str> {
+        match source.0 {
+            0 => None,
+            id => self.names.get(id as usize - 1).map(String::as_str),
+        }
+    }
+    
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    
+    /// Displays `value` using this map to give names to its positions.
+    #[inline]
+    pub fn display<'a, T: DisplayWithSourceMap + ?Sized>(&'a self, value: &'a T) -> WithSourceMap<'a, T> {
+        WithSourceMap { value: value, source_map: self }
+    }
+}
+
+/// Values (usually errors) whose messages contain positions.
+pub trait DisplayWithSourceMap {
+    /// Formats the value, writing positions with `Pos::display`.
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&SourceMap>) -> std::fmt::Result;
+}
+
+/// Returned by `SourceMap::display`.
+pub struct WithSourceMap<'a, T: 'a + ?Sized> {
+    value: &'a T,
+    source_map: &'a SourceMap,
+}
+
+impl<'a, T: DisplayWithSourceMap + ?Sized> std::fmt::Display for WithSourceMap<'a, T> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.value.fmt_with_source_map(f, Some(self.source_map))
+    }
+}
+
+/// Returned by `Pos::display`.
+#[derive(Copy, Clone, Debug)]
+pub struct PosDisplay<'a> {
+    pos: Pos,
+    source_map: Option<&'a SourceMap>,
+}
+
+impl<'a> std::fmt::Display for PosDisplay<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.source_map.and_then(|source_map| source_map.name(self.pos.source)) {
+            Some(name) => write!(f, "{}:{}:{}", name, self.pos.line + 1, self.pos.column + 1),
+            None => write!(f, "{}:{}", self.pos.line + 1, self.pos.column + 1),
+        }
+    }
+}
+
+impl Pos {
+    /// Displays the position as `line:column`, counting from one, and
+    /// prefixed by the name of the source if it is found in `source_map`.
+    #[inline]
+    pub fn display<'a>(self, source_map: Option<&'a SourceMap>) -> PosDisplay<'a> {
+        PosDisplay { pos: self, source_map: source_map }
+    }
+}
@@ -0,0 +1,649 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+mod yass {
+    pub use ::*;
+}
+
+fn key(key: &str) -> yass::PathStep {
+    yass::PathStep::Key(key.to_string())
+}
+
+fn tag(tag: &str) -> yass::PathStep {
+    yass::PathStep::Tag(tag.to_string())
+}
+
+#[test]
+fn test_path_parse() {
+    assert_eq!(yass::Path::parse("").unwrap(), yass::Path::new());
+    assert_eq!(yass::Path::parse("window/title").unwrap(), yass::Path::from_steps(vec![key("window"), key("title")]));
+    assert_eq!(
+        yass::Path::parse("item[2]/name").unwrap(),
+        yass::Path::from_steps(vec![key("item"), yass::PathStep::Index(2), key("name")])
+    );
+    assert_eq!(
+        yass::Path::parse("item(text)/data").unwrap(),
+        yass::Path::from_steps(vec![key("item"), tag("text"), key("data")])
+    );
+    assert_eq!(
+        yass::Path::parse("list/[0]/*").unwrap(),
+        yass::Path::from_steps(vec![key("list"), yass::PathStep::Any, yass::PathStep::Index(0), yass::PathStep::Any])
+    );
+    assert_eq!(yass::Path::parse("a\\/b\\(c\\)").unwrap(), yass::Path::from_steps(vec![key("a/b(c)")]));
+}
+
+#[test]
+fn test_path_parse_fail() {
+    assert_eq!(yass::Path::parse("a//b"), Err(yass::PathParseError::EmptySegment { offset: 2 }));
+    assert_eq!(yass::Path::parse("a/"), Err(yass::PathParseError::EmptySegment { offset: 2 }));
+    assert_eq!(yass::Path::parse("a[x]"), Err(yass::PathParseError::InvalidIndex { offset: 2 }));
+    assert_eq!(yass::Path::parse("a[1"), Err(yass::PathParseError::UnexpectedEnd));
+    assert_eq!(yass::Path::parse("a]"), Err(yass::PathParseError::UnexpectedChr { offset: 1, chr: ']' }));
+    assert_eq!(yass::Path::parse("a*"), Err(yass::PathParseError::UnexpectedChr { offset: 1, chr: '*' }));
+}
+
+#[test]
+fn test_path_display() {
+    for text in ["", "window/title", "item[2]/name", "item(text)/data", "list/[0]/*", "a\\/b\\(c\\)", "*"].iter() {
+        assert_eq!(yass::Path::parse(text).unwrap().to_string(), *text);
+    }
+}
+
+#[test]
+fn test_path_resolve() {
+    let document = yass_document!(
+        ("test")
+        "item": (as "image": {"name": "\"sun1\""}),
+        "item": (as "text": {"name": "\"text1\"", "data": "\"Click Here\""}),
+        "list": ["1", "2", (as "t": "3")],
+        "window": {"title": "\"Sample\""}
+    );
+    let resolve = |path: &str| yass::Path::parse(path).unwrap().resolve(&document);
+    
+    assert_eq!(resolve(""), vec![vec![]]);
+    assert_eq!(resolve("window/title"), vec![vec![3, 0]]);
+    assert_eq!(resolve("item/name"), vec![vec![0, 0, 0], vec![1, 0, 0]]);
+    assert_eq!(resolve("item[1]/name"), vec![vec![1, 0, 0]]);
+    assert_eq!(resolve("item(text)/data"), vec![vec![1, 0, 1]]);
+    assert_eq!(resolve("item(text)[0]"), vec![vec![1]]);
+    assert_eq!(resolve("item(text)[1]"), Vec::<Vec<usize>>::new());
+    assert_eq!(resolve("list/[1]"), vec![vec![2, 1]]);
+    assert_eq!(resolve("list/(t)"), vec![vec![2, 2]]);
+    assert_eq!(resolve("list/*"), vec![vec![2, 0], vec![2, 1], vec![2, 2]]);
+    assert_eq!(resolve("missing/*"), Vec::<Vec<usize>>::new());
+}
+
+fn example_1() -> yass::Document {
+    yass_document!(
+        ("example-1-widget")
+        "debug": "true",
+        "window": {"title": "\"Sample Konfabulator Widget\"", "width": "500"},
+        "item": (as "image": {"src": "\"Images/Sun.png\"", "name": "\"sun1\""}),
+        "item": (as "text": {"data": "\"Click Here\"", "name": "\"text1\""}),
+        "sizes": ["1", "2", "3"]
+    )
+}
+
+#[test]
+fn test_select() {
+    let document = example_1();
+    let atom = |s: &str| yass::Value::Atom(s.to_string());
+    
+    assert_eq!(document.select("window/title").unwrap(), vec![&atom("\"Sample Konfabulator Widget\"")]);
+    assert_eq!(document.select("item/name").unwrap(), vec![&atom("\"sun1\""), &atom("\"text1\"")]);
+    assert_eq!(document.select("item[1]/name").unwrap(), vec![&atom("\"text1\"")]);
+    assert_eq!(document.select("item(text)/data").unwrap(), vec![&atom("\"Click Here\"")]);
+    assert_eq!(document.select("sizes/[2]").unwrap(), vec![&atom("3")]);
+    assert_eq!(document.select("item[2]/name").unwrap(), Vec::<&yass::Value>::new());
+    assert_eq!(document.select("").unwrap(), Vec::<&yass::Value>::new());
+    assert!(document.select("item[").is_err());
+    
+    let window = &document.root_fields[1].value;
+    assert_eq!(window.select("").unwrap(), vec![&**window]);
+    assert_eq!(window.select("width").unwrap(), vec![&atom("500")]);
+}
+
+#[test]
+fn test_select_mut() {
+    let mut document = example_1();
+    for name in document.select_mut("item/name").unwrap() {
+        *name = yass::Value::Atom("\"renamed\"".to_string());
+    }
+    for size in document.select_mut("sizes/*").unwrap() {
+        *size = yass::Value::Atom("0".to_string());
+    }
+    
+    let mut expected = example_1();
+    expected.root_fields[2].value = yass_value!((as "image": {"src": "\"Images/Sun.png\"", "name": "\"renamed\""}));
+    expected.root_fields[3].value = yass_value!((as "text": {"data": "\"Click Here\"", "name": "\"renamed\""}));
+    expected.root_fields[4].value = yass_value!(["0", "0", "0"]);
+    assert_eq!(document, expected);
+    
+    let mut value = *yass_value!((as "t": ["1", ["2", "3"]]));
+    assert_eq!(value.select_mut("[1]/*").unwrap().len(), 2);
+    assert_eq!(value.select_mut("").unwrap().len(), 1);
+}
+
+#[test]
+fn test_atom_kind() {
+    let kind = |atom: &str| yass::Value::Atom(atom.to_string()).atom_kind();
+    assert_eq!(kind("true"), Some(yass::AtomKind::Bool));
+    assert_eq!(kind("-12"), Some(yass::AtomKind::Int));
+    assert_eq!(kind("99999999999999999999"), Some(yass::AtomKind::Int));
+    assert_eq!(kind("1.5"), Some(yass::AtomKind::Float));
+    assert_eq!(kind("-1e-3"), Some(yass::AtomKind::Float));
+    assert_eq!(kind("\"text\""), Some(yass::AtomKind::String));
+    assert_eq!(kind("center"), Some(yass::AtomKind::Symbol));
+    assert_eq!(kind("2018-04-01"), Some(yass::AtomKind::Symbol));
+    assert_eq!(yass_value!(["1"]).atom_kind(), None);
+}
+
+#[test]
+fn test_atom_accessors() {
+    let atom = |atom: &str| yass::Value::Atom(atom.to_string());
+    assert_eq!(atom("false").as_bool(), Ok(false));
+    assert_eq!(atom("-12").as_i64(), Ok(-12));
+    assert_eq!(atom("18446744073709551615").as_u64(), Ok(u64::max_value()));
+    assert_eq!(atom("12").as_f64(), Ok(12.0));
+    assert_eq!(atom("-0.25").as_f64(), Ok(-0.25));
+    assert_eq!(atom("\"a\\n\\\"b\\\"\"").as_str_unescaped(), Ok("a\n\"b\"".to_string()));
+    
+    assert_eq!(atom("1").as_bool(), Err(yass::AtomError::UnexpectedKind { expected: yass::AtomKind::Bool, found: yass::AtomKind::Int }));
+    assert_eq!(atom("1.5").as_i64(), Err(yass::AtomError::UnexpectedKind { expected: yass::AtomKind::Int, found: yass::AtomKind::Float }));
+    assert_eq!(atom("-1").as_u64(), Err(yass::AtomError::IntOutOfRange));
+    assert_eq!(atom("9223372036854775808").as_i64(), Err(yass::AtomError::IntOutOfRange));
+    assert_eq!(atom("\"x\"").as_f64(), Err(yass::AtomError::UnexpectedKind { expected: yass::AtomKind::Float, found: yass::AtomKind::String }));
+    assert_eq!(atom("center").as_str_unescaped(), Err(yass::AtomError::UnexpectedKind { expected: yass::AtomKind::String, found: yass::AtomKind::Symbol }));
+    assert_eq!(atom("\"\\q\"").as_str_unescaped(), Err(yass::AtomError::InvalidString));
+    assert_eq!(yass_value!({}).as_i64(), Err(yass::AtomError::NotAnAtom));
+    assert_eq!(yass::AtomError::UnexpectedKind { expected: yass::AtomKind::Int, found: yass::AtomKind::String }.to_string(), "Expected integer atom, found string atom");
+}
+
+#[test]
+fn test_atom_constructors() {
+    let atom = |atom: &str| yass::Value::Atom(atom.to_string());
+    assert_eq!(yass::Value::string("say \"hi\"\n\\"), atom("\"say \\\"hi\\\"\\n\\\\\""));
+    assert_eq!(yass::Value::string("ñ"), atom("\"\\u{f1}\""));
+    assert_eq!(yass::Value::bytes(b"a\xFF"), atom("\"a\\xff\""));
+    assert_eq!(yass::Value::int(-12), atom("-12"));
+    assert_eq!(yass::Value::uint(u64::max_value()), atom("18446744073709551615"));
+    assert_eq!(yass::Value::bool(true), atom("true"));
+    assert_eq!(yass::Value::try_float(0.5).unwrap().as_f64(), Ok(0.5));
+    assert_eq!(yass::Value::try_float(std::f64::NAN), None);
+    assert_eq!(yass::Value::try_float(std::f64::INFINITY), None);
+    assert_eq!(yass::Value::float(-1.5e-10), yass::Value::try_float(-1.5e-10).unwrap());
+    assert_eq!(yass::Value::from("x"), atom("\"x\""));
+    assert_eq!(yass::Value::from(3), atom("3"));
+    
+    for string in ["", "a b", "{[( )]}", "tab\there", "\u{1F600}"].iter() {
+        assert_eq!(yass::Value::string(string).as_str_unescaped().as_ref().map(|s| s.as_str()), Ok(*string));
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_float_non_finite() {
+    yass::Value::float(std::f64::NAN);
+}
+
+#[test]
+fn test_check_atom() {
+    assert_eq!(yass::check_atom("abc"), Ok(()));
+    assert_eq!(yass::check_atom("\"a b\\\"\"x"), Ok(()));
+    assert_eq!(yass::check_atom(""), Err(yass::AtomSyntaxError::Empty));
+    assert_eq!(yass::check_atom("a b"), Err(yass::AtomSyntaxError::IllegalChr { offset: 1, chr: b' ' }));
+    assert_eq!(yass::check_atom("k{"), Err(yass::AtomSyntaxError::IllegalChr { offset: 1, chr: b'{' }));
+    assert_eq!(yass::check_atom("x\"a\nb\""), Err(yass::AtomSyntaxError::IllegalChrInString { offset: 3, chr: b'\n' }));
+    assert_eq!(yass::check_atom("x\"ab\\\""), Err(yass::AtomSyntaxError::UnfinishedString { offset: 1 }));
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(example_1().validate(), Ok(()));
+    
+    let validation_error = |path: &str, part: yass::AtomPart, error: yass::AtomSyntaxError| {
+        Err(yass::ValidationError { path: yass::Path::parse(path).unwrap(), part: part, error: error, pos: None })
+    };
+    
+    let mut document = example_1();
+    document.header = "a b".to_string();
+    assert_eq!(document.validate(), validation_error("", yass::AtomPart::Header, yass::AtomSyntaxError::IllegalChr { offset: 1, chr: b' ' }));
+    
+    let mut document = example_1();
+    *document.select_mut("item(text)/name").unwrap()[0] = yass::Value::Atom("".to_string());
+    assert_eq!(document.validate(), validation_error("item[1]/name", yass::AtomPart::Value, yass::AtomSyntaxError::Empty));
+    
+    let mut document = example_1();
+    *document.select_mut("sizes/[2]").unwrap()[0] = *yass_value!((as "t(": "1"));
+    assert_eq!(document.validate(), validation_error("sizes/[2]", yass::AtomPart::Tag, yass::AtomSyntaxError::IllegalChr { offset: 1, chr: b'(' }));
+    
+    let value = yass_value!({"a{": "1"});
+    let error = value.validate().unwrap_err();
+    assert_eq!(error.path, yass::Path::from_steps(vec![key("a{")]));
+    assert_eq!(error.part, yass::AtomPart::Key);
+    assert_eq!(error.to_string(), "Invalid key at \"a{\": Illegal character 0x7B at offset 1");
+}
+
+#[test]
+fn test_visit() {
+    struct GatherPaths(Vec<String>);
+    
+    impl yass::Visit for GatherPaths {
+        fn visit_key(&mut self, key: &str, path: &yass::Path) {
+            self.0.push(format!("key {} {}", key, path));
+        }
+        
+        fn visit_atom(&mut self, atom: &str, path: &yass::Path) {
+            self.0.push(format!("atom {} {}", atom, path));
+        }
+        
+        fn visit_tag(&mut self, tag: &str, path: &yass::Path) {
+            self.0.push(format!("tag {} {}", tag, path));
+        }
+    }
+    
+    let document = yass_document!(("test") "a": "1", "b": ["2", (as "t": {"c": "3"})], "a": "4");
+    let mut visitor = GatherPaths(Vec::new());
+    yass::Visit::visit_document(&mut visitor, &document);
+    assert_eq!(visitor.0, vec![
+        "key a a[0]",
+        "atom 1 a[0]",
+        "key b b",
+        "atom 2 b/[0]",
+        "tag t b/[1]",
+        "key c b/[1]/c",
+        "atom 3 b/[1]/c",
+        "key a a[1]",
+        "atom 4 a[1]",
+    ]);
+    for line in visitor.0.iter() {
+        let path = yass::Path::parse(line.rsplit(' ').next().unwrap()).unwrap();
+        assert_eq!(document.select_path(&path).len(), 1);
+    }
+}
+
+#[test]
+fn test_visit_mut() {
+    struct Uppercase;
+    
+    impl yass::VisitMut for Uppercase {
+        fn visit_key_mut(&mut self, key: &mut String, _path: &yass::Path) {
+            *key = key.to_uppercase();
+        }
+        
+        fn visit_value_mut(&mut self, value: &mut yass::Value, path: &yass::Path) {
+            if path.to_string() == "b/[1]" {
+                *value = yass::Value::int(0);
+            } else {
+                yass::visit::walk_value_mut(self, value, path);
+            }
+        }
+    }
+    
+    let mut document = yass_document!(("test") "a": "1", "b": ["2", {"c": "3"}]);
+    yass::VisitMut::visit_document_mut(&mut Uppercase, &mut document);
+    assert_eq!(document, yass_document!(("test") "A": "1", "B": ["2", "0"]));
+}
+
+#[test]
+fn test_fold() {
+    struct RemoveDebug;
+    
+    impl yass::Fold for RemoveDebug {
+        fn fold_struct(&mut self, fields: Vec<yass::StructField>, path: &yass::Path) -> Vec<yass::StructField> {
+            let fields = fields.into_iter().filter(|field| field.key != "debug").collect();
+            yass::visit::fold_struct(self, fields, path)
+        }
+        
+        fn fold_tag(&mut self, tag: String, _path: &yass::Path) -> String {
+            format!("{}-v2", tag)
+        }
+    }
+    
+    let document = yass_document!(("test") "debug": "true", "a": (as "t": {"debug": "1", "b": "2"}));
+    let document = yass::Fold::fold_document(&mut RemoveDebug, document);
+    assert_eq!(document, yass_document!(("test") "a": (as "t-v2": {"b": "2"})));
+}
+
+#[test]
+fn test_diff() {
+    let old = yass_document!(("test")
+        "a": "1",
+        "b": ["x", "y", "z"],
+        "item": "p",
+        "item": "q",
+        "c": (as "t": "1"),
+        "d": {"e": "1"}
+    );
+    let new = yass_document!(("test-2")
+        "d": {"e": "1"},
+        "item": "q",
+        "c": (as "u": "2"),
+        "b": ["x", "w", "z", "v"],
+        "a": ["1"],
+        "f": "1"
+    );
+    let diff = yass::diff(&old, &new);
+    assert_eq!(diff.to_string(), concat!(
+        "~ header: (test) -> (test-2)\n",
+        "- item[0]: p\n",
+        "~ c: (t) -> (u)\n",
+        "~ c: 1 -> 2\n",
+        "~ b/[1]: y -> w\n",
+        "+ b/[3]: v\n",
+        "~ a: 1 -> [1]\n",
+        "+ f: 1\n",
+    ));
+    
+    let path = |s: &str| s.parse::<yass::Path>().unwrap();
+    assert_eq!(diff.changes[1], yass::Change::Removed { path: path("item[0]"), value: yass::Value::Atom("p".to_string()) });
+    assert_eq!(diff.changes[5], yass::Change::Added { path: path("b/[3]"), value: yass::Value::Atom("v".to_string()) });
+    
+    assert!(yass::diff(&old, &old).is_empty());
+    // Reordering fields is not a change, reordering items is.
+    let reordered = yass_document!(("test") "d": {"e": "1"}, "item": "q", "c": (as "t": "1"), "item": "p", "b": ["x", "y", "z"], "a": "1");
+    assert!(yass::diff(&old, &reordered).is_empty());
+    assert!(!yass::diff_values(&yass_value!(["1", "2"]), &yass_value!(["2", "1"])).is_empty());
+}
+
+#[test]
+fn test_diff_arrays() {
+    let array = |items: &[&str]| yass::Value::Array(items.iter().map(|&item| Box::new(yass::Value::from(item))).collect());
+    let path = |s: &str| s.parse::<yass::Path>().unwrap();
+    
+    let diff = yass::diff_values(&array(&["a", "b", "c", "d", "e", "f"]), &array(&["a", "x", "c", "e", "f", "g"]));
+    assert_eq!(diff.changes, [
+        yass::Change::Changed { path: path("[1]"), old: yass::Value::from("b"), new: yass::Value::from("x") },
+        yass::Change::Removed { path: path("[3]"), value: yass::Value::from("d") },
+        yass::Change::Added { path: path("[5]"), value: yass::Value::from("g") },
+    ]);
+    
+    let old = yass::Value::Array((0 .. 300).map(|i| Box::new(yass::Value::from(i))).collect());
+    let mut new_items: Vec<_> = (0 .. 300).filter(|&i| i != 200).map(|i| Box::new(yass::Value::from(i))).collect();
+    new_items.insert(100, Box::new(yass::Value::from("x")));
+    let diff = yass::diff_values(&old, &yass::Value::Array(new_items));
+    assert_eq!(diff.changes, [
+        yass::Change::Added { path: path("[100]"), value: yass::Value::from("x") },
+        yass::Change::Removed { path: path("[200]"), value: yass::Value::from(200) },
+    ]);
+    
+    // Long arrays do not need memory proportional to the product of
+    // their lengths.
+    let old = yass::Value::Array((0 .. 20000).map(|i| Box::new(yass::Value::from(i))).collect());
+    let new = yass::Value::Array((0 .. 20000).map(|i| Box::new(yass::Value::from(if i % 1000 == 500 { -i } else { i }))).collect());
+    let diff = yass::diff_values(&old, &new);
+    assert_eq!(diff.changes.len(), 20);
+    assert_eq!(diff.changes[0], yass::Change::Changed { path: path("[500]"), old: yass::Value::from(500), new: yass::Value::from(-500) });
+}
+
+#[test]
+fn test_diff_to_document() {
+    let old = yass_document!(("test") "a": "1", "b": ["x"]);
+    let new = yass_document!(("test") "a": "2", "c": {"d": "1"});
+    let document = yass::diff(&old, &new).to_document();
+    assert_eq!(document, yass_document!(("diff")
+        "changed": {"path": "\"a\"", "old": "1", "new": "2"},
+        "added": {"path": "\"c\"", "value": {"d": "1"}},
+        "removed": {"path": "\"b\"", "value": ["x"]}
+    ));
+}
+
+#[test]
+fn test_merge() {
+    let base = yass_document!(("config")
+        "name": "base",
+        "server": {"host": "localhost", "port": "80"},
+        "include": "a",
+        "include": "b",
+        "list": ["1", "2"],
+        "mode": (as "fast": {"level": "1", "debug": "false"})
+    );
+    let upper = yass_document!(("config")
+        "include": "c",
+        "server": {"port": "8080"},
+        "list": ["3"],
+        "mode": (as "fast": {"level": "2"})
+    );
+    let layers = [base, upper];
+    
+    let merged = yass::merge(&layers, &yass::MergeOptions::default()).unwrap();
+    assert_eq!(merged.document, yass_document!(("config")
+        "name": "base",
+        "server": {"host": "localhost", "port": "8080"},
+        "include": "a",
+        "include": "b",
+        "list": ["3"],
+        "mode": (as "fast": {"level": "2", "debug": "false"}),
+        "include": "c"
+    ));
+    let layer_of = |index_path: &[usize]| merged.layer_map.get(index_path).map(|map| map.layer());
+    assert_eq!(layer_of(&[0]), Some(0));
+    assert_eq!(layer_of(&[1, 0]), Some(0));
+    assert_eq!(layer_of(&[1, 1]), Some(1));
+    assert_eq!(layer_of(&[4]), Some(1));
+    assert_eq!(layer_of(&[5, 0, 1]), Some(0));
+    assert_eq!(layer_of(&[5, 0, 0]), Some(1));
+    assert_eq!(layer_of(&[6]), Some(1));
+    assert_eq!(merged.layer_map.get(&[6]).unwrap().index_path(), &[0]);
+    assert_eq!(merged.layer_map.get(&[1, 1]).unwrap().index_path(), &[1, 0]);
+    
+    let options = yass::MergeOptions {
+        repeated_keys: yass::RepeatedKeys::Replace,
+        arrays: yass::Arrays::Concat,
+    };
+    let merged = yass::merge(&layers, &options).unwrap();
+    assert_eq!(merged.document, yass_document!(("config")
+        "name": "base",
+        "server": {"host": "localhost", "port": "8080"},
+        "include": "c",
+        "list": ["1", "2", "3"],
+        "mode": (as "fast": {"level": "2", "debug": "false"})
+    ));
+    assert_eq!(merged.layer_map.get(&[3, 1]).unwrap().layer(), 0);
+    assert_eq!(merged.layer_map.get(&[3, 2]).unwrap().index_path(), &[2, 0]);
+    
+    // A different tag replaces the value.
+    let (value, layer_map) = yass::merge_values(&[
+        *yass_value!((as "a": {"x": "1"})),
+        *yass_value!((as "b": {"y": "2"})),
+    ], &options).unwrap();
+    assert_eq!(value, *yass_value!((as "b": {"y": "2"})));
+    assert_eq!(layer_map.layer(), 1);
+}
+
+#[test]
+fn test_merge_fail() {
+    assert_eq!(yass::merge(&[], &yass::MergeOptions::default()), Err(yass::MergeError::NoLayers));
+    let layers = [yass_document!(("a") "x": "1"), yass_document!(("b") "x": "2")];
+    assert_eq!(yass::merge(&layers, &yass::MergeOptions::default()), Err(yass::MergeError::HeaderMismatch {
+        layer: 1,
+        expected: "a".to_string(),
+        found: "b".to_string(),
+    }));
+}
+
+#[test]
+fn test_borrowed() {
+    let document = yass_document!(("test") "a": "1", "b": [(as "t": {"c": "2"})]);
+    let borrowed = document.as_borrowed();
+    assert_eq!(borrowed.header, "test");
+    assert_eq!(borrowed.root_fields[1].value, yass::BorrowedValue::Array(vec![
+        yass::BorrowedValue::Tagged("t", Box::new(yass::BorrowedValue::Struct(vec![
+            yass::BorrowedStructField { key: "c", value: yass::BorrowedValue::Atom("2") },
+        ]))),
+    ]));
+    assert_eq!(borrowed.into_owned(), document);
+}
+
+#[test]
+fn test_flat_document() {
+    use self::yass::flat::FlatValue;
+    
+    let document = yass_document!(("test") "a": "1", "b": [(as "t": {"c": "2", "d": []})], "a": {});
+    let flat = yass::FlatDocument::from_document(&document);
+    assert_eq!(flat.header(), "test");
+    assert_eq!(flat.root_fields().len(), 3);
+    assert_eq!(flat.root_fields().map(|field| field.key().unwrap()).collect::<Vec<_>>(), ["a", "b", "a"]);
+    
+    let tagged = flat.root_fields().get(1).unwrap().child(0).unwrap();
+    match tagged.value() {
+        FlatValue::Tagged(tag, value) => {
+            assert_eq!(tag, "t");
+            assert_eq!(value.child(0).unwrap().as_atom(), Some("2"));
+            assert_eq!(flat.node(value.id()).child(1).unwrap().key(), Some("d"));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(tagged.key(), None);
+    assert_eq!(tagged.to_value(), *yass_value!((as "t": {"c": "2", "d": []})));
+    assert_eq!(yass::Document::from(&flat), document);
+}
+
+#[test]
+fn test_canonical_atom() {
+    assert_eq!(yass::canonical_atom("+007"), "7");
+    assert_eq!(yass::canonical_atom("-0"), "0");
+    assert_eq!(yass::canonical_atom("-0012"), "-12");
+    assert_eq!(yass::canonical_atom("123456789012345678901234567890"), "123456789012345678901234567890");
+    assert_eq!(yass::canonical_atom("1.50"), "1.5");
+    assert_eq!(yass::canonical_atom("15e-1"), "1.5");
+    assert_eq!(yass::canonical_atom("1e999"), "1e999");
+    assert_eq!(yass::canonical_atom("\"\\x41\\u{E9}\""), "\"A\\u{e9}\"");
+    assert_eq!(yass::canonical_atom("\"\\xFF\""), "\"\\xff\"");
+    assert_eq!(yass::canonical_atom("true"), "true");
+    assert_eq!(yass::canonical_atom("symbol"), "symbol");
+}
+
+#[test]
+fn test_canonical() {
+    let unsorted = yass::CanonicalOptions { sort_keys: false };
+    let sorted = yass::CanonicalOptions { sort_keys: true };
+    
+    let document = yass_document!(("test") "b": "+1", "\"a\"": [(as "\"t\"": "2.0")], "b": {"y": "1", "x": "1.00"});
+    assert_eq!(document.to_canonical_string(&unsorted), "(test) b 1 \"a\" [(\"t\")2.0] b {y 1 x 1.0}");
+    assert_eq!(document.to_canonical_string(&sorted), "(test) \"a\" [(\"t\")2.0] b 1 b {x 1.0 y 1}");
+    assert_eq!(
+        document.to_canonical(&sorted),
+        yass_document!(("test") "\"a\"": [(as "\"t\"": "2.0")], "b": "1", "b": {"x": "1.0", "y": "1"})
+    );
+    assert_eq!(yass_value!(["+1", {"b": "0", "a": "-0"}]).to_canonical_string(&sorted), "[1 {a 0 b 0}]");
+    
+    let other = yass_document!(("test") "b": "1", "\"\\x61\"": [(as "\"t\"": "20e-1")], "b": {"x": "1.0", "y": "01"});
+    assert_ne!(document.digest(&unsorted), other.digest(&unsorted));
+    assert_eq!(document.digest(&sorted), other.digest(&sorted));
+    assert_eq!(
+        document.digest(&sorted),
+        yass::Digest::of_bytes(document.to_canonical_string(&sorted).as_bytes())
+    );
+}
+
+#[test]
+fn test_digest() {
+    assert_eq!(
+        yass::Digest::of_bytes(b"").to_string(),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        yass::Digest::of_bytes(b"abc").to_string(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        yass::Digest::of_bytes(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq").to_string(),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    assert_eq!(yass::Digest::of_bytes(&[b'a'; 1000]).as_bytes()[.. 4], [0x41, 0xed, 0xec, 0xe4]);
+}
+
+#[test]
+fn test_source_map() {
+    let mut source_map = yass::SourceMap::new();
+    assert!(source_map.is_empty());
+    let a = source_map.add("a.yass");
+    let b = source_map.add(String::from("dir/b.yass"));
+    assert_ne!(a, b);
+    assert_ne!(a, yass::SourceId::UNKNOWN);
+    assert_eq!(source_map.len(), 2);
+    assert_eq!(source_map.name(a), Some("a.yass"));
+    assert_eq!(source_map.name(yass::SourceId::UNKNOWN), None);
+    assert_eq!(source_map.name(yass::SourceId(3)), None);
+    
+    assert_eq!(yass::Pos::new(0, 0).source, yass::SourceId::UNKNOWN);
+    assert_eq!(yass::Pos::with_source(4, 0, b).display(Some(&source_map)).to_string(), "dir/b.yass:5:1");
+    assert_eq!(yass::Pos::with_source(4, 0, yass::SourceId(3)).display(Some(&source_map)).to_string(), "5:1");
+    assert_eq!(yass::Pos::with_source(4, 0, b).display(None).to_string(), "5:1");
+}
+
+#[test]
+fn test_interpolate_atom() {
+    use std::collections::BTreeMap;
+    
+    let mut variables = BTreeMap::new();
+    variables.insert("HOME".to_string(), "/home/user".to_string());
+    variables.insert("EMPTY".to_string(), String::new());
+    variables.insert("QUOTED".to_string(), "a \"b\"\n".to_string());
+    let interpolate = |atom: &str| yass::interpolate_atom(atom, Some(yass::Pos::new(2, 4)), &variables);
+    
+    assert_eq!(interpolate("\"${HOME}/data\"").unwrap(), "\"/home/user/data\"");
+    assert_eq!(interpolate("\"${QUOTED}\"").unwrap(), "\"a \\\"b\\\"\\n\"");
+    assert_eq!(interpolate("\"${PORT:-8080}\"").unwrap(), "\"8080\"");
+    assert_eq!(interpolate("\"${EMPTY:-x}|${EMPTY}|${HOME:-x}\"").unwrap(), "\"x||/home/user\"");
+    assert_eq!(interpolate("\"${X:-\\u{7D}\\\"}\"").unwrap(), "\"\\u{7D}\\\"\"");
+    assert_eq!(interpolate("\"$${HOME} $HOME $$ \\\\${HOME}\"").unwrap(), "\"${HOME} $HOME $$ \\\\/home/user\"");
+    assert_eq!(interpolate("${HOME}").unwrap(), "${HOME}");
+    
+    assert_eq!(
+        interpolate("\"ab${PORT}\""),
+        Err(yass::InterpolationError::UndefinedVariable { name: "PORT".to_string(), pos: Some(yass::Pos::new(2, 7)) })
+    );
+    assert_eq!(
+        interpolate("\"${1X}\""),
+        Err(yass::InterpolationError::InvalidVariableName { name: "1X".to_string(), pos: Some(yass::Pos::new(2, 5)) })
+    );
+    assert_eq!(interpolate("\"${HOME\""), Err(yass::InterpolationError::UnclosedReference { pos: Some(yass::Pos::new(2, 5)) }));
+    assert_eq!(yass::interpolate_atom("\"${A-B}\"", None, &variables).unwrap_err().pos(), None);
+}
+
+#[test]
+fn test_interpolate() {
+    use std::collections::HashMap;
+    
+    let mut variables = HashMap::new();
+    variables.insert("NAME".to_string(), "example".to_string());
+    
+    let mut document = yass_document!(
+        ("test")
+        "${NAME}": r#""${NAME}""#,
+        "list": [r#""${NAME}""#, "${NAME}", (as "\"${NAME}\"": r#""x${NAME}""#)]
+    );
+    yass::interpolate(&mut document, &yass::PosMap::new(), &variables).unwrap();
+    assert_eq!(document, yass_document!(
+        ("test")
+        "${NAME}": r#""example""#,
+        "list": [r#""example""#, "${NAME}", (as "\"${NAME}\"": r#""xexample""#)]
+    ));
+    
+    let mut source_map = yass::SourceMap::new();
+    let source = source_map.add("config.yass");
+    let mut value = *yass_value!({"a": [r#""${UNDEFINED}""#]});
+    let mut item_pos_map = yass::PosMap::new();
+    let item_pos = yass::Pos::with_source(3, 6, source);
+    item_pos_map.set_value_span(yass::Span::new(item_pos, yass::Pos::with_source(3, 20, source), 30, 44));
+    let mut field_pos_map = yass::PosMap::new();
+    field_pos_map.push_child(item_pos_map);
+    let mut pos_map = yass::PosMap::new();
+    pos_map.push_child(field_pos_map);
+    
+    let error = yass::interpolate_value(&mut value, &pos_map, &variables).unwrap_err();
+    assert_eq!(error.pos(), Some(yass::Pos::with_source(3, 7, source)));
+    assert_eq!(source_map.display(&error).to_string(), "Undefined variable \"UNDEFINED\" at config.yass:4:8");
+    
+    std::env::set_var("YASS_TEST_INTERPOLATE", "from env");
+    assert_eq!(
+        yass::interpolate_atom("\"${YASS_TEST_INTERPOLATE}\"", None, &yass::EnvVariables).unwrap(),
+        "\"from env\""
+    );
+}
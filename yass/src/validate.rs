@@ -0,0 +1,231 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::{Document, Path, Pos, PosMap, SourceMap, StructField, Value};
+use path::field_occurrences;
+
+/// Returns whether `chr` can be part of an atom outside quotes.
+pub fn is_atom_chr(chr: u8) -> bool {
+    match chr {
+        b'_' | b'.' | b',' | b':' | b';' | b'+' | b'-' | b'*' | b'/' => true,
+        b'|' | b'$' | b'#' | b'@' | b'%' | b'=' | b'<' | b'>' => true,
+        b'a' ... b'z' | b'A' ... b'Z' | b'0' ... b'9' | b'?' | b'!' => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `chr` can be part of an atom between quotes.
+pub fn is_string_chr(chr: u8) -> bool {
+    match chr {
+        0x00 ... 0x1F | 0x7F ... 0xFF => false,
+        _ => true,
+    }
+}
+
+/// Lexical error in an atom, key, tag or header. Offsets are counted in
+/// bytes from the beginning of the atom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AtomSyntaxError {
+    Empty,
+    IllegalChr {
+        offset: usize,
+        chr: u8,
+    },
+    IllegalChrInString {
+        offset: usize,
+        chr: u8,
+    },
+    UnfinishedString {
+        offset: usize,
+    },
+}
+
+impl std::fmt::Display for AtomSyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AtomSyntaxError::Empty => {
+                write!(f, "Empty atom")
+            }
+            AtomSyntaxError::IllegalChr { offset, chr } => {
+                write!(f, "Illegal character 0x{:02X} at offset {}", chr, offset)
+            }
+            AtomSyntaxError::IllegalChrInString { offset, chr } => {
+                write!(f, "Illegal character 0x{:02X} in string at offset {}", chr, offset)
+            }
+            AtomSyntaxError::UnfinishedString { offset } => {
+                write!(f, "Unfinished string at offset {}", offset)
+            }
+        }
+    }
+}
+
+/// Checks that `atom` would be read back as a single atom by the
+/// parser.
+pub fn check_atom(atom: &str) -> Result<(), AtomSyntaxError> {
+    let atom = atom.as_bytes();
+    if atom.is_empty() {
+        return Err(AtomSyntaxError::Empty);
+    }
+    
+    let mut i = 0;
+    while i != atom.len() {
+        let chr = atom[i];
+        if chr == b'"' {
+            let begin = i;
+            i += 1;
+            let mut last_was_backslash = false;
+            loop {
+                if i == atom.len() {
+                    return Err(AtomSyntaxError::UnfinishedString { offset: begin });
+                }
+                
+                let chr = atom[i];
+                if !is_string_chr(chr) {
+                    return Err(AtomSyntaxError::IllegalChrInString { offset: i, chr: chr });
+                }
+                
+                i += 1;
+                if !last_was_backslash {
+                    if chr == b'\\' {
+                        last_was_backslash = true;
+                    } else if chr == b'"' {
+                        break;
+                    }
+                } else {
+                    last_was_backslash = false;
+                }
+            }
+        } else if is_atom_chr(chr) {
+            i += 1;
+        } else {
+            return Err(AtomSyntaxError::IllegalChr { offset: i, chr: chr });
+        }
+    }
+    Ok(())
+}
+
+/// Part of a document where an atom is written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AtomPart {
+    Header,
+    Key,
+    Tag,
+    Value,
+}
+
+/// Error returned when a document or value cannot be serialized as
+/// text that parses back to it.
+///
+/// `path` is the location of the field or value whose key, tag or atom
+/// is invalid. `pos` is the position of the invalid header, key, tag or
+/// atom in the original text, when validating with a `PosMap` that
+/// knows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: Path,
+    pub part: AtomPart,
+    pub error: AtomSyntaxError,
+    pub pos: Option<Pos>,
+}
+
+impl std::fmt::Display for ValidationError {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        super::DisplayWithSourceMap::fmt_with_source_map(self, f, None)
+    }
+}
+
+impl super::DisplayWithSourceMap for ValidationError {
+    fn fmt_with_source_map(&self, f: &mut std::fmt::Formatter, source_map: Option<&SourceMap>) -> std::fmt::Result {
+        let part = match self.part {
+            AtomPart::Header => "header",
+            AtomPart::Key => "key",
+            AtomPart::Tag => "tag",
+            AtomPart::Value => "atom",
+        };
+        write!(f, "Invalid {} at \"{}\"", part, self.path)?;
+        if let Some(pos) = self.pos {
+            write!(f, " ({})", pos.display(source_map))?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl std::error::Error for ValidationError {
+    fn description(&self) -> &str {
+        "Invalid atom"
+    }
+}
+
+impl Document {
+    /// Checks that the header, keys, tags and atoms of the document are
+    /// lexically valid, so the document can be serialized and parsed
+    /// back. Returns the first error found.
+    #[inline]
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_with_pos_map(&PosMap::new())
+    }
+    
+    /// Like `validate`, but fills in the position of the error from
+    /// `pos_map`, the map of the document when it was parsed.
+    pub fn validate_with_pos_map(&self, pos_map: &PosMap) -> Result<(), ValidationError> {
+        let mut path = Path::new();
+        check_part(&self.header, &path, AtomPart::Header, pos_map.get_header_span().map(|span| span.start))?;
+        validate_fields(&self.root_fields, &mut path, pos_map)
+    }
+}
+
+impl Value {
+    /// Like `Document::validate`, but for a value. Paths in errors are
+    /// relative to `self`.
+    #[inline]
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_with_pos_map(&PosMap::new())
+    }
+    
+    /// Like `Document::validate_with_pos_map`, but for a value.
+    #[inline]
+    pub fn validate_with_pos_map(&self, pos_map: &PosMap) -> Result<(), ValidationError> {
+        validate_value(self, &mut Path::new(), pos_map)
+    }
+}
+
+fn check_part(atom: &str, path: &Path, part: AtomPart, pos: Option<Pos>) -> Result<(), ValidationError> {
+    check_atom(atom).map_err(|error| ValidationError { path: path.clone(), part: part, error: error, pos: pos })
+}
+
+fn validate_fields(fields: &[StructField], path: &mut Path, pos_map: &PosMap) -> Result<(), ValidationError> {
+    for (i, (field, occurrence)) in fields.iter().zip(field_occurrences(fields)).enumerate() {
+        let len = path.steps().len();
+        let field_pos_map = pos_map.child(i);
+        path.push_key(&field.key, occurrence);
+        check_part(&field.key, path, AtomPart::Key, field_pos_map.get_key_span().map(|span| span.start))?;
+        validate_value(&field.value, path, field_pos_map)?;
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+fn validate_value(value: &Value, path: &mut Path, pos_map: &PosMap) -> Result<(), ValidationError> {
+    match *value {
+        Value::Atom(ref atom) => check_part(atom, path, AtomPart::Value, pos_map.get_value_pos()),
+        Value::Array(ref items) => {
+            for (i, item) in items.iter().enumerate() {
+                let len = path.steps().len();
+                path.push_item(i);
+                validate_value(item, path, pos_map.child(i))?;
+                path.truncate(len);
+            }
+            Ok(())
+        }
+        Value::Tagged(ref tag, ref sub_value) => {
+            check_part(tag, path, AtomPart::Tag, pos_map.get_tag_span().map(|span| span.start))?;
+            validate_value(sub_value, path, pos_map.child(0))
+        }
+        Value::Struct(ref fields) => validate_fields(fields, path, pos_map),
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2018 Eduardo Sánchez Muñoz
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Traversal of documents and values.
+//!
+//! `Visit` walks a tree by reference, `VisitMut` by mutable reference
+//! and `Fold` by value. Every method has a default implementation that
+//! calls the `walk_*` (or `fold_*`) function of this module with the
+//! same name, which visits the children. Overriding a method and calling
+//! that function from it keeps the traversal going.
+//!
+//! Each method receives the path of the node. A struct field and its
+//! value share the same path, and so do a tagged value and its inner
+//! value. The root fields of a document are visited as a struct with an
+//! empty path.
+//!
+//! Example
+//! -------
+//!
+//! ```
+//! #[macro_use]
+//! extern crate yass;
+//!
+//! use yass::visit::Visit;
+//!
+//! struct CountAtoms(usize);
+//!
+//! impl Visit for CountAtoms {
+//!     fn visit_atom(&mut self, _atom: &str, _path: &yass::Path) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let document = yass_document!(("example") "a": "1", "b": ["2", {"c": "3"}]);
+//!     let mut counter = CountAtoms(0);
+//!     counter.visit_document(&document);
+//!     assert_eq!(counter.0, 3);
+//! }
+//! ```
+
+use super::{Document, Path, StructField, Value};
+use path::field_occurrences;
+
+pub trait Visit {
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document)
+    }
+    
+    fn visit_header(&mut self, _header: &str) {}
+    
+    fn visit_field(&mut self, field: &StructField, path: &Path) {
+        walk_field(self, field, path)
+    }
+    
+    fn visit_key(&mut self, _key: &str, _path: &Path) {}
+    
+    fn visit_value(&mut self, value: &Value, path: &Path) {
+        walk_value(self, value, path)
+    }
+    
+    fn visit_atom(&mut self, _atom: &str, _path: &Path) {}
+    
+    fn visit_array(&mut self, items: &[Box<Value>], path: &Path) {
+        walk_array(self, items, path)
+    }
+    
+    fn visit_tagged(&mut self, tag: &str, value: &Value, path: &Path) {
+        walk_tagged(self, tag, value, path)
+    }
+    
+    fn visit_tag(&mut self, _tag: &str, _path: &Path) {}
+    
+    fn visit_struct(&mut self, fields: &[StructField], path: &Path) {
+        walk_struct(self, fields, path)
+    }
+}
+
+pub fn walk_document<V: Visit + ?Sized>(visitor: &mut V, document: &Document) {
+    visitor.visit_header(&document.header);
+    visitor.visit_struct(&document.root_fields, &Path::new());
+}
+
+pub fn walk_field<V: Visit + ?Sized>(visitor: &mut V, field: &StructField, path: &Path) {
+    visitor.visit_key(&field.key, path);
+    visitor.visit_value(&field.value, path);
+}
+
+pub fn walk_value<V: Visit + ?Sized>(visitor: &mut V, value: &Value, path: &Path) {
+    match *value {
+        Value::Atom(ref atom) => visitor.visit_atom(atom, path),
+        Value::Array(ref items) => visitor.visit_array(items, path),
+        Value::Tagged(ref tag, ref sub_value) => visitor.visit_tagged(tag, sub_value, path),
+        Value::Struct(ref fields) => visitor.visit_struct(fields, path),
+    }
+}
+
+pub fn walk_array<V: Visit + ?Sized>(visitor: &mut V, items: &[Box<Value>], path: &Path) {
+    for (i, item) in items.iter().enumerate() {
+        let mut item_path = path.clone();
+        item_path.push_item(i);
+        visitor.visit_value(item, &item_path);
+    }
+}
+
+pub fn walk_tagged<V: Visit + ?Sized>(visitor: &mut V, tag: &str, value: &Value, path: &Path) {
+    visitor.visit_tag(tag, path);
+    visitor.visit_value(value, path);
+}
+
+pub fn walk_struct<V: Visit + ?Sized>(visitor: &mut V, fields: &[StructField], path: &Path) {
+    let mut field_path = path.clone();
+    for (field, occurrence) in fields.iter().zip(field_occurrences(fields)) {
+        field_path.push_key(&field.key, occurrence);
+        visitor.visit_field(field, &field_path);
+        field_path.truncate(path.steps().len());
+    }
+}
+
+pub trait VisitMut {
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document)
+    }
+    
+    fn visit_header_mut(&mut self, _header: &mut String) {}
+    
+    fn visit_field_mut(&mut self, field: &mut StructField, path: &Path) {
+        walk_field_mut(self, field, path)
+    }
+    
+    fn visit_key_mut(&mut self, _key: &mut String, _path: &Path) {}
+    
+    fn visit_value_mut(&mut self, value: &mut Value, path: &Path) {
+        walk_value_mut(self, value, path)
+    }
+    
+    fn visit_atom_mut(&mut self, _atom: &mut String, _path: &Path) {}
+    
+    fn visit_array_mut(&mut self, items: &mut Vec<Box<Value>>, path: &Path) {
+        walk_array_mut(self, items, path)
+    }
+    
+    fn visit_tagged_mut(&mut self, tag: &mut String, value: &mut Value, path: &Path) {
+        walk_tagged_mut(self, tag, value, path)
+    }
+    
+    fn visit_tag_mut(&mut self, _tag: &mut String, _path: &Path) {}
+    
+    fn visit_struct_mut(&mut self, fields: &mut Vec<StructField>, path: &Path) {
+        walk_struct_mut(self, fields, path)
+    }
+}
+
+pub fn walk_document_mut<V: VisitMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    visitor.visit_header_mut(&mut document.header);
+    visitor.visit_struct_mut(&mut document.root_fields, &Path::new());
+}
+
+pub fn walk_field_mut<V: VisitMut + ?Sized>(visitor: &mut V, field: &mut StructField, path: &Path) {
+    visitor.visit_key_mut(&mut field.key, path);
+    visitor.visit_value_mut(&mut field.value, path);
+}
+
+pub fn walk_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, value: &mut Value, path: &Path) {
+    match *value {
+        Value::Atom(ref mut atom) => visitor.visit_atom_mut(atom, path),
+        Value::Array(ref mut items) => visitor.visit_array_mut(items, path),
+        Value::Tagged(ref mut tag, ref mut sub_value) => visitor.visit_tagged_mut(tag, sub_value, path),
+        Value::Struct(ref mut fields) => visitor.visit_struct_mut(fields, path),
+    }
+}
+
+pub fn walk_array_mut<V: VisitMut + ?Sized>(visitor: &mut V, items: &mut Vec<Box<Value>>, path: &Path) {
+    for (i, item) in items.iter_mut().enumerate() {
+        let mut item_path = path.clone();
+        item_path.push_item(i);
+        visitor.visit_value_mut(item, &item_path);
+    }
+}
+
+pub fn walk_tagged_mut<V: VisitMut + ?Sized>(visitor: &mut V, tag: &mut String, value: &mut Value, path: &Path) {
+    visitor.visit_tag_mut(tag, path);
+    visitor.visit_value_mut(value, path);
+}
+
+/// Visits the fields of a struct. The paths are computed before
+/// visiting, so changing keys does not change the paths of the next
+/// fields.
+pub fn walk_struct_mut<V: VisitMut + ?Sized>(visitor: &mut V, fields: &mut Vec<StructField>, path: &Path) {
+    let occurrences = field_occurrences(fields);
+    let mut field_path = path.clone();
+    for (field, occurrence) in fields.iter_mut().zip(occurrences) {
+        field_path.push_key(&field.key, occurrence);
+        visitor.visit_field_mut(field, &field_path);
+        field_path.truncate(path.steps().len());
+    }
+}
+
+pub trait Fold {
+    fn fold_document(&mut self, document: Document) -> Document {
+        fold_document(self, document)
+    }
+    
+    fn fold_header(&mut self, header: String) -> String {
+        header
+    }
+    
+    fn fold_field(&mut self, field: StructField, path: &Path) -> StructField {
+        fold_field(self, field, path)
+    }
+    
+    fn fold_key(&mut self, key: String, _path: &Path) -> String {
+        key
+    }
+    
+    fn fold_value(&mut self, value: Value, path: &Path) -> Value {
+        fold_value(self, value, path)
+    }
+    
+    fn fold_atom(&mut self, atom: String, _path: &Path) -> String {
+        atom
+    }
+    
+    fn fold_array(&mut self, items: Vec<Box<Value>>, path: &Path) -> Vec<Box<Value>> {
+        fold_array(self, items, path)
+    }
+    
+    fn fold_tagged(&mut self, tag: String, value: Value, path: &Path) -> (String, Value) {
+        fold_tagged(self, tag, value, path)
+    }
+    
+    fn fold_tag(&mut self, tag: String, _path: &Path) -> String {
+        tag
+    }
+    
+    fn fold_struct(&mut self, fields: Vec<StructField>, path: &Path) -> Vec<StructField> {
+        fold_struct(self, fields, path)
+    }
+}
+
+pub fn fold_document<F: Fold + ?Sized>(folder: &mut F, document: Document) -> Document {
+    Document {
+        header: folder.fold_header(document.header),
+        root_fields: folder.fold_struct(document.root_fields, &Path::new()),
+    }
+}
+
+pub fn fold_field<F: Fold + ?Sized>(folder: &mut F, field: StructField, path: &Path) -> StructField {
+    StructField {
+        key: folder.fold_key(field.key, path),
+        value: Box::new(folder.fold_value(*field.value, path)),
+    }
+}
+
+pub fn fold_value<F: Fold + ?Sized>(folder: &mut F, value: Value, path: &Path) -> Value {
+    match value {
+        Value::Atom(atom) => Value::Atom(folder.fold_atom(atom, path)),
+        Value::Array(items) => Value::Array(folder.fold_array(items, path)),
+        Value::Tagged(tag, sub_value) => {
+            let (tag, sub_value) = folder.fold_tagged(tag, *sub_value, path);
+            Value::Tagged(tag, Box::new(sub_value))
+        }
+        Value::Struct(fields) => Value::Struct(folder.fold_struct(fields, path)),
+    }
+}
+
+pub fn fold_array<F: Fold + ?Sized>(folder: &mut F, items: Vec<Box<Value>>, path: &Path) -> Vec<Box<Value>> {
+    items.into_iter().enumerate().map(|(i, item)| {
+        let mut item_path = path.clone();
+        item_path.push_item(i);
+        Box::new(folder.fold_value(*item, &item_path))
+    }).collect()
+}
+
+pub fn fold_tagged<F: Fold + ?Sized>(folder: &mut F, tag: String, value: Value, path: &Path) -> (String, Value) {
+    let tag = folder.fold_tag(tag, path);
+    let value = folder.fold_value(value, path);
+    (tag, value)
+}
+
+pub fn fold_struct<F: Fold + ?Sized>(folder: &mut F, fields: Vec<StructField>, path: &Path) -> Vec<StructField> {
+    let occurrences = field_occurrences(&fields);
+    let mut field_path = path.clone();
+    fields.into_iter().zip(occurrences).map(|(field, occurrence)| {
+        field_path.push_key(&field.key, occurrence);
+        let field = folder.fold_field(field, &field_path);
+        field_path.truncate(path.steps().len());
+        field
+    }).collect()
+}